@@ -45,6 +45,20 @@ DASP_EXPORT int crypto_kem_keypair(unsigned char *pk, unsigned char *sk);
 DASP_EXPORT int crypto_kem_enc(unsigned char *ct, unsigned char *ss,
                                const unsigned char *pk);
 
+/**
+ * @brief Derandomized keypair generation from the 64-byte seed d || z.
+ * Matches the PQClean/liboqs `crypto_kem_keypair_derand` interface.
+ */
+DASP_EXPORT int crypto_kem_keypair_derand(unsigned char *pk, unsigned char *sk,
+                                          const unsigned char *coins);
+
+/**
+ * @brief Derandomized encapsulation from the 32-byte message seed m.
+ */
+DASP_EXPORT int crypto_kem_enc_derand(unsigned char *ct, unsigned char *ss,
+                                      const unsigned char *pk,
+                                      const unsigned char *coins);
+
 /**
  * @brief Standard NIST PQC Decapsulation.
  * Recovers the 32-byte shared secret from the ciphertext.
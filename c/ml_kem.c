@@ -124,32 +124,31 @@ static void crypto_pke_enc(uint8_t *ct, const uint8_t *m, const uint8_t *pk,
   poly_compress(ct + 1408, &v, 5);
 }
 
+DASP_EXPORT void ml_kem_verify_constants() {
+  poly_verify_constants();
+}
+
 /**
- * @brief Core ML-KEM Key Generation (ML-KEM.KeyGen).
+ * @brief Derandomized ML-KEM Key Generation (ML-KEM.KeyGen_internal).
  *
- * Generates a public key (pk) and secret key (sk) according to
- * Algorithm 15 in FIPS 203.
+ * Same as crypto_kem_keypair() with the seed supplied by the caller, as in
+ * PQClean and liboqs. Used to check the engine against known-answer vectors.
  *
  * @param pk Output buffer for the public key.
  * @param sk Output buffer for the secret key.
+ * @param coins 64-byte seed d || z.
  * @return 0 on success.
  */
-DASP_EXPORT void ml_kem_verify_constants() {
-  poly_verify_constants();
-}
-
-DASP_EXPORT int crypto_kem_keypair(uint8_t *pk, uint8_t *sk) {
+DASP_EXPORT int crypto_kem_keypair_derand(uint8_t *pk, uint8_t *sk,
+                                          const uint8_t *coins) {
   ml_kem_verify_constants();
-  randombytes_force_reseed();
-  uint8_t d[32], z[32], rho[32], sigma[32];
+  const uint8_t *d = coins, *z = coins + 32;
+  uint8_t rho[32], sigma[32];
   uint8_t Kr[64];
   polyvec a[MLKEM_K], s, e, pkvec;
   int i;
   uint8_t nonce = 0;
 
-  randombytes(d, 32);
-  randombytes(z, 32);
-
   sha3_512(Kr, d, 32);
   memcpy(rho, Kr, 32);
   memcpy(sigma, Kr + 32, 32);
@@ -185,6 +184,28 @@ DASP_EXPORT int crypto_kem_keypair(uint8_t *pk, uint8_t *sk) {
   sha3_256(sk + 3104, pk, 1568);
   memcpy(sk + 3136, z, 32);
 
+  dasp_secure_wipe(Kr, 64);
+  dasp_secure_wipe(sigma, 32);
+  dasp_secure_wipe(&s, sizeof(s));
+  return 0;
+}
+
+/**
+ * @brief Core ML-KEM Key Generation (ML-KEM.KeyGen).
+ *
+ * Generates a public key (pk) and secret key (sk) according to
+ * Algorithm 15 in FIPS 203.
+ *
+ * @param pk Output buffer for the public key.
+ * @param sk Output buffer for the secret key.
+ * @return 0 on success.
+ */
+DASP_EXPORT int crypto_kem_keypair(uint8_t *pk, uint8_t *sk) {
+  uint8_t coins[64];
+  randombytes_force_reseed();
+  randombytes(coins, 64);
+  crypto_kem_keypair_derand(pk, sk, coins);
+  dasp_secure_wipe(coins, 64);
   return 0;
 }
 
@@ -200,20 +221,40 @@ DASP_EXPORT int crypto_kem_keypair(uint8_t *pk, uint8_t *sk) {
  * @return 0 on success.
  */
 DASP_EXPORT int crypto_kem_enc(uint8_t *ct, uint8_t *ss, const uint8_t *pk) {
-  ml_kem_verify_constants();
+  uint8_t m[32];
   randombytes_force_reseed();
-  uint8_t m[32], Kr[64], h_pk[32];
+  randombytes(m, 32);
+  crypto_kem_enc_derand(ct, ss, pk, m);
+  dasp_secure_wipe(m, 32);
+  return 0;
+}
+
+/**
+ * @brief Derandomized ML-KEM Encapsulation (ML-KEM.Encaps_internal).
+ *
+ * @param ct Output encapsulation (ciphertext).
+ * @param ss Output shared secret.
+ * @param pk The public key to encapsulate against.
+ * @param coins 32-byte message seed m.
+ * @return 0 on success.
+ */
+DASP_EXPORT int crypto_kem_enc_derand(uint8_t *ct, uint8_t *ss,
+                                      const uint8_t *pk, const uint8_t *coins) {
+  ml_kem_verify_constants();
+  uint8_t Kr[64], h_pk[32];
   uint8_t K_pre[64];
 
-  randombytes(m, 32);
   sha3_256(h_pk, pk, 1568);
 
-  memcpy(K_pre, m, 32);
+  memcpy(K_pre, coins, 32);
   memcpy(K_pre + 32, h_pk, 32);
   sha3_512(Kr, K_pre, 64);
 
-  crypto_pke_enc(ct, m, pk, Kr + 32);
+  crypto_pke_enc(ct, coins, pk, Kr + 32);
   memcpy(ss, Kr, 32);
+
+  dasp_secure_wipe(Kr, 64);
+  dasp_secure_wipe(K_pre, 64);
   return 0;
 }
 
@@ -24,7 +24,11 @@
 #include "api.h"
 
 DASP_EXPORT int crypto_kem_keypair(uint8_t *pk, uint8_t *sk);
+DASP_EXPORT int crypto_kem_keypair_derand(uint8_t *pk, uint8_t *sk,
+                                          const uint8_t *coins);
 DASP_EXPORT int crypto_kem_enc(uint8_t *ct, uint8_t *ss, const uint8_t *pk);
+DASP_EXPORT int crypto_kem_enc_derand(uint8_t *ct, uint8_t *ss,
+                                      const uint8_t *pk, const uint8_t *coins);
 DASP_EXPORT int crypto_kem_dec(uint8_t *ss, const uint8_t *ct,
                                const uint8_t *sk);
 
@@ -0,0 +1,76 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Envelope and Session Benchmark
+ *
+ * Times single-envelope encrypt/decrypt and session seal/open for small and
+ * bulk payloads, in process, so the figures reflect the engine and its buffer
+ * handling rather than process start-up (the `bench` command of test_suite
+ * times whole CLI runs instead). Run with:
+ *
+ *   cargo bench --bench envelope
+ */
+
+use dasp_crypto::engine::DarkstarCrypt;
+use dasp_crypto::session::{SessionReceiver, SessionSender};
+use std::time::{Duration, Instant};
+
+const SIZES: [(&str, usize, u32); 3] = [
+    ("64 B", 64, 2000),
+    ("64 KiB", 64 * 1024, 200),
+    ("1 MiB", 1024 * 1024, 20),
+];
+
+fn report(what: &str, label: &str, size: usize, iters: u32, elapsed: Duration) {
+    let per_op = elapsed / iters;
+    let mb_s = (size as f64 * iters as f64 / 1048576.0) / elapsed.as_secs_f64();
+    println!(
+        "{:<14} {:>7} {:>10.1} us/op {:>9.1} MB/s",
+        what,
+        label,
+        per_op.as_secs_f64() * 1e6,
+        mb_s
+    );
+}
+
+fn main() {
+    let (pk, sk) = dasp_crypto::kem::keypair().expect("keygen");
+    let pk_hex = hex::encode(&pk[..]);
+    let sk_hex = hex::encode(&sk[..]);
+    let dc = DarkstarCrypt::new();
+
+    for (label, size, iters) in SIZES {
+        let payload = vec![0x5au8; size];
+
+        let start = Instant::now();
+        for _ in 0..iters {
+            std::hint::black_box(dc.encrypt_bytes(&payload, &pk_hex, &[], false).unwrap());
+        }
+        report("encrypt", label, size, iters, start.elapsed());
+
+        let envelope = dc.encrypt_bytes(&payload, &pk_hex, &[], false).unwrap();
+        let start = Instant::now();
+        for _ in 0..iters {
+            std::hint::black_box(dc.decrypt_bytes(&envelope, &sk_hex, None, None).unwrap());
+        }
+        report("decrypt", label, size, iters, start.elapsed());
+    }
+
+    let dem = dasp_crypto::dem::default_dem();
+    for (label, size, iters) in SIZES {
+        let payload = vec![0x5au8; size];
+        let (mut sender, header) = SessionSender::start(&pk_hex, None, dem, 0).unwrap();
+        let mut receiver = SessionReceiver::accept(&header, &sk_hex, None).unwrap();
+
+        let start = Instant::now();
+        let messages: Vec<String> = (0..iters).map(|_| sender.seal(&payload).unwrap()).collect();
+        report("session seal", label, size, iters, start.elapsed());
+
+        let start = Instant::now();
+        for message in &messages {
+            std::hint::black_box(receiver.open(message).unwrap());
+        }
+        report("session open", label, size, iters, start.elapsed());
+    }
+}
@@ -0,0 +1,241 @@
+/**
+ * @file dspna512.h
+ * @brief D-SPNA-512 C API (Rust engine).
+ *
+ * Part of the D-ASP (ASP Cascade 16) Cryptographic Suite.
+ * To the extent possible under law, the author(s) have dedicated all copyright
+ * and related and neighboring rights to this software to the public domain
+ * worldwide. This software is distributed without any warranty.
+ */
+
+#ifndef DSPNA512_H
+#define DSPNA512_H
+
+/* Generated by cbindgen from src/capi.rs and src/lib.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success.
+ */
+#define DSPNA512_OK 0
+
+/**
+ * The module is in the self-test error state.
+ */
+#define DSPNA512_ERR_SELF_TEST -1
+
+/**
+ * The output buffer is too small; `*out_len` holds the required size.
+ */
+#define DSPNA512_ERR_BUFFER_TOO_SMALL -2
+
+/**
+ * A NULL pointer, invalid UTF-8, or a malformed key, envelope or HWID.
+ */
+#define DSPNA512_ERR_INVALID_ARGUMENT -3
+
+/**
+ * MAC verification failed: wrong key, wrong HWID, or a tampered envelope.
+ */
+#define DSPNA512_ERR_INTEGRITY -4
+
+/**
+ * The envelope is older than the TTL, or has no timestamp to check.
+ */
+#define DSPNA512_ERR_EXPIRED -5
+
+/**
+ * The entropy source or DRBG failed.
+ */
+#define DSPNA512_ERR_ENTROPY -6
+
+/**
+ * Encryption refused by the DPA pattern lockout.
+ */
+#define DSPNA512_ERR_LOCKOUT -7
+
+/**
+ * The wall clock is unavailable or set before 1970.
+ */
+#define DSPNA512_ERR_CLOCK -8
+
+/**
+ * An internal error (a Rust panic) was caught at the API boundary.
+ */
+#define DSPNA512_ERR_PANIC -9
+
+/**
+ * Size of a hex public key from `dspna512_keygen`, including the NUL.
+ */
+#define DSPNA512_PUBLIC_KEY_HEX_BYTES ((1568 * 2) + 1)
+
+/**
+ * Size of a hex secret key from `dspna512_keygen`, including the NUL.
+ */
+#define DSPNA512_SECRET_KEY_HEX_BYTES ((3168 * 2) + 1)
+
+/**
+ * Size of a hex public key from `dspna512_keygen_hybrid`, including the NUL.
+ */
+#define DSPNA512_HYBRID_PUBLIC_KEY_HEX_BYTES (((32 + 1568) * 2) + 1)
+
+/**
+ * Size of a hex secret key from `dspna512_keygen_hybrid`, including the NUL.
+ */
+#define DSPNA512_HYBRID_SECRET_KEY_HEX_BYTES (((32 + 3168) * 2) + 1)
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Runs the power-on self-tests if needed. Returns 0 when they passed and -1
+ * when the module is in the error state.
+ */
+int32_t dspna512_self_test(void);
+
+/**
+ * Queries the self-test state without running anything: 0 passed, 1 not yet
+ * run, -1 error state.
+ */
+int32_t dspna512_self_test_status(void);
+
+int32_t crypto_kem_keypair(uint8_t *pk, uint8_t *sk);
+
+/**
+ * Derandomized keygen (PQClean `crypto_kem_keypair_derand`): `coins` is the
+ * 64-byte seed `d || z`, so the keys are fully reproducible.
+ */
+int32_t crypto_kem_keypair_derand(uint8_t *pk, uint8_t *sk, const uint8_t *coins);
+
+int32_t crypto_kem_enc(uint8_t *ct_out, uint8_t *ss_out, const uint8_t *pk);
+
+/**
+ * Derandomized encapsulation (PQClean `crypto_kem_enc_derand`): `coins` is
+ * the 32-byte message seed `m`.
+ */
+int32_t crypto_kem_enc_derand(uint8_t *ct_out,
+                              uint8_t *ss_out,
+                              const uint8_t *pk,
+                              const uint8_t *coins);
+
+int32_t crypto_kem_dec(uint8_t *ss_out, const uint8_t *ct, const uint8_t *sk);
+
+int32_t crypto_sha512(const uint8_t *data, size_t len, uint8_t *out);
+
+int32_t crypto_hmac_sha512(const uint8_t *key,
+                           size_t key_len,
+                           const uint8_t *data,
+                           size_t data_len,
+                           uint8_t *out);
+
+int32_t crypto_sha256(const uint8_t *data, size_t len, uint8_t *out);
+
+int32_t crypto_hmac_sha256(const uint8_t *key,
+                           size_t key_len,
+                           const uint8_t *data,
+                           size_t data_len,
+                           uint8_t *out);
+
+int32_t dspna512_encrypt_block(const uint8_t *input, const uint8_t *key, uint8_t *out);
+
+int32_t dspna512_decrypt_block(const uint8_t *input, const uint8_t *key, uint8_t *out);
+
+/**
+ * Copies the message of the calling thread's last failed call (empty after a
+ * success) into `out` as NUL-terminated text.
+ */
+int32_t dspna512_last_error(char *out, size_t *out_len);
+
+/**
+ * Generates an ML-KEM-1024 keypair as hex text, after the power-on and
+ * pairwise self-tests. Needs DSPNA512_PUBLIC_KEY_HEX_BYTES and
+ * DSPNA512_SECRET_KEY_HEX_BYTES of space; either output NULL queries both sizes.
+ */
+int32_t dspna512_keygen(char *pk_out, size_t *pk_out_len, char *sk_out, size_t *sk_out_len);
+
+/**
+ * Generates an ML-KEM keypair of the parameter set `kem_level` (512, 768 or
+ * 1024) as hex text. The key lengths tell `dspna512_encrypt` which set to
+ * use, and envelopes name it, so every other call works unchanged. Either
+ * output NULL queries both sizes.
+ */
+int32_t dspna512_keygen_ml_kem(uint32_t kem_level,
+                               char *pk_out,
+                               size_t *pk_out_len,
+                               char *sk_out,
+                               size_t *sk_out_len);
+
+/**
+ * Generates an X25519 + ML-KEM-1024 hybrid keypair as hex text. Needs
+ * DSPNA512_HYBRID_PUBLIC_KEY_HEX_BYTES and DSPNA512_HYBRID_SECRET_KEY_HEX_BYTES
+ * of space. The keys work with every other call; envelopes made with a
+ * hybrid public key need the hybrid secret key to open.
+ */
+int32_t dspna512_keygen_hybrid(char *pk_out, size_t *pk_out_len, char *sk_out, size_t *sk_out_len);
+
+/**
+ * Encrypts `payload` to a JSON envelope, optionally bound to `hwid`.
+ *
+ * The envelope length depends only on the payload length and the key type,
+ * so a length query computes it without encrypting, and a call with that
+ * capacity always fits.
+ */
+int32_t dspna512_encrypt(const uint8_t *payload,
+                         size_t payload_len,
+                         const char *pk_hex,
+                         size_t pk_hex_len,
+                         const uint8_t *hwid,
+                         size_t hwid_len,
+                         char *out,
+                         size_t *out_len);
+
+/**
+ * Verifies and decrypts an envelope into `out` as raw bytes.
+ *
+ * `sk` is hex or base64. A `ttl_secs` of 0 skips the replay check. A length
+ * query reads the length the envelope declares without decrypting it, so it
+ * succeeds even when the key or HWID is wrong.
+ */
+int32_t dspna512_decrypt(const char *envelope,
+                         size_t envelope_len,
+                         const char *sk,
+                         size_t sk_len,
+                         const uint8_t *hwid,
+                         size_t hwid_len,
+                         uint64_t ttl_secs,
+                         uint8_t *out,
+                         size_t *out_len);
+
+/**
+ * Re-encrypts an envelope to `new_pk`, bound to `new_hwid` (unbound when
+ * empty), without the plaintext leaving the library. The first seven arguments
+ * open the envelope exactly as in `dspna512_decrypt`. As there, a length query
+ * sizes the output from the declared plaintext length and the new key alone.
+ */
+int32_t dspna512_rebind(const char *envelope,
+                        size_t envelope_len,
+                        const char *sk,
+                        size_t sk_len,
+                        const uint8_t *hwid,
+                        size_t hwid_len,
+                        uint64_t ttl_secs,
+                        const char *new_pk_hex,
+                        size_t new_pk_hex_len,
+                        const uint8_t *new_hwid,
+                        size_t new_hwid_len,
+                        char *out,
+                        size_t *out_len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DSPNA512_H */
@@ -0,0 +1,588 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * ACVP ML-KEM Conformance Runner
+ *
+ * Runs NIST ACVP vector sets for ML-KEM (FIPS 203) against an engine and
+ * builds the ACVP response. Supported modes and group functions:
+ *
+ *   keyGen                          (d, z)  -> ek, dk
+ *   encapDecap / encapsulation      (ek, m) -> c, k
+ *   encapDecap / decapsulation      (dk, c) -> k
+ *   encapDecap / *KeyCheck          ek | dk -> testPassed
+ *
+ * Inputs may sit on the test or, as in older vector sets, on the group.
+ * Expected answers come from an `expectedResults.json` when one is given, and
+ * otherwise from the prompt itself, so an `internalProjection.json` can be
+ * run on its own. Every test in the response carries the computed fields
+ * plus `passed` when an expected answer was available, or `skipped` with the
+ * reason when the engine cannot run it.
+ *
+ * `RustEngine` is the `kem` module and runs all three parameter sets;
+ * `CEngine` loads the C engine's shared library and calls its ML-KEM-1024
+ * `crypto_kem_*` exports.
+ */
+
+use crate::kem::ParameterSet;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// One ACVP test function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    KeyGen,
+    Encapsulation,
+    Decapsulation,
+    EncapsulationKeyCheck,
+    DecapsulationKeyCheck,
+}
+
+impl Function {
+    fn from_group(mode: &str, group: &Value) -> Option<Self> {
+        match (mode, group["function"].as_str()) {
+            ("keyGen", _) => Some(Function::KeyGen),
+            ("encapDecap", Some("encapsulation")) => Some(Function::Encapsulation),
+            ("encapDecap", Some("decapsulation")) => Some(Function::Decapsulation),
+            ("encapDecap", Some("encapsulationKeyCheck")) => Some(Function::EncapsulationKeyCheck),
+            ("encapDecap", Some("decapsulationKeyCheck")) => Some(Function::DecapsulationKeyCheck),
+            _ => None,
+        }
+    }
+
+    /// Response fields, which are also the fields checked against the answers.
+    fn outputs(self) -> &'static [&'static str] {
+        match self {
+            Function::KeyGen => &["ek", "dk"],
+            Function::Encapsulation => &["c", "k"],
+            Function::Decapsulation => &["k"],
+            Function::EncapsulationKeyCheck | Function::DecapsulationKeyCheck => &["testPassed"],
+        }
+    }
+}
+
+/// An ML-KEM implementation under test. Byte lengths are not checked by the
+/// caller; a wrong length must come back as an error. Only parameter sets the
+/// engine `supports` are ever passed in.
+pub trait KemEngine {
+    fn name(&self) -> &str;
+
+    /// Whether the engine can run `function` on `set` at all.
+    fn supports(&self, function: Function, set: ParameterSet) -> bool;
+
+    fn keygen(&self, set: ParameterSet, d: &[u8], z: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String>;
+    fn encaps(&self, set: ParameterSet, ek: &[u8], m: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String>;
+    fn decaps(&self, set: ParameterSet, dk: &[u8], c: &[u8]) -> Result<Vec<u8>, String>;
+    fn check_ek(&self, set: ParameterSet, ek: &[u8]) -> Result<bool, String>;
+    fn check_dk(&self, set: ParameterSet, dk: &[u8]) -> Result<bool, String>;
+}
+
+fn fixed<'a, const N: usize>(bytes: &'a [u8], what: &str) -> Result<&'a [u8; N], String> {
+    bytes
+        .try_into()
+        .map_err(|_| format!("{} must be {} bytes, got {}", what, N, bytes.len()))
+}
+
+fn sized<'a>(bytes: &'a [u8], len: usize, what: &str) -> Result<&'a [u8], String> {
+    match bytes.len() == len {
+        true => Ok(bytes),
+        false => Err(format!(
+            "{} must be {} bytes, got {}",
+            what,
+            len,
+            bytes.len()
+        )),
+    }
+}
+
+/// The `kem` module.
+pub struct RustEngine;
+
+impl KemEngine for RustEngine {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn supports(&self, _function: Function, _set: ParameterSet) -> bool {
+        true
+    }
+
+    fn keygen(&self, set: ParameterSet, d: &[u8], z: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let seed = zeroize::Zeroizing::new([d, z].concat());
+        let (pk, sk) = set.keypair_derand(fixed(&seed, "d || z")?)?;
+        Ok((pk, sk.to_vec()))
+    }
+
+    fn encaps(&self, set: ParameterSet, ek: &[u8], m: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let ek = sized(ek, set.public_key_bytes(), "ek")?;
+        let (ct, ss) = set.encaps_derand(ek, fixed(m, "m")?)?;
+        Ok((ct, ss.to_vec()))
+    }
+
+    fn decaps(&self, set: ParameterSet, dk: &[u8], c: &[u8]) -> Result<Vec<u8>, String> {
+        let dk = sized(dk, set.secret_key_bytes(), "dk")?;
+        let c = sized(c, set.ciphertext_bytes(), "c")?;
+        Ok(set.decaps(dk, c)?.to_vec())
+    }
+
+    fn check_ek(&self, set: ParameterSet, ek: &[u8]) -> Result<bool, String> {
+        Ok(set.check_encapsulation_key(ek))
+    }
+
+    fn check_dk(&self, set: ParameterSet, dk: &[u8]) -> Result<bool, String> {
+        Ok(set.check_decapsulation_key(dk))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use ffi::CEngine;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod ffi {
+    use super::{fixed, Function, KemEngine, ParameterSet};
+    use crate::kem::{CIPHERTEXT_BYTES, PUBLIC_KEY_BYTES, SECRET_KEY_BYTES, SHARED_SECRET_BYTES};
+
+    type KeypairDerand = unsafe extern "C" fn(*mut u8, *mut u8, *const u8) -> i32;
+    type EncDerand = unsafe extern "C" fn(*mut u8, *mut u8, *const u8, *const u8) -> i32;
+    type Dec = unsafe extern "C" fn(*mut u8, *const u8, *const u8) -> i32;
+
+    /// The C engine (`c/dspna512.so` / `dspna512.dll`) through its
+    /// PQClean-style exports. Keygen and encapsulation need the `*_derand`
+    /// entry points; the C engine has no key-check exports, so those groups
+    /// are skipped.
+    pub struct CEngine {
+        keypair_derand: Option<KeypairDerand>,
+        enc_derand: Option<EncDerand>,
+        dec: Dec,
+        // Keeps the function pointers above valid
+        _lib: libloading::Library,
+    }
+
+    impl CEngine {
+        /// Loads the library at `path`, which must be a build of the C engine
+        /// (or anything exporting the same `crypto_kem_*` signatures).
+        pub fn load(path: &std::path::Path) -> Result<Self, String> {
+            // SAFETY: loading runs the library's initializers and the symbol
+            // types are trusted to match `c/api.h`; both are the documented
+            // contract of `load`.
+            unsafe {
+                let lib = libloading::Library::new(path)
+                    .map_err(|e| format!("Cannot load {}: {}", path.display(), e))?;
+                let dec = *lib
+                    .get::<Dec>(b"crypto_kem_dec\0")
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                let keypair_derand = lib
+                    .get::<KeypairDerand>(b"crypto_kem_keypair_derand\0")
+                    .ok()
+                    .map(|f| *f);
+                let enc_derand = lib
+                    .get::<EncDerand>(b"crypto_kem_enc_derand\0")
+                    .ok()
+                    .map(|f| *f);
+                Ok(CEngine {
+                    keypair_derand,
+                    enc_derand,
+                    dec,
+                    _lib: lib,
+                })
+            }
+        }
+    }
+
+    fn status(rc: i32, what: &str) -> Result<(), String> {
+        match rc {
+            0 => Ok(()),
+            _ => Err(format!("{} returned {}", what, rc)),
+        }
+    }
+
+    impl KemEngine for CEngine {
+        fn name(&self) -> &str {
+            "c"
+        }
+
+        fn supports(&self, function: Function, set: ParameterSet) -> bool {
+            set == ParameterSet::MlKem1024
+                && match function {
+                    Function::KeyGen => self.keypair_derand.is_some(),
+                    Function::Encapsulation => self.enc_derand.is_some(),
+                    Function::Decapsulation => true,
+                    Function::EncapsulationKeyCheck | Function::DecapsulationKeyCheck => false,
+                }
+        }
+
+        fn keygen(
+            &self,
+            _set: ParameterSet,
+            d: &[u8],
+            z: &[u8],
+        ) -> Result<(Vec<u8>, Vec<u8>), String> {
+            let f = self
+                .keypair_derand
+                .ok_or("crypto_kem_keypair_derand is not exported")?;
+            let seed = zeroize::Zeroizing::new([d, z].concat());
+            let seed = fixed::<64>(&seed, "d || z")?;
+            let mut pk = vec![0u8; PUBLIC_KEY_BYTES];
+            let mut sk = vec![0u8; SECRET_KEY_BYTES];
+            // SAFETY: every buffer has the length `c/api.h` specifies
+            status(
+                unsafe { f(pk.as_mut_ptr(), sk.as_mut_ptr(), seed.as_ptr()) },
+                "crypto_kem_keypair_derand",
+            )?;
+            Ok((pk, sk))
+        }
+
+        fn encaps(
+            &self,
+            _set: ParameterSet,
+            ek: &[u8],
+            m: &[u8],
+        ) -> Result<(Vec<u8>, Vec<u8>), String> {
+            let f = self
+                .enc_derand
+                .ok_or("crypto_kem_enc_derand is not exported")?;
+            let ek = fixed::<PUBLIC_KEY_BYTES>(ek, "ek")?;
+            let m = fixed::<32>(m, "m")?;
+            let mut ct = vec![0u8; CIPHERTEXT_BYTES];
+            let mut ss = vec![0u8; SHARED_SECRET_BYTES];
+            // SAFETY: as above
+            status(
+                unsafe { f(ct.as_mut_ptr(), ss.as_mut_ptr(), ek.as_ptr(), m.as_ptr()) },
+                "crypto_kem_enc_derand",
+            )?;
+            Ok((ct, ss))
+        }
+
+        fn decaps(&self, _set: ParameterSet, dk: &[u8], c: &[u8]) -> Result<Vec<u8>, String> {
+            let dk = fixed::<SECRET_KEY_BYTES>(dk, "dk")?;
+            let c = fixed::<CIPHERTEXT_BYTES>(c, "c")?;
+            let mut ss = vec![0u8; SHARED_SECRET_BYTES];
+            // SAFETY: as above
+            status(
+                unsafe { (self.dec)(ss.as_mut_ptr(), c.as_ptr(), dk.as_ptr()) },
+                "crypto_kem_dec",
+            )?;
+            Ok(ss)
+        }
+
+        fn check_ek(&self, _set: ParameterSet, _ek: &[u8]) -> Result<bool, String> {
+            Err("The C engine has no encapsulation key check".into())
+        }
+
+        fn check_dk(&self, _set: ParameterSet, _dk: &[u8]) -> Result<bool, String> {
+            Err("The C engine has no decapsulation key check".into())
+        }
+    }
+}
+
+/// A finished run: the response document and the per-case tally.
+pub struct Report {
+    pub response: Value,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Cases with no expected answer to compare against.
+    pub unchecked: usize,
+}
+
+/// ACVP files are either the vector set itself or `[{"acvVersion"}, set]`.
+fn vector_set(doc: &Value) -> Option<&Value> {
+    match doc {
+        Value::Array(items) => items.iter().find(|v| v.get("testGroups").is_some()),
+        Value::Object(_) => doc.get("testGroups").map(|_| doc),
+        _ => None,
+    }
+}
+
+fn groups(set: &Value) -> &[Value] {
+    set["testGroups"].as_array().map_or(&[], Vec::as_slice)
+}
+
+fn tests(group: &Value) -> &[Value] {
+    group["tests"].as_array().map_or(&[], Vec::as_slice)
+}
+
+/// A hex input from the test, falling back to the group.
+fn input(test: &Value, group: &Value, field: &str) -> Result<Vec<u8>, String> {
+    let text = test[field]
+        .as_str()
+        .or_else(|| group[field].as_str())
+        .ok_or_else(|| format!("Missing '{}'", field))?;
+    hex::decode(text).map_err(|_| format!("Invalid hex in '{}'", field))
+}
+
+fn upper(bytes: &[u8]) -> Value {
+    Value::String(hex::encode_upper(bytes))
+}
+
+fn compute(
+    engine: &dyn KemEngine,
+    function: Function,
+    set: ParameterSet,
+    test: &Value,
+    group: &Value,
+) -> Result<Map<String, Value>, String> {
+    let mut out = Map::new();
+    match function {
+        Function::KeyGen => {
+            let (ek, dk) =
+                engine.keygen(set, &input(test, group, "d")?, &input(test, group, "z")?)?;
+            out.insert("ek".into(), upper(&ek));
+            out.insert("dk".into(), upper(&dk));
+        }
+        Function::Encapsulation => {
+            let (c, k) =
+                engine.encaps(set, &input(test, group, "ek")?, &input(test, group, "m")?)?;
+            out.insert("c".into(), upper(&c));
+            out.insert("k".into(), upper(&k));
+        }
+        Function::Decapsulation => {
+            let k = engine.decaps(set, &input(test, group, "dk")?, &input(test, group, "c")?)?;
+            out.insert("k".into(), upper(&k));
+        }
+        Function::EncapsulationKeyCheck => {
+            let ok = engine.check_ek(set, &input(test, group, "ek")?)?;
+            out.insert("testPassed".into(), Value::Bool(ok));
+        }
+        Function::DecapsulationKeyCheck => {
+            let ok = engine.check_dk(set, &input(test, group, "dk")?)?;
+            out.insert("testPassed".into(), Value::Bool(ok));
+        }
+    }
+    Ok(out)
+}
+
+fn same(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::String(a), Value::String(e)) => a.eq_ignore_ascii_case(e),
+        _ => actual == expected,
+    }
+}
+
+/// Runs every test of `prompt` on `engine`. `expected` is the matching
+/// `expectedResults.json`, if the prompt does not carry the answers itself.
+pub fn run(
+    prompt: &Value,
+    expected: Option<&Value>,
+    engine: &dyn KemEngine,
+) -> Result<Report, String> {
+    let set = vector_set(prompt).ok_or("No ACVP vector set found in the prompt")?;
+    if set["algorithm"] != "ML-KEM" {
+        return Err(format!("Unsupported algorithm {}", set["algorithm"]));
+    }
+    let mode = set["mode"].as_str().unwrap_or_default();
+
+    let mut answers: HashMap<(u64, u64), &Value> = HashMap::new();
+    if let Some(doc) = expected {
+        let set = vector_set(doc).ok_or("No ACVP vector set found in the expected results")?;
+        for group in groups(set) {
+            for test in tests(group) {
+                if let (Some(tg), Some(tc)) = (group["tgId"].as_u64(), test["tcId"].as_u64()) {
+                    answers.insert((tg, tc), test);
+                }
+            }
+        }
+    }
+
+    let mut report = Report {
+        response: Value::Null,
+        passed: 0,
+        failed: 0,
+        skipped: 0,
+        unchecked: 0,
+    };
+    let mut response_groups = Vec::new();
+    for group in groups(set) {
+        let function = Function::from_group(mode, group);
+        let set = group["parameterSet"]
+            .as_str()
+            .and_then(|name| ParameterSet::ALL.into_iter().find(|set| set.name() == name));
+        let skip = match (function, set) {
+            (_, None) => Some(format!(
+                "Parameter set {} is not implemented",
+                group["parameterSet"]
+            )),
+            (None, _) => Some(format!("Unsupported function in mode '{}'", mode)),
+            (Some(f), Some(set)) if !engine.supports(f, set) => Some(format!(
+                "{} is not implemented by the {} engine",
+                set,
+                engine.name()
+            )),
+            _ => None,
+        };
+
+        let mut response_tests = Vec::new();
+        for test in tests(group) {
+            let mut result = Map::new();
+            result.insert("tcId".into(), test["tcId"].clone());
+            let (Some(function), Some(set), None) = (function, set, &skip) else {
+                result.insert("skipped".into(), json!(skip));
+                report.skipped += 1;
+                response_tests.push(Value::Object(result));
+                continue;
+            };
+
+            let key = (
+                group["tgId"].as_u64().unwrap_or(0),
+                test["tcId"].as_u64().unwrap_or(0),
+            );
+            let answer = answers.get(&key).copied().unwrap_or(test);
+            let wanted: Vec<_> = function
+                .outputs()
+                .iter()
+                .filter_map(|f| answer.get(*f).map(|v| (*f, v)))
+                .collect();
+
+            let passed = match compute(engine, function, set, test, group) {
+                Ok(fields) => {
+                    let ok = wanted
+                        .iter()
+                        .all(|(f, v)| fields.get(*f).is_some_and(|a| same(a, v)));
+                    result.extend(fields);
+                    ok
+                }
+                Err(e) => {
+                    result.insert("error".into(), Value::String(e));
+                    false
+                }
+            };
+            if wanted.is_empty() {
+                report.unchecked += 1;
+            } else {
+                result.insert("passed".into(), Value::Bool(passed));
+                if passed {
+                    report.passed += 1;
+                } else {
+                    report.failed += 1;
+                }
+            }
+            response_tests.push(Value::Object(result));
+        }
+        response_groups.push(json!({ "tgId": group["tgId"], "tests": response_tests }));
+    }
+
+    report.response = json!([
+        { "acvVersion": "1.0" },
+        {
+            "vsId": set["vsId"],
+            "algorithm": set["algorithm"],
+            "mode": set["mode"],
+            "revision": set["revision"],
+            "testGroups": response_groups,
+        }
+    ]);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kem;
+
+    const PUBLIC_KEY_LEN: usize = kem::PUBLIC_KEY_BYTES;
+
+    /// A keyGen internal projection, and `(ek, dk, c, k)` from its first case.
+    fn key_gen_projection() -> (Value, [String; 4]) {
+        let (d, z) = ([0x0a; 32], [0x0b; 32]);
+        let set = ParameterSet::MlKem1024;
+        let (ek, dk) = RustEngine.keygen(set, &d, &z).unwrap();
+        let (c, k) = RustEngine.encaps(set, &ek, &[0x0c; 32]).unwrap();
+        let projection = json!({
+            "vsId": 7, "algorithm": "ML-KEM", "mode": "keyGen", "revision": "FIPS203",
+            "testGroups": [
+                { "tgId": 1, "testType": "AFT", "parameterSet": "ML-KEM-1024", "tests": [
+                    { "tcId": 1, "d": hex::encode(d), "z": hex::encode(z),
+                      "ek": hex::encode_upper(&ek), "dk": hex::encode_upper(&dk) },
+                    { "tcId": 2, "d": hex::encode(z), "z": hex::encode(d) }
+                ]},
+                { "tgId": 2, "testType": "AFT", "parameterSet": "ML-KEM-512", "tests": [
+                    { "tcId": 3, "d": hex::encode(d), "z": hex::encode(z) }
+                ]},
+                { "tgId": 3, "testType": "AFT", "parameterSet": "Kyber-1024", "tests": [
+                    { "tcId": 4, "d": hex::encode(d), "z": hex::encode(z) }
+                ]}
+            ]
+        });
+        (projection, [ek, dk, c, k].map(hex::encode))
+    }
+
+    fn encap_decap(keys: &[String; 4], k: &str) -> Value {
+        let [ek, dk, c, _] = keys;
+        json!([{ "acvVersion": "1.0" }, {
+            "vsId": 8, "algorithm": "ML-KEM", "mode": "encapDecap", "revision": "FIPS203",
+            "testGroups": [
+                { "tgId": 1, "parameterSet": "ML-KEM-1024", "function": "decapsulation",
+                  "dk": dk, "tests": [{ "tcId": 1, "c": c, "k": k }] },
+                { "tgId": 2, "parameterSet": "ML-KEM-1024", "function": "encapsulationKeyCheck",
+                  "tests": [
+                      { "tcId": 2, "ek": ek, "testPassed": true },
+                      { "tcId": 3, "ek": "ff".repeat(PUBLIC_KEY_LEN), "testPassed": false }
+                  ]}
+            ]
+        }])
+    }
+
+    #[test]
+    fn internal_projection_runs_on_its_own() {
+        let report = run(&key_gen_projection().0, None, &RustEngine).unwrap();
+        assert_eq!(
+            (
+                report.passed,
+                report.failed,
+                report.skipped,
+                report.unchecked
+            ),
+            (1, 0, 1, 2)
+        );
+        let groups = &report.response[1]["testGroups"];
+        let tests = &groups[0]["tests"];
+        assert_eq!(tests[0]["passed"], true);
+        assert_eq!(tests[1]["ek"].as_str().unwrap().len(), 2 * PUBLIC_KEY_LEN);
+        let ek = groups[1]["tests"][0]["ek"].as_str().unwrap();
+        assert_eq!(ek.len(), 2 * ParameterSet::MlKem512.public_key_bytes());
+        assert!(groups[2]["tests"][0]["skipped"].is_string());
+    }
+
+    #[test]
+    fn expected_results_are_matched_by_group_and_case() {
+        let (_, keys) = key_gen_projection();
+        let prompt = encap_decap(&keys, &"00".repeat(32));
+        let answers = encap_decap(&keys, &keys[3]);
+        // The prompt's own (wrong) `k` is ignored once answers are supplied
+        let report = run(&prompt, Some(&answers), &RustEngine).unwrap();
+        assert_eq!((report.passed, report.failed), (3, 0));
+        assert_eq!(
+            report.response[1]["testGroups"][1]["tests"][1]["testPassed"],
+            false
+        );
+
+        let report = run(&prompt, None, &RustEngine).unwrap();
+        assert_eq!((report.passed, report.failed), (2, 1));
+        assert_eq!(
+            report.response[1]["testGroups"][0]["tests"][0]["passed"],
+            false
+        );
+    }
+
+    #[test]
+    fn malformed_inputs_fail_the_case() {
+        let prompt = json!({
+            "algorithm": "ML-KEM", "mode": "encapDecap",
+            "testGroups": [{ "tgId": 1, "parameterSet": "ML-KEM-1024", "function": "encapsulation",
+                "tests": [{ "tcId": 1, "ek": "abcd", "m": "00", "c": "", "k": "" }] }]
+        });
+        let report = run(&prompt, None, &RustEngine).unwrap();
+        assert_eq!(report.failed, 1);
+        let test = &report.response[1]["testGroups"][0]["tests"][0];
+        assert!(test["error"]
+            .as_str()
+            .unwrap()
+            .contains("ek must be 1568 bytes"));
+        assert!(run(
+            &json!({ "algorithm": "ML-DSA", "testGroups": [] }),
+            None,
+            &RustEngine
+        )
+        .is_err());
+    }
+}
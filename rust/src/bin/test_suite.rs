@@ -139,7 +139,7 @@ fn bench_command(term: &mut console::Term) {
     for (engine_name, engine_exe, run_dir) in &engines {
         pb.set_message(format!("[{}] Keygen...", engine_name));
         let output = std::process::Command::new(
-            &rust_dir
+            rust_dir
                 .join("target")
                 .join("release")
                 .join("d-spna-512.exe"),
@@ -455,10 +455,7 @@ fn mitigations_command(term: &mut console::Term) {
                 k, v
             ));
         }
-        log_content.push_str(
-            "
-",
-        );
+        log_content.push('\n');
     }
 
     pb.finish_with_message("✔ Mitigations Audit Complete");
@@ -986,7 +983,7 @@ fn crypto_analysis_command(term: &mut Term) {
     for (engine_name, engine_exe, run_dir) in &engines {
         pb.set_message(format!("[{}] Keygen...", engine_name));
         let output = Command::new(
-            &rust_dir
+            rust_dir
                 .join("target")
                 .join("release")
                 .join("d-spna-512.exe"),
@@ -1150,7 +1147,7 @@ fn crypto_analysis_command(term: &mut Term) {
         pb.set_message(format!("[{}] Cross-Key Avalanche...", engine_name));
 
         let output2 = Command::new(
-            &rust_dir
+            rust_dir
                 .join("target")
                 .join("release")
                 .join("d-spna-512.exe"),
@@ -1227,10 +1224,7 @@ fn crypto_analysis_command(term: &mut Term) {
                 k, v
             ));
         }
-        log_content.push_str(
-            "
-",
-        );
+        log_content.push('\n');
         pb.inc(20);
     }
 
@@ -0,0 +1,425 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Fault-Tolerant Hardware Binding (k-of-n HUB)
+ *
+ * A random 32-byte binding secret is split with Shamir's scheme over GF(2^8).
+ * Every share is wrapped under a pad derived from the KEM shared secret and one
+ * hardware identifier component, so any `k` of the `n` original components are
+ * enough to rebuild the secret. The rebuilt secret replaces the raw HWID as the
+ * salt of the HUB extract.
+ *
+ * Fleet binding reuses the same wrapping to let one envelope open on any of
+ * several machines without duplicating the ciphertext.
+ */
+
+use crate::error::{Error, ErrorKind};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Upper bound on identifier components, one share per component.
+pub const MAX_COMPONENTS: usize = 16;
+
+const SHARE_LEN: usize = 32;
+const TAG_LEN: usize = 8;
+
+/// Binding record stored in the envelope under `"fz"`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FuzzyRecord {
+    /// Minimum number of matching components (tolerance is `n - k`).
+    pub k: u8,
+    /// Number of components the envelope was bound to.
+    pub n: u8,
+    /// Hex encoded `wrapped_share || check_tag`, one per component slot.
+    pub s: Vec<String>,
+}
+
+#[inline(always)]
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    // Branchless Russian-peasant multiply modulo x^8 + x^4 + x^3 + x + 1
+    let mut p = 0u8;
+    for _ in 0..8 {
+        p ^= a & (0u8.wrapping_sub(b & 1));
+        let hi = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (hi & 0x1b);
+        b >>= 1;
+    }
+    p
+}
+
+#[inline(always)]
+fn gf_inv(a: u8) -> u8 {
+    // a^254 == a^-1 in GF(2^8)
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Splits `secret` into `n` shares evaluated at x = 1..=n; any `k` rebuild it.
+fn split(secret: &[u8; SHARE_LEN], k: u8, n: u8) -> Zeroizing<Vec<[u8; SHARE_LEN]>> {
+    let mut coeffs = Zeroizing::new(vec![[0u8; SHARE_LEN]; k as usize]);
+    coeffs[0] = *secret;
+    for c in coeffs.iter_mut().skip(1) {
+        rand::rngs::OsRng.fill_bytes(c);
+    }
+
+    let mut shares = Zeroizing::new(vec![[0u8; SHARE_LEN]; n as usize]);
+    for (i, share) in shares.iter_mut().enumerate() {
+        let x = (i + 1) as u8;
+        for byte in 0..SHARE_LEN {
+            // Horner evaluation from the highest coefficient down
+            let mut y = 0u8;
+            for c in coeffs.iter().rev() {
+                y = gf_mul(y, x) ^ c[byte];
+            }
+            share[byte] = y;
+        }
+    }
+    shares
+}
+
+/// Lagrange interpolation at x = 0 over the supplied `(x, share)` points.
+fn combine(points: &[(u8, [u8; SHARE_LEN])]) -> Zeroizing<[u8; SHARE_LEN]> {
+    let mut secret = Zeroizing::new([0u8; SHARE_LEN]);
+    for (j, (xj, yj)) in points.iter().enumerate() {
+        let mut num = 1u8;
+        let mut den = 1u8;
+        for (m, (xm, _)) in points.iter().enumerate() {
+            if m != j {
+                num = gf_mul(num, *xm);
+                den = gf_mul(den, xm ^ xj);
+            }
+        }
+        let lagrange = gf_mul(num, gf_inv(den));
+        for byte in 0..SHARE_LEN {
+            secret[byte] ^= gf_mul(yj[byte], lagrange);
+        }
+    }
+    secret
+}
+
+fn slot_mac(ss: &[u8], label: &[u8], x: u8, component: &[u8]) -> Zeroizing<[u8; 32]> {
+    let component_hash = Sha256::digest(component);
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(ss).expect("HMAC accepts any key length");
+    mac.update(label);
+    mac.update(&[x]);
+    mac.update(&component_hash);
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+fn binding_salt(secret: &[u8; SHARE_LEN], k: u8, n: u8) -> Zeroizing<[u8; 32]> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(b"dasp-fuzzy-salt");
+    mac.update(&[k, n]);
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+fn validate(k: usize, n: usize) -> Result<(), String> {
+    if n == 0 || n > MAX_COMPONENTS {
+        return Err(format!(
+            "Fuzzy binding needs 1..={} components (got {})",
+            MAX_COMPONENTS, n
+        ));
+    }
+    if k == 0 || k > n {
+        return Err(format!("Invalid fuzzy threshold {} of {}", k, n));
+    }
+    Ok(())
+}
+
+/// Creates a fresh k-of-n binding for `components`.
+///
+/// Returns the 32-byte HUB salt and the record to embed in the envelope.
+pub fn bind(
+    ss: &[u8],
+    components: &[Vec<u8>],
+    threshold: u8,
+) -> Result<(Zeroizing<[u8; 32]>, FuzzyRecord), String> {
+    validate(threshold as usize, components.len())?;
+    let n = components.len() as u8;
+
+    let mut secret = Zeroizing::new([0u8; SHARE_LEN]);
+    rand::rngs::OsRng.fill_bytes(&mut secret[..]);
+    let shares = split(&secret, threshold, n);
+
+    let mut wrapped = Vec::with_capacity(components.len());
+    for (i, (share, component)) in shares.iter().zip(components).enumerate() {
+        let x = (i + 1) as u8;
+        let pad = slot_mac(ss, b"dasp-fuzzy-pad", x, component);
+        let tag = slot_mac(ss, b"dasp-fuzzy-tag", x, component);
+
+        let mut entry = [0u8; SHARE_LEN + TAG_LEN];
+        for b in 0..SHARE_LEN {
+            entry[b] = share[b] ^ pad[b];
+        }
+        entry[SHARE_LEN..].copy_from_slice(&tag[..TAG_LEN]);
+        wrapped.push(hex::encode(entry));
+    }
+
+    let salt = binding_salt(&secret, threshold, n);
+
+    Ok((
+        salt,
+        FuzzyRecord {
+            k: threshold,
+            n,
+            s: wrapped,
+        },
+    ))
+}
+
+/// Rebuilds the HUB salt from the envelope record and the local components.
+///
+/// Components are matched by position; a changed or missing component simply
+/// contributes no share. Fails when fewer than `k` slots match.
+pub fn recover(
+    ss: &[u8],
+    components: &[Vec<u8>],
+    record: &FuzzyRecord,
+) -> Result<Zeroizing<[u8; 32]>, Error> {
+    validate(record.k as usize, record.n as usize)?;
+    if record.s.len() != record.n as usize {
+        return Err("Fuzzy binding record is malformed".into());
+    }
+
+    // Recovered shares are wiped on every return, including malformed-record errors
+    let mut points: Zeroizing<Vec<(u8, [u8; SHARE_LEN])>> =
+        Zeroizing::new(Vec::with_capacity(record.k as usize));
+    for (i, (entry_hex, component)) in record.s.iter().zip(components).enumerate() {
+        if points.len() == record.k as usize {
+            break;
+        }
+        let entry = hex::decode(entry_hex).map_err(|_| "Fuzzy binding record is malformed")?;
+        if entry.len() != SHARE_LEN + TAG_LEN {
+            return Err("Fuzzy binding record is malformed".into());
+        }
+
+        let x = (i + 1) as u8;
+        let tag = slot_mac(ss, b"dasp-fuzzy-tag", x, component);
+        let mut diff = 0u8;
+        for b in 0..TAG_LEN {
+            diff |= tag[b] ^ entry[SHARE_LEN + b];
+        }
+        if diff != 0 {
+            continue;
+        }
+
+        let pad = slot_mac(ss, b"dasp-fuzzy-pad", x, component);
+        let mut share = Zeroizing::new([0u8; SHARE_LEN]);
+        for b in 0..SHARE_LEN {
+            share[b] = entry[b] ^ pad[b];
+        }
+        points.push((x, *share));
+    }
+
+    if points.len() < record.k as usize {
+        return Err(Error::new(
+            ErrorKind::Integrity,
+            format!(
+                "Hardware binding mismatch ({} of {} components required)",
+                record.k, record.n
+            ),
+        ));
+    }
+
+    let secret = combine(&points);
+    Ok(binding_salt(&secret, record.k, record.n))
+}
+
+fn fleet_mac(ss: &[u8], label: &[u8], hwid: &[u8]) -> Zeroizing<[u8; 32]> {
+    let hwid_hash = Sha256::digest(hwid);
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(ss).expect("HMAC accepts any key length");
+    mac.update(label);
+    mac.update(&hwid_hash);
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+fn fleet_salt(secret: &[u8; SHARE_LEN]) -> Zeroizing<[u8; 32]> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(b"dasp-fleet-salt");
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+/// Binds one envelope to any of several HWIDs (fleet binding).
+///
+/// A random binding secret is wrapped once per HWID; the body is encrypted once
+/// under the HUB salt derived from that secret. Returns the salt and the hex
+/// encoded `wrapped_secret || check_tag` entries stored under `"hw"`.
+pub fn bind_any(
+    ss: &[u8],
+    hwids: &[Vec<u8>],
+) -> Result<(Zeroizing<[u8; 32]>, Vec<String>), String> {
+    if hwids.is_empty() || hwids.len() > MAX_COMPONENTS {
+        return Err(format!(
+            "Fleet binding needs 1..={} HWIDs (got {})",
+            MAX_COMPONENTS,
+            hwids.len()
+        ));
+    }
+
+    let mut secret = Zeroizing::new([0u8; SHARE_LEN]);
+    rand::rngs::OsRng.fill_bytes(&mut secret[..]);
+
+    let mut entries = Vec::with_capacity(hwids.len());
+    for hwid in hwids {
+        let pad = fleet_mac(ss, b"dasp-fleet-pad", hwid);
+        let tag = fleet_mac(ss, b"dasp-fleet-tag", hwid);
+
+        let mut entry = [0u8; SHARE_LEN + TAG_LEN];
+        for b in 0..SHARE_LEN {
+            entry[b] = secret[b] ^ pad[b];
+        }
+        entry[SHARE_LEN..].copy_from_slice(&tag[..TAG_LEN]);
+        entries.push(hex::encode(entry));
+    }
+
+    Ok((fleet_salt(&secret), entries))
+}
+
+/// Rebuilds the HUB salt of a fleet-bound envelope from the local HWID.
+pub fn recover_any(
+    ss: &[u8],
+    hwid: &[u8],
+    entries: &[String],
+) -> Result<Zeroizing<[u8; 32]>, Error> {
+    if entries.is_empty() || entries.len() > MAX_COMPONENTS {
+        return Err("Fleet binding record is malformed".into());
+    }
+
+    let tag = fleet_mac(ss, b"dasp-fleet-tag", hwid);
+    for entry_hex in entries {
+        let entry = hex::decode(entry_hex).map_err(|_| "Fleet binding record is malformed")?;
+        if entry.len() != SHARE_LEN + TAG_LEN {
+            return Err("Fleet binding record is malformed".into());
+        }
+
+        let mut diff = 0u8;
+        for b in 0..TAG_LEN {
+            diff |= tag[b] ^ entry[SHARE_LEN + b];
+        }
+        if diff != 0 {
+            continue;
+        }
+
+        let pad = fleet_mac(ss, b"dasp-fleet-pad", hwid);
+        let mut secret = Zeroizing::new([0u8; SHARE_LEN]);
+        for b in 0..SHARE_LEN {
+            secret[b] = entry[b] ^ pad[b];
+        }
+        return Ok(fleet_salt(&secret));
+    }
+
+    Err(Error::new(
+        ErrorKind::Integrity,
+        "Hardware binding mismatch (HWID not in envelope fleet)",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn components(n: u8) -> Vec<Vec<u8>> {
+        (0..n).map(|i| vec![i; 16]).collect()
+    }
+
+    #[test]
+    fn threshold_recovers_with_exactly_k_components() {
+        let ss = [0x11; 32];
+        let comps = components(5);
+        let (salt, record) = bind(&ss, &comps, 3).unwrap();
+
+        // Slots 0 and 3 have changed; the other three still match
+        let mut local = comps.clone();
+        local[0] = vec![0xee; 16];
+        local[3] = vec![0xee; 16];
+        assert_eq!(recover(&ss, &local, &record).unwrap(), salt);
+
+        // A third change leaves k - 1 matching slots
+        local[4] = vec![0xee; 16];
+        let err = recover(&ss, &local, &record).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Integrity);
+        assert_eq!(
+            err.to_string(),
+            "Hardware binding mismatch (3 of 5 components required)"
+        );
+
+        // A different shared secret recovers nothing
+        assert!(recover(&[0x22; 32], &comps, &record).is_err());
+    }
+
+    #[test]
+    fn component_count_and_threshold_are_bounded() {
+        let ss = [0x11; 32];
+        let too_many = components(MAX_COMPONENTS as u8 + 1);
+        assert!(bind(&ss, &too_many, 2).is_err());
+        assert!(bind(&ss, &components(MAX_COMPONENTS as u8), 2).is_ok());
+        assert!(bind(&ss, &components(3), 0).is_err());
+        assert!(bind(&ss, &components(3), 4).is_err());
+        assert!(bind(&ss, &[], 1).is_err());
+    }
+
+    #[test]
+    fn a_tampered_tag_voids_its_slot() {
+        let ss = [0x11; 32];
+        let comps = components(3);
+        let (salt, mut record) = bind(&ss, &comps, 3).unwrap();
+        assert_eq!(recover(&ss, &comps, &record).unwrap(), salt);
+
+        let mut entry = hex::decode(&record.s[1]).unwrap();
+        entry[SHARE_LEN + TAG_LEN - 1] ^= 0x01;
+        record.s[1] = hex::encode(entry);
+        assert!(recover(&ss, &comps, &record)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Hardware binding mismatch"));
+    }
+
+    #[test]
+    fn fleet_envelope_opens_on_any_listed_hwid() {
+        let ss = [0x33; 32];
+        let fleet = components(4);
+        let (salt, entries) = bind_any(&ss, &fleet).unwrap();
+        assert_eq!(entries.len(), fleet.len());
+        for hwid in &fleet {
+            assert_eq!(recover_any(&ss, hwid, &entries).unwrap(), salt);
+        }
+
+        let err = recover_any(&ss, &[0xee; 16], &entries).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Integrity);
+        assert_eq!(
+            err.to_string(),
+            "Hardware binding mismatch (HWID not in envelope fleet)"
+        );
+        assert!(recover_any(&[0x44; 32], &fleet[0], &entries).is_err());
+    }
+
+    #[test]
+    fn fleet_size_and_record_shape_are_checked() {
+        let ss = [0x33; 32];
+        assert!(bind_any(&ss, &[]).is_err());
+        assert!(bind_any(&ss, &components(MAX_COMPONENTS as u8 + 1)).is_err());
+
+        let (_, entries) = bind_any(&ss, &components(2)).unwrap();
+        assert!(recover_any(&ss, &[0], &[]).is_err());
+        let short = vec![entries[0][..entries[0].len() - 2].to_string()];
+        assert_eq!(
+            recover_any(&ss, &[0], &short).unwrap_err().to_string(),
+            "Fleet binding record is malformed"
+        );
+    }
+}
@@ -0,0 +1,862 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Envelope C API
+ *
+ * The whole protocol (keygen, JSON envelope encryption, decryption and
+ * rebinding) behind a C ABI, so foreign callers interoperate with
+ * `DarkstarCrypt` without reimplementing the KDF, CTR, MAC or envelope format.
+ * The header is `include/dspna512.h`, generated by cbindgen (see cbindgen.toml).
+ *
+ * Conventions shared by every call:
+ *
+ *   - Inputs are (pointer, length) pairs; a zero length may pass NULL.
+ *     Keys and envelopes are UTF-8 text, payloads and HWIDs are raw bytes.
+ *   - Outputs are a caller buffer `out` and `out_len`. On entry `*out_len` is
+ *     the capacity; on return it holds the bytes written or needed. Text
+ *     outputs are NUL-terminated and the count includes the NUL.
+ *   - `out == NULL` is a length query: `*out_len` is set and DSPNA512_OK returned.
+ *     A buffer that is too small returns DSPNA512_ERR_BUFFER_TOO_SMALL with
+ *     the required size and nothing written.
+ *   - Results are DSPNA512_OK or a negative DSPNA512_ERR_* code, and
+ *     `dspna512_last_error` returns the message for the calling thread.
+ *
+ * Every export in the crate, including the primitives in lib.rs, validates
+ * its pointers and lengths and runs inside `run`, so a panic is reported as
+ * DSPNA512_ERR_PANIC instead of unwinding into (or aborting) the host. That
+ * needs the unwinding panic strategy, so the release profile keeps it.
+ */
+
+use crate::engine::DarkstarCrypt;
+use crate::error::{kind_of, ErrorKind};
+use ml_kem::EncodedSizeUser;
+use std::cell::RefCell;
+use std::ffi::c_char;
+use zeroize::Zeroizing;
+
+/// Success.
+pub const DSPNA512_OK: i32 = 0;
+/// The module is in the self-test error state.
+pub const DSPNA512_ERR_SELF_TEST: i32 = -1;
+/// The output buffer is too small; `*out_len` holds the required size.
+pub const DSPNA512_ERR_BUFFER_TOO_SMALL: i32 = -2;
+/// A NULL pointer, invalid UTF-8, or a malformed key, envelope or HWID.
+pub const DSPNA512_ERR_INVALID_ARGUMENT: i32 = -3;
+/// MAC verification failed: wrong key, wrong HWID, or a tampered envelope.
+pub const DSPNA512_ERR_INTEGRITY: i32 = -4;
+/// The envelope is older than the TTL, or has no timestamp to check.
+pub const DSPNA512_ERR_EXPIRED: i32 = -5;
+/// The entropy source or DRBG failed.
+pub const DSPNA512_ERR_ENTROPY: i32 = -6;
+/// Encryption refused by the DPA pattern lockout.
+pub const DSPNA512_ERR_LOCKOUT: i32 = -7;
+/// The wall clock is unavailable or set before 1970.
+pub const DSPNA512_ERR_CLOCK: i32 = -8;
+/// An internal error (a Rust panic) was caught at the API boundary.
+pub const DSPNA512_ERR_PANIC: i32 = -9;
+
+/// Size of a hex public key from `dspna512_keygen`, including the NUL.
+pub const DSPNA512_PUBLIC_KEY_HEX_BYTES: usize = 1568 * 2 + 1;
+/// Size of a hex secret key from `dspna512_keygen`, including the NUL.
+pub const DSPNA512_SECRET_KEY_HEX_BYTES: usize = 3168 * 2 + 1;
+/// Size of a hex public key from `dspna512_keygen_hybrid`, including the NUL.
+pub const DSPNA512_HYBRID_PUBLIC_KEY_HEX_BYTES: usize = (32 + 1568) * 2 + 1;
+/// Size of a hex secret key from `dspna512_keygen_hybrid`, including the NUL.
+pub const DSPNA512_HYBRID_SECRET_KEY_HEX_BYTES: usize = (32 + 3168) * 2 + 1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+pub(crate) struct Failure {
+    code: i32,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Failure {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// The DSPNA512_ERR_* code reported for each kind of engine error.
+fn error_code(kind: ErrorKind) -> i32 {
+    match kind {
+        ErrorKind::SelfTest => DSPNA512_ERR_SELF_TEST,
+        ErrorKind::Integrity => DSPNA512_ERR_INTEGRITY,
+        ErrorKind::Expired => DSPNA512_ERR_EXPIRED,
+        ErrorKind::Entropy => DSPNA512_ERR_ENTROPY,
+        ErrorKind::Lockout => DSPNA512_ERR_LOCKOUT,
+        ErrorKind::Clock => DSPNA512_ERR_CLOCK,
+        ErrorKind::InvalidArgument => DSPNA512_ERR_INVALID_ARGUMENT,
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for Failure {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        Failure::new(error_code(kind_of(&*e)), e.to_string())
+    }
+}
+
+impl From<crate::error::Error> for Failure {
+    fn from(e: crate::error::Error) -> Self {
+        Failure::new(error_code(e.kind()), e.to_string())
+    }
+}
+
+impl From<String> for Failure {
+    fn from(e: String) -> Self {
+        Failure::new(DSPNA512_ERR_INVALID_ARGUMENT, e)
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Runs one API call, recording its error message for `dspna512_last_error`.
+/// A panic inside `f` becomes DSPNA512_ERR_PANIC instead of unwinding into C.
+pub(crate) fn run(f: impl FnOnce() -> Result<(), Failure>) -> i32 {
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|p| {
+        Err(Failure::new(
+            DSPNA512_ERR_PANIC,
+            format!("Internal error: {}", panic_message(&*p)),
+        ))
+    });
+    LAST_ERROR.with(|last| {
+        let mut last = last.borrow_mut();
+        last.clear();
+        if let Err(failure) = &res {
+            last.push_str(&failure.message);
+        }
+    });
+    match res {
+        Ok(()) => DSPNA512_OK,
+        Err(failure) => failure.code,
+    }
+}
+
+fn null_error(what: &str) -> Failure {
+    Failure::new(DSPNA512_ERR_INVALID_ARGUMENT, format!("{} is NULL", what))
+}
+
+/// Borrows a (pointer, length) input. Lengths beyond `isize::MAX` cannot
+/// describe a real buffer and are rejected rather than handed to `from_raw_parts`.
+pub(crate) fn bytes_in<'a>(ptr: *const u8, len: usize, what: &str) -> Result<&'a [u8], Failure> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if ptr.is_null() {
+        return Err(null_error(what));
+    }
+    if len > isize::MAX as usize {
+        return Err(Failure::new(
+            DSPNA512_ERR_INVALID_ARGUMENT,
+            format!("{} length {} is out of range", what, len),
+        ));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(ptr, len) })
+}
+
+/// Borrows a fixed-size input such as a key or ciphertext.
+pub(crate) fn fixed_in<'a, const N: usize>(
+    ptr: *const u8,
+    what: &str,
+) -> Result<&'a [u8; N], Failure> {
+    if ptr.is_null() {
+        return Err(null_error(what));
+    }
+    Ok(unsafe { &*ptr.cast::<[u8; N]>() })
+}
+
+/// Fixed-size output of a primitive. Unless `finish` is reached it is zeroed
+/// on drop, so a failed or panicking call never leaves partial output behind.
+pub(crate) struct OutBuf<'a>(Option<&'a mut [u8]>);
+
+impl OutBuf<'_> {
+    pub(crate) fn new(ptr: *mut u8, len: usize, what: &str) -> Result<Self, Failure> {
+        if ptr.is_null() {
+            return Err(null_error(what));
+        }
+        Ok(OutBuf(Some(unsafe {
+            std::slice::from_raw_parts_mut(ptr, len)
+        })))
+    }
+
+    pub(crate) fn finish(mut self, bytes: &[u8]) {
+        if let Some(buf) = self.0.take() {
+            buf.copy_from_slice(bytes);
+        }
+    }
+}
+
+impl Drop for OutBuf<'_> {
+    fn drop(&mut self) {
+        if let Some(buf) = self.0.take() {
+            buf.fill(0);
+        }
+    }
+}
+
+fn text_in<'a>(ptr: *const c_char, len: usize, what: &str) -> Result<&'a str, Failure> {
+    std::str::from_utf8(bytes_in(ptr.cast(), len, what)?).map_err(|_| {
+        Failure::new(
+            DSPNA512_ERR_INVALID_ARGUMENT,
+            format!("{} is not valid UTF-8", what),
+        )
+    })
+}
+
+fn capacity(out_len: *mut usize) -> Result<usize, Failure> {
+    if out_len.is_null() {
+        return Err(null_error("Output length"));
+    }
+    Ok(unsafe { *out_len })
+}
+
+/// Checks that `needed` bytes fit, reporting the size through `out_len`.
+/// Returns false for a length query (`out == NULL`).
+fn reserve(out: *mut u8, out_len: *mut usize, needed: usize) -> Result<bool, Failure> {
+    let cap = capacity(out_len)?;
+    unsafe { *out_len = needed };
+    if out.is_null() {
+        return Ok(false);
+    }
+    if cap < needed {
+        return Err(Failure::new(
+            DSPNA512_ERR_BUFFER_TOO_SMALL,
+            format!("Output buffer too small ({} bytes needed)", needed),
+        ));
+    }
+    Ok(true)
+}
+
+fn write_bytes(bytes: &[u8], out: *mut u8, out_len: *mut usize) -> Result<(), Failure> {
+    if reserve(out, out_len, bytes.len())? {
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len()) };
+    }
+    Ok(())
+}
+
+fn write_text(text: &str, out: *mut c_char, out_len: *mut usize) -> Result<(), Failure> {
+    let out = out.cast::<u8>();
+    if reserve(out, out_len, text.len() + 1)? {
+        unsafe {
+            std::ptr::copy_nonoverlapping(text.as_ptr(), out, text.len());
+            *out.add(text.len()) = 0;
+        }
+    }
+    Ok(())
+}
+
+fn hwid_arg(hwid: &[u8]) -> Option<&[u8]> {
+    (!hwid.is_empty()).then_some(hwid)
+}
+
+/// 0 disables the TTL check.
+fn ttl_arg(ttl_secs: u64) -> Option<u64> {
+    (ttl_secs != 0).then_some(ttl_secs)
+}
+
+/// Copies the message of the calling thread's last failed call (empty after a
+/// success) into `out` as NUL-terminated text.
+#[no_mangle]
+pub extern "C" fn dspna512_last_error(out: *mut c_char, out_len: *mut usize) -> i32 {
+    // Not wrapped in `run`, which would overwrite the message being read
+    std::panic::catch_unwind(|| {
+        let message = LAST_ERROR.with(|last| last.borrow().clone());
+        match write_text(&message, out, out_len) {
+            Ok(()) => DSPNA512_OK,
+            Err(failure) => failure.code,
+        }
+    })
+    .unwrap_or(DSPNA512_ERR_PANIC)
+}
+
+/// Reserves both key outputs, then writes the keys `generate` returns.
+fn keygen_into(
+    pk_out: *mut c_char,
+    pk_out_len: *mut usize,
+    sk_out: *mut c_char,
+    sk_out_len: *mut usize,
+    sizes: (usize, usize),
+    generate: impl FnOnce() -> Result<(String, Zeroizing<String>), Failure>,
+) -> Result<(), Failure> {
+    let pk_fits = reserve(pk_out.cast(), pk_out_len, sizes.0)?;
+    let sk_fits = reserve(sk_out.cast(), sk_out_len, sizes.1)?;
+    if !(pk_fits && sk_fits) {
+        return Ok(());
+    }
+    let (pk, sk) = generate()?;
+    write_text(&pk, pk_out, pk_out_len)?;
+    write_text(&sk, sk_out, sk_out_len)
+}
+
+/// Generates an ML-KEM-1024 keypair as hex text, after the power-on and
+/// pairwise self-tests. Needs DSPNA512_PUBLIC_KEY_HEX_BYTES and
+/// DSPNA512_SECRET_KEY_HEX_BYTES of space; either output NULL queries both sizes.
+#[no_mangle]
+pub extern "C" fn dspna512_keygen(
+    pk_out: *mut c_char,
+    pk_out_len: *mut usize,
+    sk_out: *mut c_char,
+    sk_out_len: *mut usize,
+) -> i32 {
+    let sizes = (DSPNA512_PUBLIC_KEY_HEX_BYTES, DSPNA512_SECRET_KEY_HEX_BYTES);
+    run(|| {
+        keygen_into(pk_out, pk_out_len, sk_out, sk_out_len, sizes, || {
+            let (dk, ek) = crate::selftest::generate_keypair()?;
+            Ok((
+                hex::encode(ek.as_bytes()),
+                crate::codec::encode_hex(&dk.as_bytes()),
+            ))
+        })
+    })
+}
+
+/// Generates an ML-KEM keypair of the parameter set `kem_level` (512, 768 or
+/// 1024) as hex text. The key lengths tell `dspna512_encrypt` which set to
+/// use, and envelopes name it, so every other call works unchanged. Either
+/// output NULL queries both sizes.
+#[no_mangle]
+pub extern "C" fn dspna512_keygen_ml_kem(
+    kem_level: u32,
+    pk_out: *mut c_char,
+    pk_out_len: *mut usize,
+    sk_out: *mut c_char,
+    sk_out_len: *mut usize,
+) -> i32 {
+    run(|| {
+        let set = crate::kem::ParameterSet::from_name(&kem_level.to_string()).ok_or_else(|| {
+            Failure::new(
+                DSPNA512_ERR_INVALID_ARGUMENT,
+                format!("Unsupported ML-KEM parameter set: {}", kem_level),
+            )
+        })?;
+        let sizes = (
+            set.public_key_bytes() * 2 + 1,
+            set.secret_key_bytes() * 2 + 1,
+        );
+        keygen_into(pk_out, pk_out_len, sk_out, sk_out_len, sizes, || {
+            let (pk, sk) = set.keypair()?;
+            Ok((hex::encode(pk), crate::codec::encode_hex(&sk)))
+        })
+    })
+}
+
+/// Generates an X25519 + ML-KEM-1024 hybrid keypair as hex text. Needs
+/// DSPNA512_HYBRID_PUBLIC_KEY_HEX_BYTES and DSPNA512_HYBRID_SECRET_KEY_HEX_BYTES
+/// of space. The keys work with every other call; envelopes made with a
+/// hybrid public key need the hybrid secret key to open.
+#[no_mangle]
+pub extern "C" fn dspna512_keygen_hybrid(
+    pk_out: *mut c_char,
+    pk_out_len: *mut usize,
+    sk_out: *mut c_char,
+    sk_out_len: *mut usize,
+) -> i32 {
+    let sizes = (
+        DSPNA512_HYBRID_PUBLIC_KEY_HEX_BYTES,
+        DSPNA512_HYBRID_SECRET_KEY_HEX_BYTES,
+    );
+    run(|| {
+        keygen_into(pk_out, pk_out_len, sk_out, sk_out_len, sizes, || {
+            let (pk, sk) = crate::hybrid::keypair()?;
+            Ok((hex::encode(pk), crate::codec::encode_hex(&sk[..])))
+        })
+    })
+}
+
+/// Encrypts `payload` to a JSON envelope, optionally bound to `hwid`.
+///
+/// The envelope length depends only on the payload length and the key type,
+/// so a length query computes it without encrypting, and a call with that
+/// capacity always fits.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn dspna512_encrypt(
+    payload: *const u8,
+    payload_len: usize,
+    pk_hex: *const c_char,
+    pk_hex_len: usize,
+    hwid: *const u8,
+    hwid_len: usize,
+    out: *mut c_char,
+    out_len: *mut usize,
+) -> i32 {
+    run(|| {
+        let payload = bytes_in(payload, payload_len, "Payload")?;
+        let pk_hex = text_in(pk_hex, pk_hex_len, "Public key")?;
+        let hwids: Vec<Vec<u8>> = hwid_arg(bytes_in(hwid, hwid_len, "HWID")?)
+            .map(|h| vec![h.to_vec()])
+            .unwrap_or_default();
+        let dc = DarkstarCrypt::new();
+        let needed = dc.envelope_len(payload.len(), pk_hex)? + 1;
+        if !reserve(out.cast(), out_len, needed)? {
+            return Ok(());
+        }
+        let envelope = dc.encrypt_bytes(payload, pk_hex, &hwids, false)?;
+        write_text(&envelope, out, out_len)
+    })
+}
+
+/// Verifies and decrypts an envelope into `out` as raw bytes.
+///
+/// `sk` is hex or base64. A `ttl_secs` of 0 skips the replay check. A length
+/// query reads the length the envelope declares without decrypting it, so it
+/// succeeds even when the key or HWID is wrong.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn dspna512_decrypt(
+    envelope: *const c_char,
+    envelope_len: usize,
+    sk: *const c_char,
+    sk_len: usize,
+    hwid: *const u8,
+    hwid_len: usize,
+    ttl_secs: u64,
+    out: *mut u8,
+    out_len: *mut usize,
+) -> i32 {
+    run(|| {
+        let envelope = text_in(envelope, envelope_len, "Envelope")?;
+        let sk = text_in(sk, sk_len, "Secret key")?;
+        let hwid = bytes_in(hwid, hwid_len, "HWID")?;
+        let dc = DarkstarCrypt::new();
+        if !reserve(out, out_len, dc.plaintext_len(envelope)?)? {
+            return Ok(());
+        }
+        let plaintext = dc.decrypt_bytes(envelope, sk, hwid_arg(hwid), ttl_arg(ttl_secs))?;
+        write_bytes(&plaintext, out, out_len)
+    })
+}
+
+/// Re-encrypts an envelope to `new_pk`, bound to `new_hwid` (unbound when
+/// empty), without the plaintext leaving the library. The first seven arguments
+/// open the envelope exactly as in `dspna512_decrypt`. As there, a length query
+/// sizes the output from the declared plaintext length and the new key alone.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn dspna512_rebind(
+    envelope: *const c_char,
+    envelope_len: usize,
+    sk: *const c_char,
+    sk_len: usize,
+    hwid: *const u8,
+    hwid_len: usize,
+    ttl_secs: u64,
+    new_pk_hex: *const c_char,
+    new_pk_hex_len: usize,
+    new_hwid: *const u8,
+    new_hwid_len: usize,
+    out: *mut c_char,
+    out_len: *mut usize,
+) -> i32 {
+    run(|| {
+        let envelope = text_in(envelope, envelope_len, "Envelope")?;
+        let sk = text_in(sk, sk_len, "Secret key")?;
+        let hwid = bytes_in(hwid, hwid_len, "HWID")?;
+        let new_pk_hex = text_in(new_pk_hex, new_pk_hex_len, "New public key")?;
+        let new_hwids: Zeroizing<Vec<Vec<u8>>> = Zeroizing::new(
+            hwid_arg(bytes_in(new_hwid, new_hwid_len, "New HWID")?)
+                .map(|h| vec![h.to_vec()])
+                .unwrap_or_default(),
+        );
+        let dc = DarkstarCrypt::new();
+        let needed = dc.envelope_len(dc.plaintext_len(envelope)?, new_pk_hex)? + 1;
+        if !reserve(out.cast(), out_len, needed)? {
+            return Ok(());
+        }
+        let plaintext = dc.decrypt_bytes(envelope, sk, hwid_arg(hwid), ttl_arg(ttl_secs))?;
+        let rebound = dc.encrypt_bytes(&plaintext, new_pk_hex, &new_hwids, false)?;
+        write_text(&rebound, out, out_len)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::{null, null_mut};
+
+    type Keygen = extern "C" fn(*mut c_char, *mut usize, *mut c_char, *mut usize) -> i32;
+
+    fn keygen() -> (String, String) {
+        keygen_with(dspna512_keygen)
+    }
+
+    fn keygen_with(f: Keygen) -> (String, String) {
+        let mut pk = vec![0u8; DSPNA512_HYBRID_PUBLIC_KEY_HEX_BYTES];
+        let mut sk = vec![0u8; DSPNA512_HYBRID_SECRET_KEY_HEX_BYTES];
+        let (mut pk_len, mut sk_len) = (pk.len(), sk.len());
+        let rc = f(
+            pk.as_mut_ptr().cast(),
+            &mut pk_len,
+            sk.as_mut_ptr().cast(),
+            &mut sk_len,
+        );
+        assert_eq!(rc, DSPNA512_OK);
+        let text = |b: &[u8], n: usize| {
+            assert_eq!(b[n - 1], 0);
+            String::from_utf8(b[..n - 1].to_vec()).unwrap()
+        };
+        (text(&pk, pk_len), text(&sk, sk_len))
+    }
+
+    fn encrypt(payload: &[u8], pk: &str, hwid: &[u8]) -> Result<String, i32> {
+        let mut len = 0;
+        let call = |out: *mut c_char, len: &mut usize| {
+            dspna512_encrypt(
+                payload.as_ptr(),
+                payload.len(),
+                pk.as_ptr().cast(),
+                pk.len(),
+                hwid.as_ptr(),
+                hwid.len(),
+                out,
+                len,
+            )
+        };
+        match call(null_mut(), &mut len) {
+            DSPNA512_OK => {}
+            rc => return Err(rc),
+        }
+        let mut out = vec![0u8; len];
+        assert_eq!(call(out.as_mut_ptr().cast(), &mut len), DSPNA512_OK);
+        assert_eq!(len, out.len());
+        out.pop();
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn decrypt(envelope: &str, sk: &str, hwid: &[u8], ttl: u64) -> Result<Vec<u8>, i32> {
+        let mut out = vec![0u8; envelope.len()];
+        let mut len = out.len();
+        let rc = dspna512_decrypt(
+            envelope.as_ptr().cast(),
+            envelope.len(),
+            sk.as_ptr().cast(),
+            sk.len(),
+            hwid.as_ptr(),
+            hwid.len(),
+            ttl,
+            out.as_mut_ptr(),
+            &mut len,
+        );
+        if rc != DSPNA512_OK {
+            return Err(rc);
+        }
+        out.truncate(len);
+        Ok(out)
+    }
+
+    fn last_error() -> String {
+        let mut buf = [0u8; 256];
+        let mut len = buf.len();
+        assert_eq!(
+            dspna512_last_error(buf.as_mut_ptr().cast(), &mut len),
+            DSPNA512_OK
+        );
+        String::from_utf8(buf[..len - 1].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn round_trip_matches_engine() {
+        let (pk, sk) = keygen();
+        let payload = [0u8, 1, 2, 0xff, 0xfe];
+        let env = encrypt(&payload, &pk, b"hwid-1").unwrap();
+        assert_eq!(decrypt(&env, &sk, b"hwid-1", 60).unwrap(), payload);
+
+        let env = DarkstarCrypt::new()
+            .encrypt("text", &pk, &[], false)
+            .unwrap();
+        assert_eq!(decrypt(&env, &sk, &[], 0).unwrap(), b"text");
+    }
+
+    #[test]
+    fn smaller_parameter_sets_round_trip() {
+        let mut pk = vec![0u8; DSPNA512_PUBLIC_KEY_HEX_BYTES];
+        let mut sk = vec![0u8; DSPNA512_SECRET_KEY_HEX_BYTES];
+        let (mut pk_len, mut sk_len) = (pk.len(), sk.len());
+        let rc = dspna512_keygen_ml_kem(
+            512,
+            pk.as_mut_ptr().cast(),
+            &mut pk_len,
+            sk.as_mut_ptr().cast(),
+            &mut sk_len,
+        );
+        assert_eq!(rc, DSPNA512_OK);
+        assert_eq!((pk_len, sk_len), (800 * 2 + 1, 1632 * 2 + 1));
+        let pk = String::from_utf8(pk[..pk_len - 1].to_vec()).unwrap();
+        let sk = String::from_utf8(sk[..sk_len - 1].to_vec()).unwrap();
+
+        let env = encrypt(b"sensor reading", &pk, b"hwid-1").unwrap();
+        assert!(env.contains("\"kem\":\"ML-KEM-512\""));
+        assert_eq!(decrypt(&env, &sk, b"hwid-1", 0).unwrap(), b"sensor reading");
+
+        let rc = dspna512_keygen_ml_kem(1000, null_mut(), &mut pk_len, null_mut(), &mut sk_len);
+        assert_eq!(rc, DSPNA512_ERR_INVALID_ARGUMENT);
+        assert!(last_error().contains("1000"));
+    }
+
+    #[test]
+    fn hybrid_keys_work_with_every_call() {
+        let (pk, sk) = keygen_with(dspna512_keygen_hybrid);
+        assert_eq!(pk.len() + 1, DSPNA512_HYBRID_PUBLIC_KEY_HEX_BYTES);
+        assert_eq!(sk.len() + 1, DSPNA512_HYBRID_SECRET_KEY_HEX_BYTES);
+        let env = encrypt(b"payload", &pk, b"hwid-1").unwrap();
+        assert!(env.contains("\"xct\""));
+        assert_eq!(decrypt(&env, &sk, b"hwid-1", 0).unwrap(), b"payload");
+
+        let (_, plain_sk) = keygen();
+        assert_eq!(
+            decrypt(&env, &plain_sk, b"hwid-1", 0),
+            Err(DSPNA512_ERR_INVALID_ARGUMENT)
+        );
+        assert!(last_error().contains("hybrid"));
+    }
+
+    #[test]
+    fn failures_map_to_error_codes() {
+        let (pk, sk) = keygen();
+        let env = encrypt(b"payload", &pk, b"hwid-1").unwrap();
+        assert_eq!(
+            decrypt(&env, &sk, b"hwid-2", 0),
+            Err(DSPNA512_ERR_INTEGRITY)
+        );
+        assert_eq!(last_error(), "Integrity Check Failed");
+        assert_eq!(encrypt(b"x", "zz", &[]), Err(DSPNA512_ERR_INVALID_ARGUMENT));
+        assert_eq!(
+            decrypt("{}", &sk, &[], 0),
+            Err(DSPNA512_ERR_INVALID_ARGUMENT)
+        );
+
+        let mut len = 0;
+        let rc = dspna512_decrypt(null(), 4, null(), 0, null(), 0, 0, null_mut(), &mut len);
+        assert_eq!(rc, DSPNA512_ERR_INVALID_ARGUMENT);
+        assert_eq!(last_error(), "Envelope is NULL");
+
+        decrypt(&env, &sk, b"hwid-1", 0).unwrap();
+        assert_eq!(last_error(), "");
+    }
+
+    fn code_of(e: impl Into<Box<dyn std::error::Error>>) -> i32 {
+        Failure::from(e.into()).code
+    }
+
+    #[test]
+    fn every_error_kind_has_its_code() {
+        for (kind, code) in [
+            (ErrorKind::SelfTest, DSPNA512_ERR_SELF_TEST),
+            (ErrorKind::Integrity, DSPNA512_ERR_INTEGRITY),
+            (ErrorKind::Expired, DSPNA512_ERR_EXPIRED),
+            (ErrorKind::Entropy, DSPNA512_ERR_ENTROPY),
+            (ErrorKind::Lockout, DSPNA512_ERR_LOCKOUT),
+            (ErrorKind::Clock, DSPNA512_ERR_CLOCK),
+            (ErrorKind::InvalidArgument, DSPNA512_ERR_INVALID_ARGUMENT),
+        ] {
+            let e = crate::error::Error::new(kind, "message");
+            assert_eq!(Failure::from(e.clone()).code, code, "{:?}", kind);
+            assert_eq!(code_of(e), code, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn codes_do_not_depend_on_the_message() {
+        for message in [
+            "SELF_TEST_ERROR: module in error state",
+            "Integrity Check Failed",
+            "Payload Expired (Replay Protection)",
+            "DPA_LOCKOUT",
+            "System clock is set before the Unix epoch",
+        ] {
+            assert_eq!(
+                code_of(message),
+                DSPNA512_ERR_INVALID_ARGUMENT,
+                "{}",
+                message
+            );
+            assert_eq!(
+                Failure::from(message.to_string()).code,
+                DSPNA512_ERR_INVALID_ARGUMENT
+            );
+        }
+        let e = crate::error::Error::new(ErrorKind::Lockout, "anything");
+        assert_eq!(code_of(e), DSPNA512_ERR_LOCKOUT);
+    }
+
+    #[test]
+    fn tag_and_binding_failures_map_to_integrity() {
+        for dem in crate::dem::ALL {
+            let err = dem
+                .open(&[1; 32], b"head", &mut [0u8; 4], b"", &[0; 16])
+                .unwrap_err();
+            assert_eq!(code_of(err), DSPNA512_ERR_INTEGRITY, "{}", dem.name());
+        }
+        let (_, entries) = crate::binding::bind_any(&[1; 32], &[vec![1; 16]]).unwrap();
+        let err = crate::binding::recover_any(&[1; 32], &[2; 16], &entries).unwrap_err();
+        assert_eq!(code_of(err), DSPNA512_ERR_INTEGRITY);
+    }
+
+    #[test]
+    fn entropy_failures_map_to_entropy() {
+        // A seeded DRBG cannot reseed itself, so every request fails
+        let drbg = crate::drbg::HmacDrbg::from_seed(&[1; 32], &[2; 16], &[])
+            .with_prediction_resistance(true);
+        let (rc, _) = crate::drbg::with_drbg(drbg, || {
+            let mut pk = vec![0u8; DSPNA512_PUBLIC_KEY_HEX_BYTES];
+            let mut sk = vec![0u8; DSPNA512_SECRET_KEY_HEX_BYTES];
+            let (mut pk_len, mut sk_len) = (pk.len(), sk.len());
+            dspna512_keygen(
+                pk.as_mut_ptr().cast(),
+                &mut pk_len,
+                sk.as_mut_ptr().cast(),
+                &mut sk_len,
+            )
+        });
+        assert_eq!(rc, DSPNA512_ERR_ENTROPY, "{}", last_error());
+        assert!(last_error().starts_with("Deterministic DRBG"));
+    }
+
+    #[test]
+    fn short_buffer_reports_required_size() {
+        let (pk, sk) = keygen();
+        let env = encrypt(b"0123456789", &pk, &[]).unwrap();
+        let mut out = [0xaau8; 4];
+        let mut len = out.len();
+        let rc = dspna512_decrypt(
+            env.as_ptr().cast(),
+            env.len(),
+            sk.as_ptr().cast(),
+            sk.len(),
+            null(),
+            0,
+            0,
+            out.as_mut_ptr(),
+            &mut len,
+        );
+        assert_eq!(rc, DSPNA512_ERR_BUFFER_TOO_SMALL);
+        assert_eq!(len, 10);
+        assert_eq!(out, [0xaa; 4]);
+
+        let mut len = out.len();
+        let rc = dspna512_encrypt(
+            b"0123456789".as_ptr(),
+            10,
+            pk.as_ptr().cast(),
+            pk.len(),
+            null(),
+            0,
+            out.as_mut_ptr().cast(),
+            &mut len,
+        );
+        assert_eq!(rc, DSPNA512_ERR_BUFFER_TOO_SMALL);
+        assert_eq!(len, env.len() + 1);
+        assert_eq!(out, [0xaa; 4]);
+    }
+
+    #[test]
+    fn rebind_moves_envelope_to_new_key_and_hwid() {
+        let (pk, sk) = keygen();
+        let (new_pk, new_sk) = keygen();
+        let env = encrypt(b"payload", &pk, b"old").unwrap();
+        let call = |out: *mut c_char, len: &mut usize| {
+            dspna512_rebind(
+                env.as_ptr().cast(),
+                env.len(),
+                sk.as_ptr().cast(),
+                sk.len(),
+                b"old".as_ptr(),
+                3,
+                0,
+                new_pk.as_ptr().cast(),
+                new_pk.len(),
+                b"new".as_ptr(),
+                3,
+                out,
+                len,
+            )
+        };
+        let mut len = 0;
+        assert_eq!(call(null_mut(), &mut len), DSPNA512_OK);
+        let mut out = vec![0u8; len];
+        assert_eq!(call(out.as_mut_ptr().cast(), &mut len), DSPNA512_OK);
+        let rebound = String::from_utf8(out[..len - 1].to_vec()).unwrap();
+        assert_eq!(decrypt(&rebound, &new_sk, b"new", 0).unwrap(), b"payload");
+        assert_eq!(
+            decrypt(&rebound, &sk, b"new", 0),
+            Err(DSPNA512_ERR_INTEGRITY)
+        );
+    }
+
+    #[test]
+    fn length_queries_do_not_open_the_envelope() {
+        let (pk, _) = keygen();
+        let (new_pk, wrong_sk) = keygen();
+        let env = encrypt(b"0123456789", &pk, b"hwid-1").unwrap();
+
+        // The wrong key and HWID only fail once there is a buffer to fill
+        let mut len = 0;
+        let rc = dspna512_decrypt(
+            env.as_ptr().cast(),
+            env.len(),
+            wrong_sk.as_ptr().cast(),
+            wrong_sk.len(),
+            b"hwid-2".as_ptr(),
+            6,
+            0,
+            null_mut(),
+            &mut len,
+        );
+        assert_eq!((rc, len), (DSPNA512_OK, 10));
+        assert_eq!(
+            decrypt(&env, &wrong_sk, b"hwid-2", 0),
+            Err(DSPNA512_ERR_INTEGRITY)
+        );
+
+        let mut len = 0;
+        let rc = dspna512_rebind(
+            env.as_ptr().cast(),
+            env.len(),
+            wrong_sk.as_ptr().cast(),
+            wrong_sk.len(),
+            null(),
+            0,
+            0,
+            new_pk.as_ptr().cast(),
+            new_pk.len(),
+            null(),
+            0,
+            null_mut(),
+            &mut len,
+        );
+        assert_eq!(rc, DSPNA512_OK);
+        assert_eq!(len, encrypt(b"0123456789", &new_pk, &[]).unwrap().len() + 1);
+
+        let odd = env.replacen("\"data\":\"", "\"data\":\"0", 1);
+        let rc = dspna512_decrypt(
+            odd.as_ptr().cast(),
+            odd.len(),
+            wrong_sk.as_ptr().cast(),
+            wrong_sk.len(),
+            null(),
+            0,
+            0,
+            null_mut(),
+            &mut len,
+        );
+        assert_eq!(rc, DSPNA512_ERR_INVALID_ARGUMENT);
+    }
+}
+
+// Property-based cases are far too slow under Miri
+#[cfg(all(test, not(miri)))]
+mod hostile;
@@ -0,0 +1,248 @@
+//! Hostile-input harness for every `extern "C"` export.
+//!
+//! Each call must come back with an error code instead of panicking, reading
+//! through a NULL pointer or leaving partial output behind.
+
+use super::*;
+use crate::*;
+use proptest::prelude::*;
+use std::ptr::{null, null_mut};
+
+const ALL_ERRORS: [i32; 9] = [
+    DSPNA512_ERR_SELF_TEST,
+    DSPNA512_ERR_BUFFER_TOO_SMALL,
+    DSPNA512_ERR_INVALID_ARGUMENT,
+    DSPNA512_ERR_INTEGRITY,
+    DSPNA512_ERR_EXPIRED,
+    DSPNA512_ERR_ENTROPY,
+    DSPNA512_ERR_LOCKOUT,
+    DSPNA512_ERR_CLOCK,
+    DSPNA512_ERR_PANIC,
+];
+
+/// A pointer that is non-NULL but must never be read through.
+fn dangling() -> *const u8 {
+    std::ptr::NonNull::dangling().as_ptr()
+}
+
+#[test]
+fn null_pointers_are_rejected_by_every_export() {
+    let mut out = [0u8; 4096];
+    let mut len = out.len();
+    let o = out.as_mut_ptr();
+    let results = [
+        crypto_kem_keypair(null_mut(), null_mut()),
+        crypto_kem_keypair(o, null_mut()),
+        crypto_kem_enc(null_mut(), null_mut(), null()),
+        crypto_kem_enc(o, o, null()),
+        crypto_kem_keypair_derand(o, o, null()),
+        crypto_kem_enc_derand(o, o, o, null()),
+        crypto_kem_dec(null_mut(), null(), null()),
+        crypto_kem_dec(o, null(), null()),
+        crypto_sha256(null(), 1, o),
+        crypto_sha256(null(), 0, null_mut()),
+        crypto_sha512(null(), 1, o),
+        crypto_hmac_sha256(null(), 1, null(), 0, o),
+        crypto_hmac_sha512(null(), 0, null(), 1, o),
+        dspna512_encrypt_block(null(), null(), o),
+        dspna512_decrypt_block(o, null(), o),
+        dspna512_keygen(null_mut(), null_mut(), null_mut(), null_mut()),
+        dspna512_keygen_hybrid(o.cast(), null_mut(), o.cast(), &mut len),
+        dspna512_keygen_ml_kem(768, o.cast(), &mut len, o.cast(), null_mut()),
+        dspna512_encrypt(null(), 1, null(), 0, null(), 0, o.cast(), &mut len),
+        dspna512_encrypt(null(), 0, null(), 0, null(), 0, o.cast(), null_mut()),
+        dspna512_decrypt(null(), 1, null(), 0, null(), 0, 0, o, &mut len),
+        dspna512_rebind(
+            null(),
+            0,
+            null(),
+            0,
+            null(),
+            7,
+            0,
+            null(),
+            0,
+            null(),
+            0,
+            o.cast(),
+            &mut len,
+        ),
+        dspna512_last_error(o.cast(), null_mut()),
+    ];
+    for (i, rc) in results.into_iter().enumerate() {
+        assert_eq!(rc, DSPNA512_ERR_INVALID_ARGUMENT, "call #{}", i);
+    }
+    assert!(last_error_text().contains("NULL"));
+}
+
+#[test]
+fn out_of_range_lengths_are_rejected() {
+    let mut out = [0xaau8; 64];
+    let huge = usize::MAX;
+    assert_eq!(
+        crypto_sha256(dangling(), huge, out.as_mut_ptr()),
+        DSPNA512_ERR_INVALID_ARGUMENT
+    );
+    assert_eq!(
+        crypto_hmac_sha512(dangling(), 1 << 63, dangling(), 0, out.as_mut_ptr()),
+        DSPNA512_ERR_INVALID_ARGUMENT
+    );
+    // A failed primitive never leaves stale bytes in its output
+    assert_eq!(out, [0u8; 64]);
+
+    let mut len = out.len();
+    let rc = dspna512_decrypt(
+        dangling().cast(),
+        huge,
+        null(),
+        0,
+        null(),
+        0,
+        0,
+        out.as_mut_ptr(),
+        &mut len,
+    );
+    assert_eq!(rc, DSPNA512_ERR_INVALID_ARGUMENT);
+    assert!(last_error_text().contains("out of range"));
+}
+
+#[test]
+fn garbage_keys_and_ciphertexts_do_not_panic() {
+    let junk = [0xffu8; 3168];
+    let mut ct = [0u8; 1568];
+    let mut ss = [0u8; 32];
+    let rc = crypto_kem_enc(ct.as_mut_ptr(), ss.as_mut_ptr(), junk.as_ptr());
+    assert!(rc == DSPNA512_OK || ALL_ERRORS.contains(&rc));
+    // Decapsulation of garbage yields the implicit-rejection secret
+    assert_eq!(
+        crypto_kem_dec(ss.as_mut_ptr(), junk.as_ptr(), junk.as_ptr()),
+        DSPNA512_OK
+    );
+
+    let key = [0u8; 1024];
+    let mut block = [0u8; 64];
+    assert_eq!(
+        dspna512_encrypt_block(junk.as_ptr(), key.as_ptr(), block.as_mut_ptr()),
+        DSPNA512_OK
+    );
+}
+
+#[test]
+fn panics_are_caught_at_the_boundary() {
+    let mut out = [0xaau8; 32];
+    let rc = run(|| {
+        let _guard = OutBuf::new(out.as_mut_ptr(), 32, "out")?;
+        panic!("injected fault");
+    });
+    assert_eq!(rc, DSPNA512_ERR_PANIC);
+    assert_eq!(last_error_text(), "Internal error: injected fault");
+    assert_eq!(out, [0u8; 32]);
+
+    // The thread is still usable afterwards
+    assert_eq!(run(|| Ok(())), DSPNA512_OK);
+    assert_eq!(last_error_text(), "");
+}
+
+fn last_error_text() -> String {
+    let mut buf = [0u8; 512];
+    let mut len = buf.len();
+    assert_eq!(
+        dspna512_last_error(buf.as_mut_ptr().cast(), &mut len),
+        DSPNA512_OK
+    );
+    String::from_utf8_lossy(&buf[..len - 1]).into_owned()
+}
+
+fn envelope_like() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..512),
+        "\\{\"data\":\"[0-9a-f]{0,64}\",\"ct\":\"[0-9a-f]{0,64}\",\"mac\":\"[0-9a-f]{0,64}\"(,\"ts\":[0-9]{1,20})?\\}"
+            .prop_map(String::into_bytes),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn hostile_decrypt_returns_an_error(
+        envelope in envelope_like(),
+        sk in prop::collection::vec(any::<u8>(), 0..128),
+        hwid in prop::collection::vec(any::<u8>(), 0..64),
+        ttl in any::<u64>(),
+        cap in 0usize..64,
+    ) {
+        let mut out = vec![0u8; cap];
+        let mut len = cap;
+        let rc = dspna512_decrypt(
+            envelope.as_ptr().cast(),
+            envelope.len(),
+            sk.as_ptr().cast(),
+            sk.len(),
+            hwid.as_ptr(),
+            hwid.len(),
+            ttl,
+            out.as_mut_ptr(),
+            &mut len,
+        );
+        prop_assert!(ALL_ERRORS.contains(&rc) && rc != DSPNA512_ERR_PANIC, "rc = {}", rc);
+    }
+
+    #[test]
+    fn hostile_encrypt_and_rebind_return_an_error(
+        payload in prop::collection::vec(any::<u8>(), 0..256),
+        pk in prop::collection::vec(any::<u8>(), 0..256),
+        envelope in envelope_like(),
+        cap in 0usize..64,
+    ) {
+        let mut out = vec![0u8; cap];
+        let mut len = cap;
+        let rc = dspna512_encrypt(
+            payload.as_ptr(),
+            payload.len(),
+            pk.as_ptr().cast(),
+            pk.len(),
+            null(),
+            0,
+            out.as_mut_ptr().cast(),
+            &mut len,
+        );
+        prop_assert_eq!(rc, DSPNA512_ERR_INVALID_ARGUMENT);
+
+        let mut len = cap;
+        let rc = dspna512_rebind(
+            envelope.as_ptr().cast(),
+            envelope.len(),
+            pk.as_ptr().cast(),
+            pk.len(),
+            null(),
+            0,
+            0,
+            pk.as_ptr().cast(),
+            pk.len(),
+            payload.as_ptr(),
+            payload.len(),
+            out.as_mut_ptr().cast(),
+            &mut len,
+        );
+        prop_assert!(ALL_ERRORS.contains(&rc) && rc != DSPNA512_ERR_PANIC, "rc = {}", rc);
+    }
+
+    #[test]
+    fn arbitrary_primitive_inputs_never_panic(
+        key in prop::collection::vec(any::<u8>(), 0..300),
+        data in prop::collection::vec(any::<u8>(), 0..300),
+    ) {
+        let mut out = [0u8; 64];
+        prop_assert_eq!(crypto_sha256(data.as_ptr(), data.len(), out.as_mut_ptr()), DSPNA512_OK);
+        prop_assert_eq!(crypto_sha512(data.as_ptr(), data.len(), out.as_mut_ptr()), DSPNA512_OK);
+        prop_assert_eq!(
+            crypto_hmac_sha256(key.as_ptr(), key.len(), data.as_ptr(), data.len(), out.as_mut_ptr()),
+            DSPNA512_OK
+        );
+        prop_assert_eq!(
+            crypto_hmac_sha512(key.as_ptr(), key.len(), data.as_ptr(), data.len(), out.as_mut_ptr()),
+            DSPNA512_OK
+        );
+    }
+}
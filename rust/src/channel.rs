@@ -0,0 +1,1000 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Secure Channel: Mutual KEM Authentication over a Byte Stream
+ *
+ * Two endpoints that each hold a static KEM keypair and know the other's
+ * public key agree on traffic keys with three encapsulations, then exchange
+ * DEM-sealed frames in both directions. Any reliable, ordered transport will
+ * do; `TcpStream` and `UnixStream` are the ones the CLI uses.
+ *
+ *   client -> server  hello     {"v","eph","ct"[,"xct"][,"dem"]}
+ *   server -> client  reply     {"ect","sct"[,"sxct"]}
+ *   server -> client  finished  {"fin"[,"sig"]}
+ *   client -> server  finished  {"fin"[,"sig"]}
+ *
+ * `ct` encapsulates to the server's static key, `ect` to the client's fresh
+ * ephemeral key `eph` and `sct` to the client's static key. Only the server
+ * can recover the first secret and only the client the other two, so a
+ * correct `fin` proves its sender holds the static secret key the peer
+ * expects. The ephemeral key is wiped as soon as it has been used: once it is
+ * gone, recorded traffic stays sealed even if both static keys leak later.
+ *
+ *   T        = SHA-256 over "dasp-channel-v1", pk_server, pk_client and every
+ *              handshake frame so far, each with a u32 length prefix
+ *   master   = HUB blend of ss_server || ss_eph || ss_client, salted with T_reply
+ *   fin_s    = HMAC(HMAC(master, "dasp-channel-server-fin"), T_reply)
+ *   fin_c    = HMAC(HMAC(master, "dasp-channel-client-fin"), T_server_fin)
+ *   CK_c2s   = HMAC(master, "dasp-channel-c2s" || T_server_fin), likewise s2c
+ *
+ * An endpoint configured with its peer's signature identity (ML-DSA-65 with
+ * the `ml-dsa` feature, see `mldsa.rs`) also requires `sig`, the peer's
+ * signature over the same hash its `fin` covers.
+ *
+ * After the handshake every frame is
+ *
+ *   len (u32, big endian) || body || tag
+ *
+ * sealed by the client's DEM under the next message key of its direction's
+ * chain (the session ratchet step) with `direction || seq` as the header, so
+ * frames cannot be dropped, reordered or reflected. An empty body is the
+ * close notification; a stream that ends without one has been truncated.
+ */
+
+use crate::dem::Dem;
+use crate::engine::{decapsulate, encapsulate, hub_blend};
+use crate::kem::ParameterSet;
+use crate::secret::SecretBuffer;
+use crate::session::{decode_ct, decode_x_ct, hmac, step};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::io::{self, Read, Write};
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const PROTOCOL: &str = "dasp-channel-v1";
+
+/// Largest payload carried by one frame; longer writes are split.
+pub const MAX_FRAME: usize = 1 << 20;
+
+/// Handshake frames are small JSON objects; anything bigger is refused
+/// before it is buffered.
+const MAX_HANDSHAKE_FRAME: usize = 64 * 1024;
+
+/// Signs the handshake transcript with a long-term signature key.
+pub trait Identity: Send + Sync {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// Checks the peer's transcript signature against its expected key.
+pub trait PeerIdentity: Send + Sync {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), String>;
+}
+
+/// Transports a [`SecureStream`] can be split over; both halves share the
+/// underlying socket.
+pub trait Duplex: Read + Write + Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl Duplex for std::net::TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        std::net::TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl Duplex for std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        std::os::unix::net::UnixStream::try_clone(self)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Hello {
+    v: String,
+    eph: String,
+    ct: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xct: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dem: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Reply {
+    ect: String,
+    sct: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sxct: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Finished {
+    fin: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sig: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Role {
+    Client,
+    Server,
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Role::Client => "client",
+            Role::Server => "server",
+        }
+    }
+}
+
+/// A static KEM key in any of the forms the envelope accepts.
+struct StaticKey {
+    set: ParameterSet,
+    hybrid: bool,
+}
+
+impl StaticKey {
+    fn of_public(pk: &[u8]) -> Option<Self> {
+        if pk.len() == crate::hybrid::PUBLIC_KEY_BYTES {
+            return Some(StaticKey {
+                set: ParameterSet::MlKem1024,
+                hybrid: true,
+            });
+        }
+        ParameterSet::from_public_key_len(pk.len()).map(|set| StaticKey { set, hybrid: false })
+    }
+}
+
+/// Running hash of everything both ends have agreed on so far.
+#[derive(Clone)]
+struct Transcript(Sha256);
+
+impl Transcript {
+    fn new(server_pk: &[u8], client_pk: &[u8]) -> Self {
+        let mut transcript = Transcript(Sha256::new());
+        for part in [PROTOCOL.as_bytes(), server_pk, client_pk] {
+            transcript.absorb(part);
+        }
+        transcript
+    }
+
+    fn absorb(&mut self, part: &[u8]) {
+        self.0.update((part.len() as u32).to_be_bytes());
+        self.0.update(part);
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        self.0.clone().finalize().into()
+    }
+}
+
+/// One side of a channel: its own static key, the peer's public key and the
+/// optional signature identities.
+pub struct Endpoint {
+    sk: SecretBuffer,
+    key: StaticKey,
+    pk: Vec<u8>,
+    peer_pk: Vec<u8>,
+    peer_key: StaticKey,
+    dem: &'static dyn Dem,
+    identity: Option<Box<dyn Identity>>,
+    peer_identity: Option<Box<dyn PeerIdentity>>,
+}
+
+impl Endpoint {
+    /// # Arguments
+    /// * `sk` - This endpoint's ML-KEM (any parameter set) or hybrid secret
+    ///   key, hex or base64 encoded.
+    /// * `peer_pk_hex` - The public key the other end must prove it holds.
+    pub fn new(sk: &str, peer_pk_hex: &str) -> Result<Self, Box<dyn Error>> {
+        let (sk, key) = load_secret_key(sk)?;
+        let pk = public_key_of(&sk, &key);
+        let peer_pk = hex::decode(peer_pk_hex).map_err(|_| "Invalid peer public key hex")?;
+        let peer_key = StaticKey::of_public(&peer_pk).ok_or_else(|| {
+            format!(
+                "Invalid peer public key length (Arg length: {})",
+                peer_pk_hex.len()
+            )
+        })?;
+        Ok(Endpoint {
+            sk,
+            key,
+            pk,
+            peer_pk,
+            peer_key,
+            dem: crate::dem::default_dem(),
+            identity: None,
+            peer_identity: None,
+        })
+    }
+
+    /// Frame cipher to propose when connecting; the listener follows the client.
+    pub fn with_dem(mut self, dem: &'static dyn Dem) -> Self {
+        self.dem = dem;
+        self
+    }
+
+    /// Signs every handshake with this identity as well.
+    pub fn with_identity(mut self, identity: Box<dyn Identity>) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Requires the peer to sign its handshake with this identity.
+    pub fn with_peer_identity(mut self, peer: Box<dyn PeerIdentity>) -> Self {
+        self.peer_identity = Some(peer);
+        self
+    }
+
+    /// This endpoint's static public key, hex encoded, as its peer needs it.
+    pub fn public_key(&self) -> String {
+        hex::encode(&self.pk)
+    }
+
+    /// Runs the client side of the handshake on `stream`.
+    pub fn connect<S: Read + Write>(
+        &self,
+        mut stream: S,
+    ) -> Result<SecureStream<S>, Box<dyn Error>> {
+        crate::selftest::check()?;
+        let mut transcript = Transcript::new(&self.peer_pk, &self.pk);
+
+        let eph_set = self.peer_key.set;
+        let (eph_pk, eph_sk) = eph_set.keypair()?;
+        let (ct, x_ct, _, ss_server) = encapsulate(&self.peer_pk)?;
+        let dem_name =
+            (self.dem.name() != crate::dem::default_dem().name()).then(|| self.dem.name());
+        let hello = Hello {
+            v: PROTOCOL.to_string(),
+            eph: hex::encode(&eph_pk),
+            ct: hex::encode(&ct),
+            xct: x_ct.map(hex::encode),
+            dem: dem_name.map(str::to_string),
+        };
+        send_handshake(&mut stream, &mut transcript, &serde_json::to_vec(&hello)?)?;
+
+        let reply: Reply = serde_json::from_slice(&recv_handshake(&mut stream, &mut transcript)?)?;
+        let ect = decode_ct(&reply.ect, eph_set)?;
+        let ss_eph = eph_set.decaps(&eph_sk, &ect)?;
+        drop(eph_sk);
+        let sct = decode_ct(&reply.sct, self.key.set)?;
+        let sxct = decode_x_ct(reply.sxct.as_deref())?;
+        if sxct.is_some() != self.key.hybrid {
+            return Err("Handshake reply does not match the client key".into());
+        }
+        let ss_client = decapsulate(&self.sk, &sct, sxct, self.key.set)?;
+
+        let t_reply = transcript.hash();
+        let master = master_secret(&t_reply, &[&ss_server[..], &ss_eph[..], &ss_client[..]])?;
+        let theirs: Finished =
+            serde_json::from_slice(&recv_handshake(&mut stream, &mut transcript)?)?;
+        self.check_finished(&master, Role::Server, &t_reply, &theirs)?;
+
+        let t_server_fin = transcript.hash();
+        let ours = self.finished(&master, Role::Client, &t_server_fin)?;
+        send_handshake(&mut stream, &mut transcript, &serde_json::to_vec(&ours)?)?;
+        SecureStream::new(stream, self.dem, &master, Role::Client, &t_server_fin)
+    }
+
+    /// Runs the server side of the handshake on `stream`.
+    pub fn accept<S: Read + Write>(
+        &self,
+        mut stream: S,
+    ) -> Result<SecureStream<S>, Box<dyn Error>> {
+        crate::selftest::check()?;
+        let mut transcript = Transcript::new(&self.pk, &self.peer_pk);
+
+        let hello: Hello = serde_json::from_slice(&recv_handshake(&mut stream, &mut transcript)?)?;
+        if hello.v != PROTOCOL {
+            return Err(format!("Unsupported channel protocol '{}'", hello.v).into());
+        }
+        let dem = match &hello.dem {
+            Some(name) => crate::dem::ALL
+                .into_iter()
+                .find(|dem| dem.name() == name)
+                .ok_or_else(|| format!("Unsupported DEM '{}'", name))?,
+            None => crate::dem::default_dem(),
+        };
+        let ct = decode_ct(&hello.ct, self.key.set)?;
+        let x_ct = decode_x_ct(hello.xct.as_deref())?;
+        if x_ct.is_some() != self.key.hybrid {
+            return Err("Handshake hello does not match the server key".into());
+        }
+        let ss_server = decapsulate(&self.sk, &ct, x_ct, self.key.set)?;
+
+        let eph = hex::decode(&hello.eph).map_err(|_| "Invalid ephemeral key hex")?;
+        let eph_set = ParameterSet::from_public_key_len(eph.len())
+            .filter(|set| set.check_encapsulation_key(&eph))
+            .ok_or("Invalid ephemeral key")?;
+        let (ect, ss_eph) = eph_set.encaps(&eph)?;
+        let (sct, sxct, _, ss_client) = encapsulate(&self.peer_pk)?;
+        let reply = Reply {
+            ect: hex::encode(&ect),
+            sct: hex::encode(&sct),
+            sxct: sxct.map(hex::encode),
+        };
+        send_handshake(&mut stream, &mut transcript, &serde_json::to_vec(&reply)?)?;
+
+        let t_reply = transcript.hash();
+        let master = master_secret(&t_reply, &[&ss_server[..], &ss_eph[..], &ss_client[..]])?;
+        let ours = self.finished(&master, Role::Server, &t_reply)?;
+        send_handshake(&mut stream, &mut transcript, &serde_json::to_vec(&ours)?)?;
+
+        let t_server_fin = transcript.hash();
+        let theirs: Finished =
+            serde_json::from_slice(&recv_handshake(&mut stream, &mut transcript)?)?;
+        self.check_finished(&master, Role::Client, &t_server_fin, &theirs)?;
+        SecureStream::new(stream, dem, &master, Role::Server, &t_server_fin)
+    }
+
+    fn finished(&self, master: &[u8], role: Role, hash: &[u8; 32]) -> Result<Finished, String> {
+        let fin = finished_mac(master, role, hash)?.finalize().into_bytes();
+        let sig = match &self.identity {
+            Some(identity) => Some(hex::encode(identity.sign(&signed_message(role, hash))?)),
+            None => None,
+        };
+        Ok(Finished {
+            fin: hex::encode(fin),
+            sig,
+        })
+    }
+
+    fn check_finished(
+        &self,
+        master: &[u8],
+        role: Role,
+        hash: &[u8; 32],
+        theirs: &Finished,
+    ) -> Result<(), String> {
+        let fin = hex::decode(&theirs.fin).map_err(|_| "Invalid finished MAC hex".to_string())?;
+        finished_mac(master, role, hash)?
+            .verify_slice(&fin)
+            .map_err(|_| {
+                format!(
+                    "The {} handshake MAC does not match: one side holds a key the other does not expect",
+                    role.name()
+                )
+            })?;
+        if let Some(peer) = &self.peer_identity {
+            let sig = theirs
+                .sig
+                .as_deref()
+                .ok_or_else(|| format!("The {} did not sign the handshake", role.name()))?;
+            let sig = hex::decode(sig).map_err(|_| "Invalid signature hex".to_string())?;
+            peer.verify(&signed_message(role, hash), &sig)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a secret key of any supported kind, telling them apart by length.
+fn load_secret_key(sk: &str) -> Result<(SecretBuffer, StaticKey), String> {
+    let hybrid = StaticKey {
+        set: ParameterSet::MlKem1024,
+        hybrid: true,
+    };
+    let candidates = std::iter::once((crate::hybrid::SECRET_KEY_BYTES, hybrid)).chain(
+        ParameterSet::ALL
+            .into_iter()
+            .map(|set| (set.secret_key_bytes(), StaticKey { set, hybrid: false })),
+    );
+    for (len, key) in candidates {
+        if let Ok(decoded) = crate::codec::decode_secret(sk, len) {
+            let dk = &decoded[if key.hybrid {
+                crate::hybrid::X25519_BYTES
+            } else {
+                0
+            }..];
+            if !key.set.check_decapsulation_key(dk) {
+                return Err("Invalid secret key".into());
+            }
+            return Ok((decoded, key));
+        }
+    }
+    Err(format!(
+        "Invalid secret key length (Arg length: {})",
+        sk.len()
+    ))
+}
+
+/// The public key inside a secret key: an ML-KEM decapsulation key embeds
+/// its encapsulation key, and the X25519 half of a hybrid key is recomputed.
+fn public_key_of(sk: &[u8], key: &StaticKey) -> Vec<u8> {
+    let mut pk = Vec::new();
+    let mut dk = sk;
+    if key.hybrid {
+        let (x_sk, rest) = sk.split_at(crate::hybrid::X25519_BYTES);
+        let x_sk: zeroize::Zeroizing<[u8; 32]> =
+            zeroize::Zeroizing::new(x_sk.try_into().expect("split at X25519_BYTES"));
+        let x_sk = x25519_dalek::StaticSecret::from(*x_sk);
+        pk.extend_from_slice(x25519_dalek::PublicKey::from(&x_sk).as_bytes());
+        dk = rest;
+    }
+    // dk = dk_pke (384k) || ek (384k + 32) || H(ek) || z
+    let ek_len = key.set.public_key_bytes();
+    let ek_start = ek_len - 32;
+    pk.extend_from_slice(&dk[ek_start..ek_start + ek_len]);
+    pk
+}
+
+fn master_secret(t_reply: &[u8; 32], secrets: &[&[u8]; 3]) -> Result<SecretBuffer, String> {
+    let mut ikm = SecretBuffer::new(secrets.iter().map(|s| s.len()).sum());
+    let mut at = 0;
+    for secret in secrets {
+        ikm[at..at + secret.len()].copy_from_slice(secret);
+        at += secret.len();
+    }
+    hub_blend(t_reply, &ikm)
+}
+
+fn finished_mac(master: &[u8], role: Role, hash: &[u8; 32]) -> Result<HmacSha256, String> {
+    let label = format!("dasp-channel-{}-fin", role.name());
+    let key = hmac(master, &[label.as_bytes()])?;
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(&key)
+        .map_err(|e| format!("HMAC init error: {:?}", e))?;
+    mac.update(hash);
+    Ok(mac)
+}
+
+/// What an identity signs: the role, so a signature cannot be reflected, and
+/// the transcript hash.
+fn signed_message(role: Role, hash: &[u8; 32]) -> Vec<u8> {
+    [PROTOCOL.as_bytes(), b" ", role.name().as_bytes(), hash].concat()
+}
+
+fn send_handshake(
+    stream: &mut impl Write,
+    transcript: &mut Transcript,
+    frame: &[u8],
+) -> io::Result<()> {
+    transcript.absorb(frame);
+    write_frame(stream, &[frame])
+}
+
+fn recv_handshake(stream: &mut impl Read, transcript: &mut Transcript) -> io::Result<Vec<u8>> {
+    let len = read_len(stream, MAX_HANDSHAKE_FRAME)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Connection closed during the handshake",
+        )
+    })?;
+    let mut frame = vec![0u8; len];
+    stream.read_exact(&mut frame)?;
+    transcript.absorb(&frame);
+    Ok(frame)
+}
+
+fn write_frame(stream: &mut impl Write, parts: &[&[u8]]) -> io::Result<()> {
+    let len: usize = parts.iter().map(|p| p.len()).sum();
+    let mut frame = Vec::with_capacity(4 + len);
+    frame.extend_from_slice(&(len as u32).to_be_bytes());
+    for part in parts {
+        frame.extend_from_slice(part);
+    }
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+/// Reads a frame length; `None` when the stream ends cleanly before it.
+fn read_len(stream: &mut impl Read, max: usize) -> io::Result<Option<usize>> {
+    let mut len = [0u8; 4];
+    let mut got = 0;
+    while got < len.len() {
+        match stream.read(&mut len[got..]) {
+            Ok(0) if got == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => got += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame of {} bytes exceeds the limit", len),
+        ));
+    }
+    Ok(Some(len))
+}
+
+fn invalid(e: impl Into<Box<dyn Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// The key chain of one direction.
+struct Chain {
+    label: &'static [u8; 3],
+    /// CK_seq.
+    key: SecretBuffer,
+    seq: u64,
+}
+
+impl Chain {
+    fn new(
+        master: &[u8],
+        label: &'static [u8; 3],
+        t_server_fin: &[u8; 32],
+    ) -> Result<Self, String> {
+        let key = hmac(master, &[b"dasp-channel-", label, t_server_fin])?;
+        Ok(Chain { label, key, seq: 0 })
+    }
+
+    /// MK_seq and the header its frame is sealed with; CK_seq is gone afterwards.
+    fn next(&mut self) -> io::Result<(SecretBuffer, [u8; 11])> {
+        let seq = self.seq;
+        self.seq = seq
+            .checked_add(1)
+            .ok_or_else(|| invalid("Channel frame limit reached"))?;
+        let (message_key, next) = step(&self.key).map_err(invalid)?;
+        self.key = next;
+        let mut header = [0u8; 11];
+        header[..3].copy_from_slice(self.label);
+        header[3..].copy_from_slice(&seq.to_be_bytes());
+        Ok((message_key, header))
+    }
+}
+
+struct SendHalf {
+    dem: &'static dyn Dem,
+    chain: Chain,
+    closed: bool,
+}
+
+impl SendHalf {
+    fn seal(&mut self, stream: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+        if self.closed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Channel is closed",
+            ));
+        }
+        let (message_key, header) = self.chain.next()?;
+        let mut body = Zeroizing::new(payload.to_vec());
+        let tag = self
+            .dem
+            .seal(&message_key, &header, &mut body, &[])
+            .map_err(invalid)?;
+        write_frame(stream, &[&body, &tag])
+    }
+
+    fn send(&mut self, stream: &mut impl Write, data: &[u8]) -> io::Result<()> {
+        for chunk in data.chunks(MAX_FRAME) {
+            self.seal(stream, chunk)?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, stream: &mut impl Write) -> io::Result<()> {
+        if !self.closed {
+            self.seal(stream, &[])?;
+            self.closed = true;
+        }
+        Ok(())
+    }
+}
+
+struct RecvHalf {
+    dem: &'static dyn Dem,
+    chain: Chain,
+    closed: bool,
+    /// Set after a bad frame; the chain is out of step from then on.
+    failed: bool,
+    /// Payload handed out piecemeal through `Read`, and how much of it is gone.
+    pending: Option<(Zeroizing<Vec<u8>>, usize)>,
+}
+
+impl RecvHalf {
+    fn recv(&mut self, stream: &mut impl Read) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+        if self.failed {
+            return Err(invalid("Channel failed earlier"));
+        }
+        if self.closed {
+            return Ok(None);
+        }
+        let result = self.open(stream);
+        if result.is_err() {
+            self.failed = true;
+        }
+        result
+    }
+
+    fn open(&mut self, stream: &mut impl Read) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+        let tag_bytes = self.dem.tag_bytes();
+        let len = read_len(stream, MAX_FRAME + tag_bytes)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed without a close notification",
+            )
+        })?;
+        if len < tag_bytes {
+            return Err(invalid("Frame is shorter than its tag"));
+        }
+        let mut body = Zeroizing::new(vec![0u8; len - tag_bytes]);
+        stream.read_exact(&mut body)?;
+        let mut tag = vec![0u8; tag_bytes];
+        stream.read_exact(&mut tag)?;
+
+        let (message_key, header) = self.chain.next()?;
+        self.dem
+            .open(&message_key, &header, &mut body, &[], &tag)
+            .map_err(invalid)?;
+        if body.is_empty() {
+            self.closed = true;
+            return Ok(None);
+        }
+        Ok(Some(body))
+    }
+
+    fn read(&mut self, stream: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_none() {
+            match self.recv(stream)? {
+                Some(payload) => self.pending = Some((payload, 0)),
+                None => return Ok(0),
+            }
+        }
+        let (payload, at) = self.pending.as_mut().expect("just filled");
+        let n = buf.len().min(payload.len() - *at);
+        buf[..n].copy_from_slice(&payload[*at..*at + n]);
+        *at += n;
+        if *at == payload.len() {
+            self.pending = None;
+        }
+        Ok(n)
+    }
+}
+
+fn halves(
+    dem: &'static dyn Dem,
+    master: &[u8],
+    role: Role,
+    t_server_fin: &[u8; 32],
+) -> Result<(SendHalf, RecvHalf), String> {
+    let (tx, rx) = match role {
+        Role::Client => (b"c2s", b"s2c"),
+        Role::Server => (b"s2c", b"c2s"),
+    };
+    Ok((
+        SendHalf {
+            dem,
+            chain: Chain::new(master, tx, t_server_fin)?,
+            closed: false,
+        },
+        RecvHalf {
+            dem,
+            chain: Chain::new(master, rx, t_server_fin)?,
+            closed: false,
+            failed: false,
+            pending: None,
+        },
+    ))
+}
+
+/// An authenticated, encrypted stream produced by [`Endpoint::connect`] or
+/// [`Endpoint::accept`]. `Write` sends one frame per call; `Read` returns 0
+/// once the peer has closed its side.
+pub struct SecureStream<S> {
+    stream: S,
+    tx: SendHalf,
+    rx: RecvHalf,
+}
+
+impl<S: Read + Write> SecureStream<S> {
+    fn new(
+        stream: S,
+        dem: &'static dyn Dem,
+        master: &[u8],
+        role: Role,
+        t_server_fin: &[u8; 32],
+    ) -> Result<Self, Box<dyn Error>> {
+        let (tx, rx) = halves(dem, master, role, t_server_fin)?;
+        Ok(SecureStream { stream, tx, rx })
+    }
+
+    /// Name of the frame cipher the handshake settled on.
+    pub fn dem(&self) -> &'static str {
+        self.tx.dem.name()
+    }
+
+    /// Sends `data`, in as many frames as [`MAX_FRAME`] requires.
+    pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.tx.send(&mut self.stream, data)
+    }
+
+    /// The next frame's payload, or `None` once the peer has closed.
+    pub fn recv(&mut self) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+        self.rx.recv(&mut self.stream)
+    }
+
+    /// Sends the close notification; receiving is unaffected.
+    pub fn close(&mut self) -> io::Result<()> {
+        self.tx.close(&mut self.stream)
+    }
+
+    /// The underlying transport.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S: Duplex> SecureStream<S> {
+    /// Splits the stream so each direction can run on its own thread.
+    pub fn split(self) -> io::Result<(SecureReader<S>, SecureWriter<S>)> {
+        let clone = self.stream.try_clone()?;
+        Ok((
+            SecureReader {
+                stream: self.stream,
+                rx: self.rx,
+            },
+            SecureWriter {
+                stream: clone,
+                tx: self.tx,
+            },
+        ))
+    }
+}
+
+impl<S: Read + Write> Read for SecureStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.rx.read(&mut self.stream, buf)
+    }
+}
+
+impl<S: Read + Write> Write for SecureStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_FRAME);
+        if n > 0 {
+            self.tx.seal(&mut self.stream, &buf[..n])?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// The receiving half of a split [`SecureStream`].
+pub struct SecureReader<S> {
+    stream: S,
+    rx: RecvHalf,
+}
+
+impl<S: Read> SecureReader<S> {
+    /// The next frame's payload, or `None` once the peer has closed.
+    pub fn recv(&mut self) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+        self.rx.recv(&mut self.stream)
+    }
+}
+
+impl<S: Read> Read for SecureReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.rx.read(&mut self.stream, buf)
+    }
+}
+
+/// The sending half of a split [`SecureStream`].
+pub struct SecureWriter<S> {
+    stream: S,
+    tx: SendHalf,
+}
+
+impl<S: Write> SecureWriter<S> {
+    /// Sends `data`, in as many frames as [`MAX_FRAME`] requires.
+    pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.tx.send(&mut self.stream, data)
+    }
+
+    /// Sends the close notification.
+    pub fn close(&mut self) -> io::Result<()> {
+        self.tx.close(&mut self.stream)
+    }
+}
+
+impl<S: Write> Write for SecureWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_FRAME);
+        if n > 0 {
+            self.tx.seal(&mut self.stream, &buf[..n])?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+
+    type Handshake = Result<SecureStream<TcpStream>, String>;
+
+    fn keys() -> (String, String) {
+        let (pk, sk) = crate::kem::keypair().unwrap();
+        (hex::encode(pk), hex::encode(&sk[..]))
+    }
+
+    /// Runs `client.connect` against `server.accept` over loopback TCP.
+    fn loopback(client: Endpoint, server: Endpoint) -> (Handshake, Handshake) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            server.accept(stream).map_err(|e| e.to_string())
+        });
+        let stream = TcpStream::connect(addr).unwrap();
+        // A client that fails drops its stream, which ends the server's wait
+        let connected = client.connect(stream).map_err(|e| e.to_string());
+        (connected, accepted.join().unwrap())
+    }
+
+    #[test]
+    fn both_directions_round_trip_over_tcp() {
+        let (client_pk, client_sk) = keys();
+        let (hybrid_pk, hybrid_sk) = crate::hybrid::keypair().unwrap();
+        let (server_pk, server_sk) = (hex::encode(hybrid_pk), hex::encode(&hybrid_sk[..]));
+
+        for dem in crate::dem::ALL {
+            let client = Endpoint::new(&client_sk, &server_pk).unwrap().with_dem(dem);
+            let server = Endpoint::new(&server_sk, &client_pk).unwrap();
+            assert_eq!(client.public_key(), client_pk);
+            assert_eq!(server.public_key(), server_pk);
+
+            let (client, server) = loopback(client, server);
+            let (mut client, mut server) = (client.unwrap(), server.unwrap());
+            assert_eq!(server.dem(), dem.name());
+
+            let big = vec![0x42u8; MAX_FRAME + 10];
+            client.send(b"ping").unwrap();
+            client.send(&big).unwrap();
+            client.close().unwrap();
+            assert_eq!(&server.recv().unwrap().unwrap()[..], b"ping");
+            assert_eq!(server.recv().unwrap().unwrap().len(), MAX_FRAME);
+            assert_eq!(server.recv().unwrap().unwrap().len(), 10);
+            assert!(server.recv().unwrap().is_none());
+
+            // The other direction keeps working after the client's close
+            let (mut reader, mut writer) = server.split().unwrap();
+            writer.write_all(b"pong").unwrap();
+            writer.close().unwrap();
+            let mut echoed = Vec::new();
+            client.read_to_end(&mut echoed).unwrap();
+            assert_eq!(echoed, b"pong");
+            assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn a_peer_without_the_expected_key_is_refused() {
+        let (client_pk, client_sk) = keys();
+        let (server_pk, server_sk) = keys();
+        let (other_pk, other_sk) = keys();
+
+        // The client dialled someone holding a different key than it expects
+        let client = Endpoint::new(&client_sk, &other_pk).unwrap();
+        let server = Endpoint::new(&server_sk, &client_pk).unwrap();
+        let (client, server) = loopback(client, server);
+        assert!(client.is_err());
+        assert!(server.is_err());
+
+        // The server expects a different client. That client cannot recover
+        // the secret sealed to the expected key, so it sees the mismatch first
+        let client = Endpoint::new(&other_sk, &server_pk).unwrap();
+        let server = Endpoint::new(&server_sk, &client_pk).unwrap();
+        let (client, server) = loopback(client, server);
+        assert!(client
+            .err()
+            .unwrap()
+            .starts_with("The server handshake MAC does not match"));
+        assert_eq!(
+            server.err().unwrap(),
+            "Connection closed during the handshake"
+        );
+    }
+
+    /// Stand-in signature scheme: a MAC under a key both sides share.
+    struct TestIdentity(&'static [u8]);
+
+    impl Identity for TestIdentity {
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>, String> {
+            Ok(hmac(self.0, &[message])?.to_vec())
+        }
+    }
+
+    impl PeerIdentity for TestIdentity {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), String> {
+            let expected = hmac(self.0, &[message])?;
+            if expected[..] != *signature {
+                return Err("Peer signature is invalid".into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn signature_identities_are_required_once_configured() {
+        let (client_pk, client_sk) = keys();
+        let (server_pk, server_sk) = keys();
+        let endpoints = || {
+            (
+                Endpoint::new(&client_sk, &server_pk).unwrap(),
+                Endpoint::new(&server_sk, &client_pk).unwrap(),
+            )
+        };
+
+        let (client, server) = endpoints();
+        let client = client.with_identity(Box::new(TestIdentity(b"client")));
+        let server = server
+            .with_identity(Box::new(TestIdentity(b"server")))
+            .with_peer_identity(Box::new(TestIdentity(b"client")));
+        let (client, server) = loopback(client, server);
+        assert!(client.is_ok() && server.is_ok());
+
+        // The server signs, but not with the identity the client expects
+        let (client, server) = endpoints();
+        let client = client.with_peer_identity(Box::new(TestIdentity(b"someone else")));
+        let server = server.with_identity(Box::new(TestIdentity(b"server")));
+        let (client, _) = loopback(client, server);
+        assert_eq!(client.err().unwrap(), "Peer signature is invalid");
+
+        // The client does not sign at all
+        let (client, server) = endpoints();
+        let server = server.with_peer_identity(Box::new(TestIdentity(b"client")));
+        let (_, server) = loopback(client, server);
+        assert_eq!(
+            server.err().unwrap(),
+            "The client did not sign the handshake"
+        );
+    }
+
+    #[test]
+    fn frames_cannot_be_altered_reordered_or_truncated() {
+        let master = [7u8; 32];
+        let t = [9u8; 32];
+        let pair = || {
+            let (tx, _) = halves(crate::dem::default_dem(), &master, Role::Client, &t).unwrap();
+            let (_, rx) = halves(crate::dem::default_dem(), &master, Role::Server, &t).unwrap();
+            (tx, rx)
+        };
+        let mut wire = Vec::new();
+        let (mut tx, _) = pair();
+        tx.send(&mut wire, b"first").unwrap();
+        let split = wire.len();
+        tx.send(&mut wire, b"second").unwrap();
+
+        let (_, mut rx) = pair();
+        let mut reader = io::Cursor::new(&wire);
+        assert_eq!(&rx.recv(&mut reader).unwrap().unwrap()[..], b"first");
+        assert_eq!(&rx.recv(&mut reader).unwrap().unwrap()[..], b"second");
+        // Ending without the close notification is reported
+        let err = rx.recv(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut flipped = wire.clone();
+        flipped[6] ^= 1;
+        let (_, mut rx) = pair();
+        assert!(rx.recv(&mut io::Cursor::new(&flipped)).is_err());
+        assert!(rx.recv(&mut io::Cursor::new(&wire)).is_err());
+
+        let swapped = [&wire[split..], &wire[..split]].concat();
+        let (_, mut rx) = pair();
+        assert!(rx.recv(&mut io::Cursor::new(&swapped)).is_err());
+
+        // A frame sent by the client cannot be played back to it
+        let (_, mut own) = halves(crate::dem::default_dem(), &master, Role::Client, &t).unwrap();
+        assert!(own.recv(&mut io::Cursor::new(&wire)).is_err());
+    }
+}
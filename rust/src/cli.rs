@@ -0,0 +1,362 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Command-Line Option Parsing
+ *
+ * Lives in the library rather than `main.rs` so the parser can be fuzzed and
+ * unit-tested without spawning the binary. Malformed input is reported as an
+ * error; nothing in here panics or exits the process.
+ */
+
+/// Flags and positional arguments of one CLI invocation.
+#[derive(Debug, Default)]
+pub struct CliOptions {
+    /// The command followed by its positional arguments, flags removed.
+    pub args: Vec<String>,
+    pub hwids: Vec<Vec<u8>>,
+    pub new_hwids: Vec<Vec<u8>>,
+    pub hwid_parts: Vec<Vec<u8>>,
+    pub new_hwid_parts: Vec<Vec<u8>>,
+    pub threshold: Option<u8>,
+    pub pin: Option<String>,
+    pub seal_sk: bool,
+    /// Generate an X25519 + ML-KEM-1024 hybrid keypair.
+    pub hybrid: bool,
+    /// ML-KEM parameter set for keygen; ML-KEM-1024 when absent.
+    pub kem: Option<crate::kem::ParameterSet>,
+    /// `bulk-encrypt` / `stream-decrypt` use one KEM session for all lines.
+    pub session: bool,
+    /// Messages between session re-keys; 0 disables them.
+    pub rekey: Option<u64>,
+    /// Body cipher for new envelopes; D-ASP CTR + HMAC when absent.
+    pub dem: Option<&'static dyn crate::dem::Dem>,
+    /// HPKE KDF and AEAD for `hpke-encrypt`; HKDF-SHA256 and AES-256-GCM when absent.
+    pub hpke_kdf: Option<crate::hpke::Kdf>,
+    pub hpke_aead: Option<crate::hpke::Aead>,
+    pub hpke_info: Vec<u8>,
+    pub hpke_aad: Vec<u8>,
+    pub hpke_psk: Option<Vec<u8>>,
+    pub hpke_psk_id: Option<Vec<u8>>,
+    /// ML-DSA-65 seed that signs `connect` / `listen` handshakes.
+    pub identity: Option<String>,
+    /// ML-DSA-65 public key the channel peer must sign with.
+    pub peer_identity: Option<Vec<u8>>,
+    /// `keygen` generates an ML-DSA-65 identity instead of KEM keys.
+    pub mldsa: bool,
+    pub ttl_secs: Option<u64>,
+    pub telemetry: bool,
+    pub diagnostic: bool,
+    /// Shared library of the C engine, for `acvp`.
+    pub c_lib: Option<String>,
+    /// Output directory, for `acvp`.
+    pub out: Option<String>,
+}
+
+/// Keeps only the hex digits of a (public) identifier such as a HWID.
+pub fn clean_hex(s: &str) -> String {
+    s.chars().filter(|c| c.is_ascii_hexdigit()).collect()
+}
+
+/// Reads `@path` arguments from disk (trimmed); anything else is returned unchanged.
+pub fn resolve_arg(arg: &str) -> Result<String, String> {
+    if let Some(stripped) = arg.strip_prefix('@') {
+        let path = std::path::Path::new(stripped);
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading argument @file '{}': {}", path.display(), e))?;
+        Ok(content.trim().to_string())
+    } else {
+        Ok(arg.to_string())
+    }
+}
+
+/// Parses the `bulk-encrypt` message count.
+pub fn parse_count(value: &str) -> Result<usize, String> {
+    value
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid count '{}'", value))
+}
+
+/// The `i`-th `bulk-encrypt` payload: the last 10 bytes of `payload` replaced
+/// by `i`, zero-padded, or `i` appended to a short payload. The cut moves back
+/// to a character boundary, so multi-byte text is never split.
+pub fn bulk_payload(payload: &str, i: usize) -> String {
+    if payload.len() <= 10 {
+        return format!("{}{}", payload, i);
+    }
+    let mut cut = payload.len() - 10;
+    while !payload.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}{:010}", &payload[..cut], i)
+}
+
+fn take_value<I, R>(iter: &mut I, flag: &str, resolve: &R) -> Result<String, String>
+where
+    I: Iterator<Item = String>,
+    R: Fn(&str) -> Result<String, String>,
+{
+    let raw = iter
+        .next()
+        .ok_or_else(|| format!("{} requires a value", flag))?;
+    resolve(&raw)
+}
+
+fn parse_hex(value: &str, what: &str) -> Result<Vec<u8>, String> {
+    hex::decode(clean_hex(value)).map_err(|_| format!("Invalid {} hex", what))
+}
+
+/// Separates flags from positional arguments.
+///
+/// Flags may appear anywhere on the command line. Flag values go through
+/// `resolve` (normally [`resolve_arg`]) so they can also be given as `@file`.
+pub fn parse_args<R>(raw_args: Vec<String>, resolve: R) -> Result<CliOptions, String>
+where
+    R: Fn(&str) -> Result<String, String>,
+{
+    let mut opts = CliOptions::default();
+    let mut iter = raw_args.into_iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--hwid" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.hwids.push(parse_hex(&v, "HWID")?);
+            }
+            "--new-hwid" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.new_hwids.push(parse_hex(&v, "New HWID")?);
+            }
+            "--hwid-part" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.hwid_parts.push(parse_hex(&v, "HWID part")?);
+            }
+            "--new-hwid-part" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.new_hwid_parts.push(parse_hex(&v, "HWID part")?);
+            }
+            "--threshold" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                let k = v
+                    .parse::<u8>()
+                    .map_err(|_| format!("Invalid threshold value '{}'", v))?;
+                opts.threshold = Some(k);
+            }
+            "--ttl" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                let t = v
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid TTL value '{}'", v))?;
+                opts.ttl_secs = Some(t);
+            }
+            "--kem" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                let set = crate::kem::ParameterSet::from_name(&v)
+                    .ok_or_else(|| format!("Unknown ML-KEM parameter set '{}'", v))?;
+                opts.kem = Some(set);
+            }
+            "--rekey" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                let n = v
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid re-key interval '{}'", v))?;
+                opts.rekey = Some(n);
+            }
+            "--dem" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                let dem =
+                    crate::dem::from_name(&v).ok_or_else(|| format!("Unknown DEM '{}'", v))?;
+                opts.dem = Some(dem);
+            }
+            "--kdf" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                let kdf = crate::hpke::Kdf::from_name(&v)
+                    .ok_or_else(|| format!("Unknown HPKE KDF '{}'", v))?;
+                opts.hpke_kdf = Some(kdf);
+            }
+            "--aead" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                let aead = crate::hpke::Aead::from_name(&v)
+                    .ok_or_else(|| format!("Unknown HPKE AEAD '{}'", v))?;
+                opts.hpke_aead = Some(aead);
+            }
+            "--info" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.hpke_info = parse_hex(&v, "HPKE info")?;
+            }
+            "--aad" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.hpke_aad = parse_hex(&v, "HPKE AAD")?;
+            }
+            "--psk" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.hpke_psk = Some(parse_hex(&v, "PSK")?);
+            }
+            "--psk-id" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.hpke_psk_id = Some(parse_hex(&v, "PSK ID")?);
+            }
+            "--identity" => opts.identity = Some(take_value(&mut iter, &arg, &resolve)?),
+            "--peer-identity" => {
+                let v = take_value(&mut iter, &arg, &resolve)?;
+                opts.peer_identity = Some(parse_hex(&v, "peer identity")?);
+            }
+            "--pin" => opts.pin = Some(take_value(&mut iter, &arg, &resolve)?),
+            "--c-lib" => opts.c_lib = Some(take_value(&mut iter, &arg, &resolve)?),
+            "--out" => opts.out = Some(take_value(&mut iter, &arg, &resolve)?),
+            "--seal" => opts.seal_sk = true,
+            "--hybrid" => opts.hybrid = true,
+            "--session" => opts.session = true,
+            "--mldsa" => opts.mldsa = true,
+            "--diagnostic" => opts.diagnostic = true,
+            "--telemetry" => opts.telemetry = true,
+            _ => opts.args.push(arg),
+        }
+    }
+    Ok(opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliOptions, String> {
+        parse_args(args.iter().map(|s| s.to_string()).collect(), |s| {
+            Ok(s.to_string())
+        })
+    }
+
+    #[test]
+    fn flags_are_separated_from_positionals() {
+        let hw = "ab".repeat(32);
+        let opts = parse(&[
+            "decrypt",
+            "--hwid",
+            &hw,
+            "{}",
+            "--ttl",
+            "60",
+            "sk",
+            "--telemetry",
+        ])
+        .unwrap();
+        assert_eq!(opts.args, ["decrypt", "{}", "sk"]);
+        assert_eq!(opts.hwids, vec![vec![0xab; 32]]);
+        assert_eq!(opts.ttl_secs, Some(60));
+        assert!(opts.telemetry);
+    }
+
+    #[test]
+    fn repeated_flags_accumulate() {
+        let opts = parse(&[
+            "encrypt",
+            "--hwid-part",
+            "01",
+            "--hwid-part",
+            "02",
+            "--threshold",
+            "1",
+        ])
+        .unwrap();
+        assert_eq!(opts.hwid_parts, vec![vec![1], vec![2]]);
+        assert_eq!(opts.threshold, Some(1));
+    }
+
+    #[test]
+    fn kem_accepts_names_and_numbers() {
+        use crate::kem::ParameterSet;
+        let opts = parse(&["keygen", "--kem", "768"]).unwrap();
+        assert_eq!(opts.kem, Some(ParameterSet::MlKem768));
+        let opts = parse(&["keygen", "--kem", "ML-KEM-512"]).unwrap();
+        assert_eq!(opts.kem, Some(ParameterSet::MlKem512));
+    }
+
+    #[test]
+    fn hpke_flags_parse() {
+        use crate::hpke::{Aead, Kdf};
+        let opts = parse(&[
+            "hpke-encrypt",
+            "--kdf",
+            "hkdf-sha512",
+            "--aead",
+            "chacha20-poly1305",
+            "--info",
+            "0102",
+        ])
+        .unwrap();
+        assert_eq!(opts.hpke_kdf, Some(Kdf::HkdfSha512));
+        assert_eq!(opts.hpke_aead, Some(Aead::ChaCha20Poly1305));
+        assert_eq!(opts.hpke_info, [1, 2]);
+        assert!(parse(&["hpke-encrypt", "--aead", "aes-192-gcm"]).is_err());
+
+        let opts = parse(&["encrypt", "--dem", "chacha20-poly1305"]).unwrap();
+        assert_eq!(opts.dem.unwrap().name(), "chacha20-poly1305");
+        assert!(parse(&["encrypt", "--dem", "rot13"]).is_err());
+    }
+
+    #[test]
+    fn channel_flags_parse() {
+        let opts = parse(&[
+            "listen",
+            "127.0.0.1:7000",
+            "--identity",
+            "@id.seed",
+            "sk",
+            "--peer-identity",
+            "ab:cd",
+            "pk",
+        ])
+        .unwrap();
+        assert_eq!(opts.args, ["listen", "127.0.0.1:7000", "sk", "pk"]);
+        assert_eq!(opts.identity.as_deref(), Some("@id.seed"));
+        assert_eq!(opts.peer_identity, Some(vec![0xab, 0xcd]));
+        assert!(parse(&["keygen", "--mldsa"]).unwrap().mldsa);
+        assert!(parse(&["connect", "--peer-identity", "xyz0"]).is_err());
+    }
+
+    // Regressions: each of these used to panic inside main()
+    #[test]
+    fn malformed_flag_values_are_errors() {
+        assert!(parse(&["encrypt", "--threshold", "256"]).is_err());
+        assert!(parse(&["encrypt", "--threshold", "-1"]).is_err());
+        assert!(parse(&["decrypt", "--ttl", "soon"]).is_err());
+        assert!(parse(&["decrypt", "--hwid", "abc"]).is_err());
+        assert!(parse(&["decrypt", "--new-hwid-part", "0"]).is_err());
+        assert!(parse(&["keygen", "--kem", "2048"]).is_err());
+        assert!(parse(&["bulk-encrypt", "--rekey", "-5"]).is_err());
+        assert!(parse_count("ten").is_err());
+        assert!(parse_count("-1").is_err());
+        assert_eq!(parse_count("10"), Ok(10));
+    }
+
+    #[test]
+    fn bulk_payloads_vary_without_splitting_characters() {
+        assert_eq!(bulk_payload("short", 7), "short7");
+        assert_eq!(bulk_payload("reading-0000000000", 42), "reading-0000000042");
+        // 'é' straddles the 10-byte cut and is dropped whole
+        let payload = "caf\u{e9}123456789";
+        assert_eq!(payload.len(), 14);
+        assert_eq!(bulk_payload(payload, 3), "caf0000000003");
+        assert_eq!(
+            bulk_payload(&"\u{1f512}".repeat(4), 1),
+            "\u{1f512}0000000001"
+        );
+    }
+
+    #[test]
+    fn trailing_flag_without_value_is_an_error() {
+        for flag in ["--hwid", "--pin", "--ttl", "--threshold", "--new-hwid"] {
+            assert!(parse(&["decrypt", flag]).is_err(), "{}", flag);
+        }
+    }
+
+    #[test]
+    fn unreadable_file_argument_is_an_error() {
+        assert!(resolve_arg("@/nonexistent/dasp/arg").is_err());
+        let err = parse_args(
+            vec!["--pin".into(), "@/nonexistent/dasp/pin".into()],
+            resolve_arg,
+        );
+        assert!(err.is_err());
+    }
+}
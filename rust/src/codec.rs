@@ -0,0 +1,262 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Constant-Time Hex / Base64 Codecs for Secret Material
+ *
+ * Table lookups and per-character `match` arms leak the value being decoded
+ * through branch prediction and cache timing. Every character here is mapped
+ * with branch-free arithmetic masks instead (after libsodium's sodium_hex2bin /
+ * sodium_base642bin), and a bad character only sets an error flag that is
+ * checked once the whole input has been processed. Input length and padding
+ * are treated as public.
+ */
+
+use crate::secret::SecretBuffer;
+use zeroize::Zeroizing;
+
+/// All-ones (`0xff`) when `lo <= c < hi`, otherwise zero. Valid for `c`, `lo`, `hi` in 0..=256.
+#[inline(always)]
+fn range_mask(c: i32, lo: i32, hi: i32) -> i32 {
+    (((lo - 1 - c) & (c - hi)) >> 8) & 0xff
+}
+
+/// Decodes one hex digit; returns the nibble and a `0xff` validity mask.
+#[inline(always)]
+fn hex_nibble(c: u8) -> (u8, u8) {
+    let c = c as i32;
+    let digit = range_mask(c, b'0' as i32, b'9' as i32 + 1);
+    let lower = range_mask(c, b'a' as i32, b'f' as i32 + 1);
+    let upper = range_mask(c, b'A' as i32, b'F' as i32 + 1);
+    let val = (digit & (c - 48)) | (lower & (c - 87)) | (upper & (c - 55));
+    (val as u8, (digit | lower | upper) as u8)
+}
+
+/// Encodes a nibble as a lowercase hex digit.
+#[inline(always)]
+fn hex_char(n: u8) -> u8 {
+    let n = n as i32;
+    (n + 48 + (((9 - n) >> 8) & 39)) as u8
+}
+
+/// Decodes one base64 character (standard alphabet); returns the value and a `0xff` validity mask.
+#[inline(always)]
+fn b64_value(c: u8) -> (u8, u8) {
+    let c = c as i32;
+    let upper = range_mask(c, b'A' as i32, b'Z' as i32 + 1);
+    let lower = range_mask(c, b'a' as i32, b'z' as i32 + 1);
+    let digit = range_mask(c, b'0' as i32, b'9' as i32 + 1);
+    let plus = range_mask(c, b'+' as i32, b'+' as i32 + 1);
+    let slash = range_mask(c, b'/' as i32, b'/' as i32 + 1);
+    let val =
+        (upper & (c - 65)) | (lower & (c - 71)) | (digit & (c + 4)) | (plus & 62) | (slash & 63);
+    (val as u8, (upper | lower | digit | plus | slash) as u8)
+}
+
+/// Encodes a 6-bit value as a base64 character (standard alphabet).
+#[inline(always)]
+fn b64_char(b: u8) -> u8 {
+    let b = b as i32;
+    let mut diff = 65;
+    diff += ((25 - b) >> 8) & 6;
+    diff -= ((51 - b) >> 8) & 75;
+    diff -= ((61 - b) >> 8) & 15;
+    diff += ((62 - b) >> 8) & 3;
+    (b + diff) as u8
+}
+
+/// Lowercase hex encoding without secret-dependent branches or lookups.
+pub fn encode_hex(bytes: &[u8]) -> Zeroizing<String> {
+    let mut out = Zeroizing::new(Vec::with_capacity(bytes.len() * 2));
+    for &b in bytes {
+        out.push(hex_char(b >> 4));
+        out.push(hex_char(b & 0x0f));
+    }
+    Zeroizing::new(String::from_utf8(std::mem::take(&mut *out)).unwrap())
+}
+
+/// Strict hex decoding into locked memory.
+///
+/// Accepts upper and lower case digits only; whitespace, prefixes and odd
+/// lengths are errors.
+pub fn decode_hex(s: &str) -> Result<SecretBuffer, String> {
+    let mut out = SecretBuffer::new(s.len() / 2);
+    hex_into(s, &mut out)?;
+    Ok(out)
+}
+
+/// Strict hex decoding onto the heap, wiped on drop.
+///
+/// For bulk data such as envelope bodies, which would waste locked pages.
+pub fn decode_hex_vec(s: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut out = Zeroizing::new(vec![0u8; s.len() / 2]);
+    hex_into(s, &mut out)?;
+    Ok(out)
+}
+
+fn hex_into(s: &str, out: &mut [u8]) -> Result<(), String> {
+    let input = s.as_bytes();
+    if !input.len().is_multiple_of(2) {
+        return Err("Invalid hex length (odd number of digits)".into());
+    }
+
+    let mut valid = 0xffu8;
+    for (dst, pair) in out.iter_mut().zip(input.chunks_exact(2)) {
+        let (hi, hi_ok) = hex_nibble(pair[0]);
+        let (lo, lo_ok) = hex_nibble(pair[1]);
+        *dst = (hi << 4) | lo;
+        valid &= hi_ok & lo_ok;
+    }
+
+    if unsafe { std::ptr::read_volatile(&valid) } != 0xff {
+        return Err("Invalid hex character".into());
+    }
+    Ok(())
+}
+
+/// Standard (padded) base64 encoding without secret-dependent branches or lookups.
+pub fn encode_base64(bytes: &[u8]) -> Zeroizing<String> {
+    let mut out = Zeroizing::new(Vec::with_capacity(bytes.len().div_ceil(3) * 4));
+    for group in bytes.chunks(3) {
+        let b0 = group[0];
+        let b1 = group.get(1).copied().unwrap_or(0);
+        let b2 = group.get(2).copied().unwrap_or(0);
+        out.push(b64_char(b0 >> 2));
+        out.push(b64_char(((b0 & 0x03) << 4) | (b1 >> 4)));
+        if group.len() > 1 {
+            out.push(b64_char(((b1 & 0x0f) << 2) | (b2 >> 6)));
+        } else {
+            out.push(b'=');
+        }
+        if group.len() > 2 {
+            out.push(b64_char(b2 & 0x3f));
+        } else {
+            out.push(b'=');
+        }
+    }
+    Zeroizing::new(String::from_utf8(std::mem::take(&mut *out)).unwrap())
+}
+
+/// Strict standard-alphabet base64 decoding into locked memory.
+///
+/// The input must be padded to a multiple of four characters, `=` may only
+/// appear as trailing padding, and unused trailing bits must be zero.
+pub fn decode_base64(s: &str) -> Result<SecretBuffer, String> {
+    let input = s.as_bytes();
+    if !input.len().is_multiple_of(4) {
+        return Err("Invalid base64 length (must be a multiple of 4)".into());
+    }
+    let pad = input
+        .iter()
+        .rev()
+        .take(2)
+        .take_while(|&&c| c == b'=')
+        .count();
+    let body = &input[..input.len() - pad];
+    let out_len = input.len() / 4 * 3 - pad;
+
+    let mut out = SecretBuffer::new(out_len);
+    let mut valid = 0xffu8;
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let mut pos = 0;
+    for &c in body {
+        let (v, ok) = b64_value(c);
+        valid &= ok;
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out[pos] = (acc >> bits) as u8;
+            pos += 1;
+        }
+    }
+    // Non-canonical encodings leave set bits below the last full byte
+    let leftover = (acc & ((1u32 << bits) - 1)) as u8;
+    valid &= !((leftover | leftover.wrapping_neg()) >> 7).wrapping_neg();
+
+    if unsafe { std::ptr::read_volatile(&valid) } != 0xff {
+        return Err("Invalid base64 character or padding".into());
+    }
+    Ok(out)
+}
+
+/// Decodes a secret of exactly `len` bytes given either as hex or base64.
+///
+/// The encoding is chosen from the (public) input length alone.
+pub fn decode_secret(s: &str, len: usize) -> Result<SecretBuffer, String> {
+    let out = if s.len() == len * 2 {
+        decode_hex(s)?
+    } else if s.len() == len.div_ceil(3) * 4 {
+        decode_base64(s)?
+    } else {
+        return Err("Invalid secret key length".into());
+    };
+    if out.len() != len {
+        return Err("Invalid secret key length".into());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    #[test]
+    fn hex_matches_reference_codec() {
+        let all: Vec<u8> = (0..=255).collect();
+        let encoded = encode_hex(&all);
+        assert_eq!(encoded.as_str(), hex::encode(&all));
+        assert_eq!(&decode_hex(&encoded).unwrap()[..], &all[..]);
+        assert_eq!(&decode_hex(&hex::encode_upper(&all)).unwrap()[..], &all[..]);
+        assert_eq!(&decode_hex_vec(&encoded).unwrap()[..], &all[..]);
+        assert!(decode_hex_vec("0g").is_err());
+        assert!(decode_hex_vec("abc").is_err());
+    }
+
+    #[test]
+    fn hex_rejects_malformed_input() {
+        assert!(decode_hex("abc").is_err());
+        for bad in ["0g", "g0", " 0", "0x", "zz", "0:", "@0", "`0", "0G"] {
+            assert!(decode_hex(bad).is_err(), "{} should be rejected", bad);
+        }
+        assert!(decode_hex("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn base64_matches_reference_codec() {
+        let std = base64::engine::general_purpose::STANDARD;
+        for len in 0..70 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 11) as u8).collect();
+            let encoded = encode_base64(&data);
+            assert_eq!(encoded.as_str(), std.encode(&data));
+            assert_eq!(&decode_base64(&encoded).unwrap()[..], &data[..]);
+        }
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(encode_base64(&all).as_str(), std.encode(&all));
+    }
+
+    #[test]
+    fn base64_rejects_malformed_input() {
+        for bad in [
+            "A", "AAA", "AA=A", "A===", "====", "QQ=", "QR==", "QUI=x", "QU-=", "QU_=", "QU I",
+        ] {
+            assert!(decode_base64(bad).is_err(), "{} should be rejected", bad);
+        }
+        assert_eq!(&decode_base64("QQ==").unwrap()[..], b"A");
+        assert_eq!(&decode_base64("QUI=").unwrap()[..], b"AB");
+    }
+
+    #[test]
+    fn secret_accepts_either_encoding() {
+        let key: Vec<u8> = (0..100).map(|i| i as u8).collect();
+        let b64 = base64::engine::general_purpose::STANDARD.encode(&key);
+        assert_eq!(
+            &decode_secret(&hex::encode(&key), 100).unwrap()[..],
+            &key[..]
+        );
+        assert_eq!(&decode_secret(&b64, 100).unwrap()[..], &key[..]);
+        assert!(decode_secret(&hex::encode(&key[..99]), 100).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Data Encapsulation (DEM) Backends
+ *
+ * The envelope's KEM, HWID binding and HUB blend are shared; what happens to
+ * the body afterwards is a `Dem`. Every backend gets the 32-byte blended
+ * secret, which is fresh per envelope, and must authenticate
+ *
+ *   header || body || trailer
+ *
+ * where the header is the KEM ciphertext(s) and the `kem`/`dem` names, and the
+ * trailer is the big-endian timestamp. That is the order the D-ASP HMAC has
+ * always used, so D-ASP envelopes are unchanged.
+ *
+ *   D-ASP CTR + HMAC-SHA256   `dasp-ctr-hmac-sha256`  32-byte tag (default)
+ *   AES-256-GCM               `aes-256-gcm`           16-byte tag
+ *   ChaCha20-Poly1305         `chacha20-poly1305`     16-byte tag
+ *
+ * The AEAD key and nonce are one HKDF-SHA256 expand of the blended secret
+ * with the backend name as info. A key is never used twice, so a derived
+ * nonce is safe.
+ */
+
+use crate::engine::{ctr_xor, DaspKeys};
+use crate::error::{Error, ErrorKind};
+use aes_gcm::aead::generic_array::typenum::Unsigned;
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A data-encapsulation backend: encrypts and authenticates an envelope body
+/// under keys derived from the blended secret.
+pub trait Dem: Sync {
+    /// The name carried in the envelope's `dem` field.
+    fn name(&self) -> &'static str;
+
+    /// Length of the tag stored in the envelope's `mac` field.
+    fn tag_bytes(&self) -> usize;
+
+    /// Encrypts `body` in place and returns the tag over `header || body || trailer`.
+    fn seal(
+        &self,
+        blended_ss: &[u8],
+        header: &[u8],
+        body: &mut [u8],
+        trailer: &[u8],
+    ) -> Result<Vec<u8>, String>;
+
+    /// Checks `tag`, then decrypts `body` in place. On a bad tag the body is
+    /// left encrypted and the error is an `ErrorKind::Integrity` "Integrity
+    /// Check Failed".
+    fn open(
+        &self,
+        blended_ss: &[u8],
+        header: &[u8],
+        body: &mut [u8],
+        trailer: &[u8],
+        tag: &[u8],
+    ) -> Result<(), Error>;
+}
+
+impl std::fmt::Debug for dyn Dem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn integrity_failure() -> Error {
+    Error::new(ErrorKind::Integrity, "Integrity Check Failed")
+}
+
+/// Every built-in backend, the default first.
+pub static ALL: [&dyn Dem; 3] = [&DaspCtrHmac, &Aes256Gcm, &ChaCha20Poly1305];
+
+/// The D-ASP backend, used when an envelope names none.
+pub fn default_dem() -> &'static dyn Dem {
+    ALL[0]
+}
+
+/// Looks a backend up by its envelope name; `dasp` is accepted for the default.
+pub fn from_name(name: &str) -> Option<&'static dyn Dem> {
+    let name = name.to_ascii_lowercase();
+    if name == "dasp" {
+        return Some(default_dem());
+    }
+    ALL.into_iter().find(|dem| dem.name() == name)
+}
+
+/// D-ASP Cascade 16 in CTR mode with an HMAC-SHA256 tag.
+pub struct DaspCtrHmac;
+
+impl DaspCtrHmac {
+    fn tag(
+        keys: &DaspKeys,
+        header: &[u8],
+        body: &[u8],
+        trailer: &[u8],
+    ) -> Result<[u8; 32], String> {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&keys.hmac_key)
+            .map_err(|e| format!("HMAC error: {:?}", e))?;
+        mac.update(header);
+        mac.update(body);
+        mac.update(trailer);
+        Ok(mac.finalize().into_bytes().into())
+    }
+}
+
+impl Dem for DaspCtrHmac {
+    fn name(&self) -> &'static str {
+        "dasp-ctr-hmac-sha256"
+    }
+
+    fn tag_bytes(&self) -> usize {
+        32
+    }
+
+    fn seal(
+        &self,
+        blended_ss: &[u8],
+        header: &[u8],
+        body: &mut [u8],
+        trailer: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let keys = DaspKeys::derive(blended_ss)?;
+        ctr_xor(body, &keys);
+        Ok(Self::tag(&keys, header, body, trailer)?.to_vec())
+    }
+
+    fn open(
+        &self,
+        blended_ss: &[u8],
+        header: &[u8],
+        body: &mut [u8],
+        trailer: &[u8],
+        tag: &[u8],
+    ) -> Result<(), Error> {
+        let keys = DaspKeys::derive(blended_ss)?;
+        let expected: &[u8; 32] = tag.try_into().map_err(|_| integrity_failure())?;
+        let actual = Self::tag(&keys, header, body, trailer)?;
+
+        let mut diff_verify = 0u8;
+        for i in 0..32 {
+            diff_verify |= actual[i] ^ expected[i];
+        }
+
+        let valid_2 = unsafe { std::ptr::read_volatile(&diff_verify) } == 0;
+
+        if !valid_2 {
+            return Err(integrity_failure());
+        }
+
+        #[cfg(all(target_arch = "x86_64", not(miri)))]
+        unsafe {
+            core::arch::x86_64::_mm_lfence()
+        };
+        #[cfg(all(target_arch = "aarch64", not(miri)))]
+        unsafe {
+            core::arch::aarch64::isb(core::arch::aarch64::SY)
+        };
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+
+        ctr_xor(body, &keys);
+        Ok(())
+    }
+}
+
+/// AES-256-GCM with a derived key and nonce.
+pub struct Aes256Gcm;
+
+/// ChaCha20-Poly1305 with a derived key and nonce.
+pub struct ChaCha20Poly1305;
+
+const AEAD_KEY_BYTES: usize = 32;
+const AEAD_NONCE_BYTES: usize = 12;
+
+/// `key || nonce = HKDF-Expand(blended_ss, "dasp-dem-v1" || name, 44)`.
+fn aead_key_nonce(
+    blended_ss: &[u8],
+    name: &str,
+) -> Result<Zeroizing<[u8; AEAD_KEY_BYTES + AEAD_NONCE_BYTES]>, String> {
+    let mut okm = Zeroizing::new([0u8; AEAD_KEY_BYTES + AEAD_NONCE_BYTES]);
+    hkdf::Hkdf::<Sha256>::from_prk(blended_ss)
+        .map_err(|_| "Invalid blended secret length".to_string())?
+        .expand_multi_info(&[b"dasp-dem-v1", name.as_bytes()], &mut okm[..])
+        .map_err(|_| "DEM key expansion failed".to_string())?;
+    Ok(okm)
+}
+
+fn aead_seal<C: AeadInPlace + KeyInit>(
+    name: &str,
+    blended_ss: &[u8],
+    header: &[u8],
+    body: &mut [u8],
+    trailer: &[u8],
+) -> Result<Vec<u8>, String> {
+    let okm = aead_key_nonce(blended_ss, name)?;
+    let (key, nonce) = okm.split_at(AEAD_KEY_BYTES);
+    let aad = [header, trailer].concat();
+    let tag = C::new_from_slice(key)
+        .map_err(|_| "Invalid AEAD key length".to_string())?
+        .encrypt_in_place_detached(nonce.into(), &aad, body)
+        .map_err(|_| format!("{} encryption failed", name))?;
+    Ok(tag.to_vec())
+}
+
+fn aead_open<C: AeadInPlace + KeyInit>(
+    name: &str,
+    blended_ss: &[u8],
+    header: &[u8],
+    body: &mut [u8],
+    trailer: &[u8],
+    tag: &[u8],
+) -> Result<(), Error> {
+    if tag.len() != C::TagSize::USIZE {
+        return Err(integrity_failure());
+    }
+    let okm = aead_key_nonce(blended_ss, name)?;
+    let (key, nonce) = okm.split_at(AEAD_KEY_BYTES);
+    let aad = [header, trailer].concat();
+    C::new_from_slice(key)
+        .map_err(|_| "Invalid AEAD key length".to_string())?
+        .decrypt_in_place_detached(nonce.into(), &aad, body, tag.into())
+        .map_err(|_| integrity_failure())
+}
+
+macro_rules! aead_dem {
+    ($ty:ty, $cipher:ty, $name:literal) => {
+        impl Dem for $ty {
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            fn tag_bytes(&self) -> usize {
+                <$cipher as aes_gcm::aead::AeadCore>::TagSize::USIZE
+            }
+
+            fn seal(
+                &self,
+                blended_ss: &[u8],
+                header: &[u8],
+                body: &mut [u8],
+                trailer: &[u8],
+            ) -> Result<Vec<u8>, String> {
+                aead_seal::<$cipher>($name, blended_ss, header, body, trailer)
+            }
+
+            fn open(
+                &self,
+                blended_ss: &[u8],
+                header: &[u8],
+                body: &mut [u8],
+                trailer: &[u8],
+                tag: &[u8],
+            ) -> Result<(), Error> {
+                aead_open::<$cipher>($name, blended_ss, header, body, trailer, tag)
+            }
+        }
+    };
+}
+
+aead_dem!(Aes256Gcm, aes_gcm::Aes256Gcm, "aes-256-gcm");
+aead_dem!(
+    ChaCha20Poly1305,
+    chacha20poly1305::ChaCha20Poly1305,
+    "chacha20-poly1305"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_backend_round_trips_and_authenticates() {
+        let ss = [0x5a; 32];
+        for dem in ALL {
+            let mut body = b"the quick brown fox".to_vec();
+            let tag = dem.seal(&ss, b"head", &mut body, b"tail").unwrap();
+            assert_eq!(tag.len(), dem.tag_bytes(), "{}", dem.name());
+            assert_ne!(&body[..], b"the quick brown fox");
+
+            // Header, trailer and tag are all covered
+            for (header, trailer) in [(&b"Head"[..], &b"tail"[..]), (b"head", b"")] {
+                let mut copy = body.clone();
+                let err = dem.open(&ss, header, &mut copy, trailer, &tag).unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::Integrity);
+                assert_eq!(err.to_string(), "Integrity Check Failed");
+            }
+            assert!(dem
+                .open(&ss, b"head", &mut body.clone(), b"tail", &tag[1..])
+                .is_err());
+
+            dem.open(&ss, b"head", &mut body, b"tail", &tag).unwrap();
+            assert_eq!(&body[..], b"the quick brown fox");
+        }
+    }
+
+    #[test]
+    fn backends_are_found_by_name() {
+        assert_eq!(from_name("dasp").unwrap().name(), "dasp-ctr-hmac-sha256");
+        assert_eq!(from_name("AES-256-GCM").unwrap().name(), "aes-256-gcm");
+        assert!(from_name("aes-128-gcm").is_none());
+        assert_eq!(default_dem().tag_bytes(), 32);
+    }
+}
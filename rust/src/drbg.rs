@@ -0,0 +1,301 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * HMAC_DRBG (NIST SP 800-90A Rev. 1, section 10.1.2) over HMAC-SHA-256
+ *
+ * One process-wide instance, seeded from the health-tested `entropy` source,
+ * supplies every ML-KEM keygen and encapsulation seed and every seal seed.
+ * It reseeds itself after `reseed_interval` requests, or before every request
+ * with prediction resistance enabled. Callers can swap it out for the whole
+ * process (`install`) or for one thread (`with_drbg`), e.g. to run with a
+ * deterministic instance from `HmacDrbg::from_seed` in tests.
+ */
+
+use crate::error::{Error, ErrorKind};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::cell::RefCell;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const OUTLEN: usize = 32;
+/// Entropy input per (re)seed: the 256-bit security strength.
+const SEED_LEN: usize = 32;
+/// Nonce drawn at instantiation: half the security strength.
+const NONCE_LEN: usize = 16;
+/// Largest single request: 2^19 bits.
+pub const MAX_REQUEST_BYTES: usize = 1 << 16;
+/// Upper bound allowed by SP 800-90A for the reseed interval.
+pub const MAX_RESEED_INTERVAL: u64 = 1 << 48;
+/// Requests served between automatic reseeds unless configured otherwise.
+pub const DEFAULT_RESEED_INTERVAL: u64 = 1 << 16;
+/// Personalization string of the process-wide instance.
+const PERSONALIZATION: &[u8] = b"D-SPNA-512 HMAC_DRBG";
+
+type EntropySource = fn(&mut [u8]) -> Result<(), String>;
+
+/// An HMAC_DRBG instance.
+pub struct HmacDrbg {
+    k: Zeroizing<[u8; OUTLEN]>,
+    v: Zeroizing<[u8; OUTLEN]>,
+    reseed_counter: u64,
+    reseed_interval: u64,
+    prediction_resistance: bool,
+    /// `None` for deterministic instances, which must be reseeded explicitly.
+    source: Option<EntropySource>,
+}
+
+impl HmacDrbg {
+    /// Instantiates from the health-tested OS entropy source.
+    ///
+    /// # Arguments
+    /// * `personalization` - Optional string mixed into the seed to separate instances.
+    pub fn new(personalization: &[u8]) -> Result<Self, String> {
+        Self::with_source(crate::entropy::fill, personalization)
+    }
+
+    fn with_source(source: EntropySource, personalization: &[u8]) -> Result<Self, String> {
+        let mut seed = Zeroizing::new([0u8; SEED_LEN + NONCE_LEN]);
+        source(&mut seed[..])?;
+        let mut drbg = Self::from_seed(&seed[..SEED_LEN], &seed[SEED_LEN..], personalization);
+        drbg.source = Some(source);
+        Ok(drbg)
+    }
+
+    /// Deterministic instantiation from caller-supplied entropy input and nonce.
+    /// For tests and known-answer vectors only: the instance has no entropy
+    /// source, so it cannot reseed on its own or offer prediction resistance.
+    pub fn from_seed(entropy_input: &[u8], nonce: &[u8], personalization: &[u8]) -> Self {
+        let mut drbg = HmacDrbg {
+            k: Zeroizing::new([0x00; OUTLEN]),
+            v: Zeroizing::new([0x01; OUTLEN]),
+            reseed_counter: 1,
+            reseed_interval: DEFAULT_RESEED_INTERVAL,
+            prediction_resistance: false,
+            source: None,
+        };
+        drbg.update(&[entropy_input, nonce, personalization]);
+        drbg
+    }
+
+    /// Sets how many requests are served before a reseed is required
+    /// (clamped to 1..=[`MAX_RESEED_INTERVAL`]).
+    pub fn with_reseed_interval(mut self, interval: u64) -> Self {
+        self.reseed_interval = interval.clamp(1, MAX_RESEED_INTERVAL);
+        self
+    }
+
+    /// Enables prediction resistance: fresh entropy is drawn before every request.
+    pub fn with_prediction_resistance(mut self, enabled: bool) -> Self {
+        self.prediction_resistance = enabled;
+        self
+    }
+
+    /// Reseeds from the entropy source.
+    pub fn reseed(&mut self, additional_input: &[u8]) -> Result<(), String> {
+        let source = self
+            .source
+            .ok_or("Deterministic DRBG has no entropy source; reseed it explicitly")?;
+        let mut entropy = Zeroizing::new([0u8; SEED_LEN]);
+        source(&mut entropy[..])?;
+        self.reseed_with(&entropy[..], additional_input);
+        Ok(())
+    }
+
+    /// Reseeds with caller-supplied entropy input.
+    pub fn reseed_with(&mut self, entropy_input: &[u8], additional_input: &[u8]) {
+        self.update(&[entropy_input, additional_input]);
+        self.reseed_counter = 1;
+    }
+
+    /// Fills `out` with pseudorandom bytes.
+    ///
+    /// Reseeds first when prediction resistance is on or the reseed interval has
+    /// been reached; for deterministic instances that is an error instead.
+    pub fn generate(&mut self, out: &mut [u8], additional_input: &[u8]) -> Result<(), String> {
+        if out.len() > MAX_REQUEST_BYTES {
+            return Err(format!(
+                "DRBG request of {} bytes exceeds the {} byte limit",
+                out.len(),
+                MAX_REQUEST_BYTES
+            ));
+        }
+        let mut additional_input = additional_input;
+        if self.prediction_resistance || self.reseed_counter > self.reseed_interval {
+            self.reseed(additional_input)?;
+            additional_input = &[];
+        }
+        if !additional_input.is_empty() {
+            self.update(&[additional_input]);
+        }
+
+        for chunk in out.chunks_mut(OUTLEN) {
+            *self.v = self.hmac(&[&self.v[..]]);
+            chunk.copy_from_slice(&self.v[..chunk.len()]);
+        }
+        self.update(&[additional_input]);
+        self.reseed_counter += 1;
+        Ok(())
+    }
+
+    /// HMAC_DRBG_Update. `provided_data` is the concatenation of the slices.
+    fn update(&mut self, provided_data: &[&[u8]]) {
+        let empty = provided_data.iter().all(|d| d.is_empty());
+        for round in [0x00u8, 0x01] {
+            if round == 0x01 && empty {
+                break;
+            }
+            let separator = [round];
+            let mut parts: Vec<&[u8]> = vec![&self.v[..], &separator[..]];
+            parts.extend_from_slice(provided_data);
+            *self.k = self.hmac(&parts);
+            *self.v = self.hmac(&[&self.v[..]]);
+        }
+    }
+
+    fn hmac(&self, parts: &[&[u8]]) -> [u8; OUTLEN] {
+        let mut mac = HmacSha256::new_from_slice(&self.k[..]).expect("HMAC accepts any key length");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.finalize().into_bytes().into()
+    }
+}
+
+static GLOBAL: Mutex<Option<HmacDrbg>> = Mutex::new(None);
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<HmacDrbg>> = const { RefCell::new(None) };
+}
+
+/// Replaces the process-wide DRBG.
+pub fn install(drbg: HmacDrbg) {
+    *GLOBAL.lock().unwrap_or_else(|e| e.into_inner()) = Some(drbg);
+}
+
+/// Runs `f` with `drbg` supplying all randomness drawn on this thread, then
+/// restores the previous generator and hands `drbg` back.
+pub fn with_drbg<R>(drbg: HmacDrbg, f: impl FnOnce() -> R) -> (R, HmacDrbg) {
+    let previous = OVERRIDE.with(|o| o.borrow_mut().replace(drbg));
+    let result = f();
+    let drbg = OVERRIDE.with(|o| std::mem::replace(&mut *o.borrow_mut(), previous));
+    (result, drbg.expect("DRBG override removed while in use"))
+}
+
+/// Fills `out` from the thread's override, or else the process-wide DRBG
+/// (instantiated from the OS on first use). Every failure, from the entropy
+/// source or the DRBG itself, is an [`ErrorKind::Entropy`] error.
+pub fn fill(out: &mut [u8]) -> Result<(), Error> {
+    let overridden = OVERRIDE.with(|o| o.borrow_mut().as_mut().map(|drbg| drbg.generate(out, &[])));
+    let res = overridden.unwrap_or_else(|| {
+        let mut global = GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+        if global.is_none() {
+            *global = Some(HmacDrbg::new(PERSONALIZATION)?);
+        }
+        global.as_mut().unwrap().generate(out, &[])
+    });
+    res.map_err(|e| Error::new(ErrorKind::Entropy, e))
+}
+
+/// Draws a fixed-size random array from the DRBG.
+pub fn random<const N: usize>() -> Result<Zeroizing<[u8; N]>, Error> {
+    let mut out = Zeroizing::new([0u8; N]);
+    fill(&mut out[..])?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s.replace(char::is_whitespace, "")).unwrap()
+    }
+
+    // NIST CAVP HMAC_DRBG.rsp, [SHA-256] no prediction resistance, COUNT = 0
+    #[test]
+    fn cavp_sha256_no_reseed() {
+        let mut drbg = HmacDrbg::from_seed(
+            &h("ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488"),
+            &h("659ba96c601dc69fc902940805ec0ca8"),
+            &[],
+        );
+        let mut out = [0u8; 128];
+        drbg.generate(&mut out, &[]).unwrap();
+        drbg.generate(&mut out, &[]).unwrap();
+        assert_eq!(
+            out[..],
+            h(
+                "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89
+               d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1
+               07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668
+               961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8"
+            )[..]
+        );
+    }
+
+    #[test]
+    fn personalization_reseed_and_additional_input() {
+        let mut drbg = HmacDrbg::from_seed(&[0x11; 32], &[0x22; 16], b"personal");
+        let mut out = [0u8; 40];
+        drbg.generate(&mut out, b"extra").unwrap();
+        drbg.reseed_with(&[0x33; 32], b"again");
+        drbg.generate(&mut out, &[]).unwrap();
+        assert_eq!(hex::encode(out), KAT_PERSONALIZED);
+    }
+
+    #[test]
+    fn deterministic_instance_cannot_reseed_itself() {
+        let mut drbg = HmacDrbg::from_seed(&[1; 32], &[2; 16], &[]).with_reseed_interval(2);
+        let mut out = [0u8; 16];
+        drbg.generate(&mut out, &[]).unwrap();
+        drbg.generate(&mut out, &[]).unwrap();
+        assert!(drbg.generate(&mut out, &[]).is_err());
+        drbg.reseed_with(&[3; 32], &[]);
+        assert!(drbg.generate(&mut out, &[]).is_ok());
+
+        let mut pr = HmacDrbg::from_seed(&[1; 32], &[2; 16], &[]).with_prediction_resistance(true);
+        assert!(pr.generate(&mut out, &[]).is_err());
+    }
+
+    #[test]
+    fn os_instance_reseeds_automatically() {
+        let mut drbg = HmacDrbg::new(b"test")
+            .unwrap()
+            .with_reseed_interval(1)
+            .with_prediction_resistance(true);
+        let (mut a, mut b) = ([0u8; 32], [0u8; 32]);
+        drbg.generate(&mut a, &[]).unwrap();
+        drbg.generate(&mut b, &[]).unwrap();
+        assert_ne!(a, b);
+        assert!(drbg
+            .generate(&mut [0u8; MAX_REQUEST_BYTES + 1], &[])
+            .is_err());
+    }
+
+    #[test]
+    fn thread_override_is_deterministic() {
+        let draw = || (random::<32>().unwrap(), random::<32>().unwrap());
+        let seeded = || HmacDrbg::from_seed(&[7; 32], &[8; 16], &[]);
+        let (first, _) = with_drbg(seeded(), draw);
+        let (second, _) = with_drbg(seeded(), draw);
+        assert_eq!(*first.0, *second.0);
+        assert_eq!(*first.1, *second.1);
+        assert_ne!(*first.0, *random::<32>().unwrap());
+    }
+
+    #[test]
+    fn keygen_is_reproducible_under_a_seeded_drbg() {
+        use ml_kem::EncodedSizeUser;
+        let keygen = || crate::selftest::generate_keypair().unwrap().1.as_bytes();
+        let seeded = || HmacDrbg::from_seed(&[9; 32], &[10; 16], b"keygen");
+        assert_eq!(with_drbg(seeded(), keygen).0, with_drbg(seeded(), keygen).0);
+    }
+
+    // Cross-checked against an independent Python implementation of 10.1.2
+    const KAT_PERSONALIZED: &str =
+        "dede4f2b5a172f20ee2afc3bc17f80d0789a85b2bc14106784b4f2755f7a479b5d0f9efb2510efcd";
+}
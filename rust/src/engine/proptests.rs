@@ -0,0 +1,144 @@
+//! Property-based tests for the envelope format.
+//!
+//! Every case runs a full ML-KEM encapsulation and decapsulation, so the case
+//! counts are kept well below proptest's default of 256.
+
+use super::tests::FORCE_DPA;
+use super::*;
+use crate::host::unix_time;
+use ml_kem::{EncodedSizeUser, KemCore, MlKem1024};
+use proptest::prelude::*;
+use std::sync::OnceLock;
+
+/// Keypairs are expensive in debug builds; every case shares the same two.
+fn keypairs() -> &'static [(String, String); 2] {
+    static KEYS: OnceLock<[(String, String); 2]> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        [(); 2].map(|_| {
+            let (dk, ek) = MlKem1024::generate(&mut rand::rngs::OsRng);
+            (hex::encode(ek.as_bytes()), hex::encode(dk.as_bytes()))
+        })
+    })
+}
+
+fn ascii(len: usize) -> impl Strategy<Value = String> {
+    prop::collection::vec(0x20u8..0x7f, len).prop_map(|b| String::from_utf8(b).unwrap())
+}
+
+/// Payloads around the 64-byte block boundary, arbitrary Unicode, and large buffers.
+fn payload() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::sample::select(vec![0usize, 1, 63, 64, 65, 127, 128, 129]).prop_flat_map(ascii),
+        ".{0,300}",
+        (4096usize..70_000).prop_flat_map(ascii),
+    ]
+}
+
+fn hwid() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(any::<u8>(), 1..64)
+}
+
+fn encrypt_at(payload: &str, hwid: Option<&[u8]>, ts: u64) -> String {
+    let pk = &keypairs()[0].0;
+    DarkstarCrypt::new()
+        .encrypt_bound(payload.as_bytes(), pk, Binding::Hwid(hwid), ts, false)
+        .unwrap()
+}
+
+fn decrypt(
+    envelope: &str,
+    hwid: Option<&[u8]>,
+    ttl: Option<u64>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let sk = &keypairs()[0].1;
+    DarkstarCrypt::new().decrypt(envelope, sk, hwid.map(<[u8]>::to_vec), false, ttl)
+}
+
+/// Flips one bit of a hex-encoded envelope field.
+fn flip_hex(v: &mut serde_json::Value, field: &str, bit: usize) {
+    let mut bytes = hex::decode(v[field].as_str().unwrap()).unwrap();
+    let bit = bit % (bytes.len() * 8);
+    bytes[bit / 8] ^= 1 << (bit % 8);
+    v[field] = hex::encode(bytes).into();
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn round_trip(payload in payload(), hwid in prop::option::of(hwid())) {
+        let env = encrypt_at(&payload, hwid.as_deref(), unix_time().unwrap());
+        prop_assert_eq!(decrypt(&env, hwid.as_deref(), None).unwrap(), payload);
+    }
+
+    #[test]
+    fn round_trip_within_ttl(payload in payload(), ttl in 60u64..1_000_000, age_pct in 0u64..50) {
+        let ts = unix_time().unwrap() - ttl * age_pct / 100;
+        let env = encrypt_at(&payload, None, ts);
+        prop_assert_eq!(decrypt(&env, None, Some(ttl)).unwrap(), payload);
+    }
+
+    #[test]
+    fn expired_payload_is_rejected(ttl in 0u64..1_000_000, overdue in 2u64..1_000_000) {
+        let env = encrypt_at("stale", None, unix_time().unwrap() - ttl - overdue);
+        let err = decrypt(&env, None, Some(ttl)).unwrap_err().to_string();
+        prop_assert!(err.contains("Payload Expired"), "{}", err);
+    }
+
+    #[test]
+    fn any_single_bit_flip_is_rejected(
+        payload in payload(),
+        field in prop::sample::select(vec!["data", "ct", "mac", "ts"]),
+        bit in any::<usize>(),
+    ) {
+        prop_assume!(field != "data" || !payload.is_empty());
+        let env = encrypt_at(&payload, None, unix_time().unwrap());
+        let mut v: serde_json::Value = serde_json::from_str(&env).unwrap();
+        if field == "ts" {
+            let ts = v["ts"].as_u64().unwrap();
+            v["ts"] = (ts ^ (1u64 << (bit % 64))).into();
+        } else {
+            flip_hex(&mut v, field, bit);
+        }
+        let err = decrypt(&v.to_string(), None, None).unwrap_err().to_string();
+        prop_assert_eq!(err, "Integrity Check Failed");
+    }
+
+    #[test]
+    fn wrong_hwid_is_rejected(payload in payload(), bound in hwid(), other in prop::option::of(hwid())) {
+        prop_assume!(other.as_ref() != Some(&bound));
+        let env = encrypt_at(&payload, Some(&bound), unix_time().unwrap());
+        prop_assert!(decrypt(&env, other.as_deref(), None).is_err());
+    }
+
+    #[test]
+    fn unbound_payload_rejects_any_hwid(payload in payload(), hwid in hwid()) {
+        let env = encrypt_at(&payload, None, unix_time().unwrap());
+        prop_assert!(decrypt(&env, Some(&hwid), None).is_err());
+    }
+
+    #[test]
+    fn wrong_key_is_rejected(payload in payload(), hwid in prop::option::of(hwid())) {
+        let env = encrypt_at(&payload, hwid.as_deref(), unix_time().unwrap());
+        let other_sk = &keypairs()[1].1;
+        let err = DarkstarCrypt::new()
+            .decrypt(&env, other_sk, hwid, false, None)
+            .unwrap_err()
+            .to_string();
+        prop_assert_eq!(err, "Integrity Check Failed");
+    }
+
+    #[test]
+    fn dpa_lockout_refuses_every_payload(payload in payload()) {
+        let pk = &keypairs()[0].0;
+        FORCE_DPA.with(|f| f.set(true));
+        let res = DarkstarCrypt::new().encrypt(&payload, pk, &[], false);
+        FORCE_DPA.with(|f| f.set(false));
+        prop_assert!(res.unwrap_err().to_string().starts_with("DPA_LOCKOUT"));
+    }
+
+    #[test]
+    fn fresh_signatures_never_lock_out(sig in 1u64..) {
+        prop_assert!(!DarkstarCrypt::check_dpa_pattern(sig));
+    }
+}
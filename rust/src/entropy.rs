@@ -0,0 +1,219 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Health-Tested Entropy Source
+ *
+ * Seeds and reseeds the `drbg` that supplies every key, encapsulation and seal
+ * seed. Each byte is fed to the two continuous health tests of NIST
+ * SP 800-90B section 4.4 before it is handed out, and a failing draw is
+ * returned as an error instead of seeding a key:
+ *
+ *   Repetition Count Test  - C = 1 + ceil(-log2(alpha) / H)
+ *   Adaptive Proportion    - W = 512, C = 1 + CRITBINOM(W, 2^-H, 1 - alpha)
+ *
+ * with alpha = 2^-40 and H = 8 bits per byte (the OS and WASM host sources are
+ * conditioned, full-entropy outputs), giving cutoffs of 6 and 19. The first
+ * draw in a process also runs the start-up test over 1024 samples.
+ *
+ * On WASM the samples come from the host's `host_getrandom`, which cannot
+ * report failure itself; a stuck or constant host is caught here.
+ */
+
+use std::sync::Mutex;
+
+/// Repetition Count Test cutoff: this many identical bytes in a row fail.
+const RCT_CUTOFF: u32 = 6;
+/// Adaptive Proportion Test window size (non-binary samples).
+const APT_WINDOW: u32 = 512;
+/// Adaptive Proportion Test cutoff: this many copies of the window's first byte fail.
+const APT_CUTOFF: u32 = 19;
+/// Samples drawn and discarded by the start-up test.
+const STARTUP_SAMPLES: usize = 1024;
+
+/// Running state of both continuous tests. The tests span draws, so a source
+/// that repeats across short requests is caught just like one that repeats
+/// within a long one.
+#[derive(Default)]
+pub(crate) struct HealthTests {
+    started: bool,
+    rct_last: u8,
+    rct_count: u32,
+    apt_ref: u8,
+    apt_count: u32,
+    apt_seen: u32,
+}
+
+impl HealthTests {
+    pub(crate) const fn new() -> Self {
+        HealthTests {
+            started: false,
+            rct_last: 0,
+            rct_count: 0,
+            apt_ref: 0,
+            apt_count: 0,
+            apt_seen: 0,
+        }
+    }
+
+    /// Feeds one sample through both tests.
+    fn sample(&mut self, b: u8) -> Result<(), String> {
+        if self.rct_count > 0 && b == self.rct_last {
+            self.rct_count += 1;
+            if self.rct_count >= RCT_CUTOFF {
+                return Err(format!(
+                    "Entropy source failed repetition count test ({} identical samples)",
+                    self.rct_count
+                ));
+            }
+        } else {
+            self.rct_last = b;
+            self.rct_count = 1;
+        }
+
+        if self.apt_seen == 0 {
+            self.apt_ref = b;
+            self.apt_count = 1;
+        } else if b == self.apt_ref {
+            self.apt_count += 1;
+            if self.apt_count >= APT_CUTOFF {
+                return Err(format!(
+                    "Entropy source failed adaptive proportion test ({} of {} samples identical)",
+                    self.apt_count, APT_WINDOW
+                ));
+            }
+        }
+        self.apt_seen = (self.apt_seen + 1) % APT_WINDOW;
+        Ok(())
+    }
+
+    /// Runs every byte of `buf` through the tests. After a failure the state is
+    /// reset, so the next draw starts over (including the start-up test).
+    pub(crate) fn check(
+        &mut self,
+        buf: &[u8],
+        source: impl Fn(&mut [u8]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let res = self.run(buf, source);
+        if res.is_err() {
+            *self = HealthTests::new();
+        }
+        res
+    }
+
+    fn run(
+        &mut self,
+        buf: &[u8],
+        source: impl Fn(&mut [u8]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        if !self.started {
+            let mut startup = [0u8; STARTUP_SAMPLES];
+            source(&mut startup)?;
+            for &b in &startup {
+                self.sample(b)?;
+            }
+            self.started = true;
+        }
+        buf.iter().try_for_each(|&b| self.sample(b))
+    }
+}
+
+static HEALTH: Mutex<HealthTests> = Mutex::new(HealthTests::new());
+
+fn os_source(buf: &mut [u8]) -> Result<(), String> {
+    #[cfg(target_arch = "wasm32")]
+    let res = crate::host::random_fill(buf);
+    #[cfg(not(target_arch = "wasm32"))]
+    let res = getrandom::getrandom(buf).map_err(|e| e.to_string());
+    res.map_err(|e| format!("Entropy source unavailable: {}", e))
+}
+
+/// Fills `buf` from the OS (or WASM host) entropy source, running the
+/// SP 800-90B health tests on every byte. On error `buf` is zeroed.
+pub fn fill(buf: &mut [u8]) -> Result<(), String> {
+    let res = os_source(buf).and_then(|()| {
+        let mut health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+        health.check(buf, os_source)
+    });
+    if res.is_err() {
+        zeroize::Zeroize::zeroize(buf);
+    }
+    res
+}
+
+/// Draws a fixed-size, health-tested random array.
+pub fn random<const N: usize>() -> Result<zeroize::Zeroizing<[u8; N]>, String> {
+    let mut out = zeroize::Zeroizing::new([0u8; N]);
+    fill(&mut out[..])?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok_source(buf: &mut [u8]) -> Result<(), String> {
+        getrandom::getrandom(buf).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn os_source_passes() {
+        let mut health = HealthTests::new();
+        let mut buf = [0u8; 4096];
+        for _ in 0..64 {
+            ok_source(&mut buf).unwrap();
+            health.check(&buf, ok_source).unwrap();
+        }
+        assert!(fill(&mut buf).is_ok());
+    }
+
+    #[test]
+    fn stuck_source_fails_repetition_count() {
+        let mut health = HealthTests::new();
+        let stuck = |b: &mut [u8]| {
+            b.fill(0);
+            Ok(())
+        };
+        let err = health.check(&[0u8; 32], stuck).unwrap_err();
+        assert!(err.contains("repetition count"), "{}", err);
+    }
+
+    #[test]
+    fn repetition_spanning_draws_is_caught() {
+        let mut health = HealthTests::new();
+        health.check(&[1, 2, 3, 9, 9, 9], ok_source).unwrap();
+        assert!(health.check(&[9, 9, 9], ok_source).is_err());
+    }
+
+    #[test]
+    fn biased_source_fails_adaptive_proportion() {
+        // Never repeats back-to-back, but half the samples are the same value
+        let biased: Vec<u8> = (0..512u32)
+            .map(|i| if i % 2 == 0 { 0xaa } else { (i / 2) as u8 })
+            .collect();
+        let mut health = HealthTests::new();
+        let err = health.check(&biased, ok_source).unwrap_err();
+        assert!(err.contains("adaptive proportion"), "{}", err);
+    }
+
+    #[test]
+    fn failing_startup_test_is_reported() {
+        let mut health = HealthTests::new();
+        let counter_then_stuck = |b: &mut [u8]| {
+            for (i, x) in b.iter_mut().enumerate() {
+                *x = if i < 100 { i as u8 } else { 0x42 };
+            }
+            Ok(())
+        };
+        assert!(health.check(&[1, 2, 3], counter_then_stuck).is_err());
+        // State is reset, so a healthy source starts over cleanly
+        assert!(health.check(&[1, 2, 3], ok_source).is_ok());
+    }
+
+    #[test]
+    fn unavailable_source_is_an_error() {
+        let mut health = HealthTests::new();
+        let broken = |_: &mut [u8]| Err("host RNG missing".to_string());
+        assert!(health.check(&[1, 2, 3], broken).is_err());
+    }
+}
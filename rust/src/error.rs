@@ -0,0 +1,115 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Error Kinds
+ *
+ * Most errors in the crate are plain messages. The failures a caller may need
+ * to act on (a tampered envelope, an expired one, a broken entropy source or
+ * clock, the DPA lockout, the self-test error state) are raised as an `Error`
+ * that also carries an `ErrorKind`. It travels inside the usual
+ * `Box<dyn std::error::Error>`, so the C API recovers the kind with
+ * `kind_of` instead of parsing the message.
+ */
+
+/// What kind of failure an [`Error`] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The module is in the self-test error state.
+    SelfTest,
+    /// A tag or hardware binding did not verify.
+    Integrity,
+    /// The envelope is older than the TTL, or has no timestamp to check.
+    Expired,
+    /// The entropy source or DRBG failed.
+    Entropy,
+    /// Encryption was refused by the DPA pattern lockout.
+    Lockout,
+    /// The wall clock is unavailable or set before 1970.
+    Clock,
+    /// Anything else: malformed input, a wrong key type, and so on.
+    InvalidArgument,
+}
+
+/// A message with its [`ErrorKind`]. It displays as the message alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Error {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Plain messages are invalid-argument errors.
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::new(ErrorKind::InvalidArgument, message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::new(ErrorKind::InvalidArgument, message)
+    }
+}
+
+/// Lets the many `Result<_, String>` helpers keep using `?`; the kind is dropped.
+impl From<Error> for String {
+    fn from(e: Error) -> Self {
+        e.message
+    }
+}
+
+/// The kind of any error returned by the engine. Errors that are not an
+/// [`Error`] are plain messages, so they are invalid-argument errors.
+pub fn kind_of(e: &(dyn std::error::Error + 'static)) -> ErrorKind {
+    e.downcast_ref::<Error>()
+        .map_or(ErrorKind::InvalidArgument, Error::kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_survives_boxing_and_messages_do_not_gain_one() {
+        let boxed: Box<dyn std::error::Error> = Error::new(
+            ErrorKind::Clock,
+            "System clock is set before the Unix epoch",
+        )
+        .into();
+        assert_eq!(kind_of(&*boxed), ErrorKind::Clock);
+        assert_eq!(
+            boxed.to_string(),
+            "System clock is set before the Unix epoch"
+        );
+
+        // A message that merely looks like a typed failure stays untyped
+        let plain: Box<dyn std::error::Error> = "Integrity Check Failed".into();
+        assert_eq!(kind_of(&*plain), ErrorKind::InvalidArgument);
+        assert_eq!(Error::from("x").kind(), ErrorKind::InvalidArgument);
+        assert_eq!(
+            String::from(Error::new(ErrorKind::Entropy, "e")),
+            "e".to_string()
+        );
+    }
+}
@@ -0,0 +1,183 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Fuzz Harnesses
+ *
+ * The bodies of the `fuzz/` targets. They live in the library so they can reach
+ * crate-private decoders, and so that every input under `fuzz/seeds/` and
+ * `fuzz/regressions/` is replayed by `cargo test` without a fuzzing toolchain.
+ * Only compiled for tests and with the `fuzzing` feature.
+ */
+
+use crate::engine::DarkstarCrypt;
+
+/// Secret half of the fixed keypair the seed envelopes were encrypted to
+/// (`fuzz/keys/fuzz_pk.hex` is the public half).
+pub const FUZZ_SK: &str = include_str!("../fuzz/keys/fuzz_sk.hex");
+/// HWID used by the bound seed envelopes.
+pub const FUZZ_HWID: [u8; 32] = [0x5a; 32];
+
+/// k-of-n components used by the fuzzy seed envelopes.
+pub fn fuzz_parts() -> Vec<Vec<u8>> {
+    vec![b"disk-0".to_vec(), b"nic-0".to_vec(), b"board-0".to_vec()]
+}
+
+/// Envelope decryption. The first byte selects the mode, the rest is the JSON:
+///
+/// * `0x01` - decrypt with [`FUZZ_HWID`]
+/// * `0x02` - enforce a TTL of `(flags >> 4) * 60` seconds
+/// * `0x04` - decrypt with the fuzzy components from [`fuzz_parts`]
+/// * `0x08` - telemetry output
+pub fn decrypt_envelope(data: &[u8]) {
+    let Some((&flags, json)) = data.split_first() else {
+        return;
+    };
+    let Ok(json) = std::str::from_utf8(json) else {
+        return;
+    };
+    let dc = DarkstarCrypt::new();
+    let ttl = (flags & 0x02 != 0).then_some(u64::from(flags >> 4) * 60);
+    let telemetry = flags & 0x08 != 0;
+    let sk = FUZZ_SK.trim();
+
+    // Any result is fine; only panics (and sanitizer reports) are findings
+    let _ = if flags & 0x04 != 0 {
+        dc.decrypt_fuzzy(json, sk, &fuzz_parts(), telemetry, ttl)
+    } else {
+        let hwid = (flags & 0x01 != 0).then(|| FUZZ_HWID.to_vec());
+        dc.decrypt(json, sk, hwid, telemetry, ttl)
+    };
+}
+
+/// The constant-time codec used for secrets against the `hex` crate, which
+/// decodes public keys and envelope fields; both must accept the same inputs.
+pub fn hex_decode(data: &[u8]) {
+    let s = String::from_utf8_lossy(data);
+    match (crate::codec::decode_hex(&s), hex::decode(&*s)) {
+        (Ok(ct), Ok(reference)) => assert_eq!(&ct[..], &reference[..]),
+        (Err(_), Err(_)) => {}
+        (ct, reference) => panic!("decoders disagree: {:?} vs {:?}", ct, reference),
+    }
+    let _ = crate::codec::decode_base64(&s);
+
+    let hex = crate::codec::encode_hex(data);
+    assert_eq!(&crate::codec::decode_hex(&hex).unwrap()[..], data);
+    let b64 = crate::codec::encode_base64(data);
+    assert_eq!(&crate::codec::decode_base64(&b64).unwrap()[..], data);
+}
+
+/// The C ABI block and hash exports against the safe Rust implementations.
+/// The first 64 bytes are the block, the next 1024 the round keys (zero-padded).
+pub fn ffi_block(data: &[u8]) {
+    use sha2::{Digest, Sha256, Sha512};
+
+    let mut input = [0u8; 64];
+    let mut key = [0u8; 1024];
+    let n = data.len().min(64);
+    input[..n].copy_from_slice(&data[..n]);
+    if data.len() > 64 {
+        let m = (data.len() - 64).min(1024);
+        key[..m].copy_from_slice(&data[64..64 + m]);
+    }
+
+    let mut round_keys = [0u64; 128];
+    for (rk, chunk) in round_keys.iter_mut().zip(key.chunks_exact(8)) {
+        *rk = u64::from_le_bytes(chunk.try_into().unwrap());
+    }
+    let mut expected = input;
+    crate::engine::dasp_cascade_64(&mut expected, &round_keys);
+
+    let mut out = [0u8; 64];
+    crate::dspna512_encrypt_block(input.as_ptr(), key.as_ptr(), out.as_mut_ptr());
+    assert_eq!(out, expected);
+    crate::dspna512_decrypt_block(input.as_ptr(), key.as_ptr(), out.as_mut_ptr());
+    assert_eq!(out, expected);
+
+    let mut digest256 = [0u8; 32];
+    crate::crypto_sha256(data.as_ptr(), data.len(), digest256.as_mut_ptr());
+    assert_eq!(digest256[..], Sha256::digest(data)[..]);
+    let mut digest512 = [0u8; 64];
+    crate::crypto_sha512(data.as_ptr(), data.len(), digest512.as_mut_ptr());
+    assert_eq!(digest512[..], Sha512::digest(data)[..]);
+
+    let (k, m) = data.split_at(data.len() / 2);
+    crate::crypto_hmac_sha256(
+        k.as_ptr(),
+        k.len(),
+        m.as_ptr(),
+        m.len(),
+        digest256.as_mut_ptr(),
+    );
+    crate::crypto_hmac_sha512(
+        k.as_ptr(),
+        k.len(),
+        m.as_ptr(),
+        m.len(),
+        digest512.as_mut_ptr(),
+    );
+}
+
+/// The CLI parser over NUL-separated arguments. `@file` values are refused
+/// instead of touching the filesystem.
+pub fn cli_args(data: &[u8]) {
+    let args: Vec<String> = data
+        .split(|&b| b == 0)
+        .map(|a| String::from_utf8_lossy(a).into_owned())
+        .collect();
+    let total = args.len();
+    let resolve = |s: &str| {
+        if s.starts_with('@') {
+            Err("file arguments are not fuzzed".to_string())
+        } else {
+            Ok(s.to_string())
+        }
+    };
+    if let Ok(opts) = crate::cli::parse_args(args, resolve) {
+        assert!(opts.args.len() <= total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Replays every seed and every saved crash for `target` through its harness.
+    fn replay(target: &str, harness: fn(&[u8])) {
+        let fuzz_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz");
+        let mut count = 0;
+        for kind in ["seeds", "regressions"] {
+            let Ok(entries) = std::fs::read_dir(fuzz_dir.join(kind).join(target)) else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry.unwrap().path();
+                harness(&std::fs::read(&path).unwrap());
+                count += 1;
+            }
+        }
+        assert!(count > 0, "no inputs for fuzz target {}", target);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // one ML-KEM decapsulation per seed
+    fn replay_decrypt_envelope() {
+        replay("decrypt_envelope", decrypt_envelope);
+    }
+
+    #[test]
+    fn replay_hex_decode() {
+        replay("hex_decode", hex_decode);
+    }
+
+    #[test]
+    fn replay_ffi_block() {
+        replay("ffi_block", ffi_block);
+    }
+
+    #[test]
+    fn replay_cli_args() {
+        replay("cli_args", cli_args);
+    }
+}
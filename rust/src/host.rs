@@ -0,0 +1,149 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Host Services: Entropy and Clocks
+ *
+ * Native builds use the OS directly. On `wasm32-unknown-unknown` there is no
+ * OS, so every service is a host import and every import can report failure;
+ * nothing here panics in the browser. The imports (all in the `env` module)
+ * and a reference JS implementation are documented in `wasm/dasp_host.js`:
+ *
+ *   host_getrandom(ptr, len) -> f64   fill `len` bytes at `ptr`; 0 = success
+ *   host_unix_time_ms() -> f64        wall clock, ms since the Unix epoch
+ *   host_gettime_us() -> f64          monotonic clock in microseconds
+ *
+ * `host_getrandom` returns an f64 rather than an i32 so that a JS function
+ * returning nothing arrives as NaN, not 0: only an explicit 0 is success, and
+ * a shim written against the older void import fails closed.
+ *
+ * With the `js` feature the same three services are bound through wasm-bindgen
+ * to `crypto.getRandomValues`, `Date.now` and `performance.now`, so the
+ * generated glue needs no `env` module.
+ */
+
+use crate::error::{Error, ErrorKind};
+
+#[cfg(all(target_arch = "wasm32", not(feature = "js")))]
+mod imports {
+    extern "C" {
+        pub fn host_getrandom(ptr: *mut u8, len: usize) -> f64;
+        pub fn host_unix_time_ms() -> f64;
+        pub fn host_gettime_us() -> f64;
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "js"))]
+mod imports {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = crypto, js_name = getRandomValues, catch)]
+        fn get_random_values(buf: &mut [u8]) -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(js_namespace = Date, js_name = now)]
+        fn date_now() -> f64;
+        #[wasm_bindgen(js_namespace = performance, js_name = now)]
+        fn performance_now() -> f64;
+    }
+
+    /// `getRandomValues` rejects requests over 65536 bytes, so larger buffers
+    /// are filled in chunks.
+    pub unsafe fn host_getrandom(ptr: *mut u8, len: usize) -> f64 {
+        let buf = std::slice::from_raw_parts_mut(ptr, len);
+        for chunk in buf.chunks_mut(65536) {
+            if get_random_values(chunk).is_err() {
+                return 1.0;
+            }
+        }
+        0.0
+    }
+
+    pub unsafe fn host_unix_time_ms() -> f64 {
+        date_now()
+    }
+
+    pub unsafe fn host_gettime_us() -> f64 {
+        performance_now() * 1000.0
+    }
+}
+
+/// Fills `buf` from the host's CSPRNG (the `getrandom` backend on WASM).
+#[cfg(target_arch = "wasm32")]
+pub fn random_fill(buf: &mut [u8]) -> Result<(), String> {
+    let status = unsafe { imports::host_getrandom(buf.as_mut_ptr(), buf.len()) };
+    if status == 0.0 {
+        Ok(())
+    } else {
+        Err(format!("host_getrandom failed (code {})", status))
+    }
+}
+
+/// Seconds since the Unix epoch, from the wall clock.
+///
+/// Used for envelope timestamps and TTL checks, so a broken or pre-1970
+/// clock is an error rather than a bogus `ts`.
+pub fn unix_time() -> Result<u64, Error> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let ms = unsafe { imports::host_unix_time_ms() };
+        if !ms.is_finite() || ms < 0.0 {
+            return Err(Error::new(
+                ErrorKind::Clock,
+                format!("host_unix_time_ms returned an invalid time ({})", ms),
+            ));
+        }
+        Ok((ms / 1000.0) as u64)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::Clock,
+                    "System clock is set before the Unix epoch",
+                )
+            })
+    }
+}
+
+/// Monotonic timer for telemetry, backed by `host_gettime_us` on WASM.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Copy)]
+pub struct Instant {
+    start_us: f64,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    pub fn now() -> Self {
+        let us = unsafe { imports::host_gettime_us() };
+        Instant { start_us: us }
+    }
+    pub fn elapsed(&self) -> core::time::Duration {
+        let us = unsafe { imports::host_gettime_us() };
+        // Telemetry only: a misbehaving host clock yields zero, never a panic
+        let diff = us - self.start_us;
+        if diff.is_finite() && diff > 0.0 {
+            core::time::Duration::from_micros(diff as u64)
+        } else {
+            core::time::Duration::ZERO
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_time_is_current() {
+        // 2024-01-01T00:00:00Z
+        assert!(unix_time().unwrap() > 1_704_067_200);
+    }
+}
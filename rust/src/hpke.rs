@@ -0,0 +1,700 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * HPKE (RFC 9180) over ML-KEM
+ *
+ * A standard alternative to the native envelope for peers that cannot run the
+ * D-ASP key derivation: any RFC 9180 library with an ML-KEM KEM can open what
+ * this module seals, and the other way round.
+ *
+ *   KEM    ML-KEM-512 / 768 / 1024   0x0040 / 0x0041 / 0x0042
+ *          X25519 + ML-KEM-1024      0xFF42 (unregistered; this crate only)
+ *   KDF    HKDF-SHA256 / SHA512      0x0001 / 0x0003
+ *   AEAD   AES-128-GCM / AES-256-GCM / ChaCha20-Poly1305 / export-only
+ *          0x0001 / 0x0002 / 0x0003 / 0xFFFF
+ *
+ * ML-KEM is used as specified in draft-ietf-hpke-pq: `enc` is the ML-KEM
+ * ciphertext and the shared secret goes into the key schedule unchanged. The
+ * hybrid KEM's `enc` is the ML-KEM ciphertext followed by the ephemeral X25519
+ * key, and its secret is the combiner from `hybrid`.
+ *
+ * Base and PSK modes are supported. Auth and AuthPSK need AuthEncap, which
+ * only Diffie-Hellman KEMs have, so `setup_sender` never produces them. The
+ * KEM-independent part (key schedule, AEAD, exporter) is checked against the
+ * RFC 9180 A.1 vectors; no published vectors cover ML-KEM suites yet.
+ */
+
+use crate::kem::{self, ParameterSet};
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use sha2::{Sha256, Sha512};
+use zeroize::Zeroizing;
+
+const NONCE_BYTES: usize = 12;
+const TAG_BYTES: usize = 16;
+
+/// The four RFC 9180 modes, by their key schedule identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Base = 0,
+    Psk = 1,
+    Auth = 2,
+    AuthPsk = 3,
+}
+
+/// A KEM this module can encapsulate to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kem {
+    MlKem(ParameterSet),
+    Hybrid,
+}
+
+impl Kem {
+    pub fn id(self) -> u16 {
+        match self {
+            Kem::MlKem(ParameterSet::MlKem512) => 0x0040,
+            Kem::MlKem(ParameterSet::MlKem768) => 0x0041,
+            Kem::MlKem(ParameterSet::MlKem1024) => 0x0042,
+            Kem::Hybrid => 0xff42,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        Self::all().find(|kem| kem.id() == id)
+    }
+
+    /// The KEM a public key belongs to; every supported length is distinct.
+    pub fn from_public_key_len(len: usize) -> Option<Self> {
+        match len {
+            crate::hybrid::PUBLIC_KEY_BYTES => Some(Kem::Hybrid),
+            _ => ParameterSet::from_public_key_len(len).map(Kem::MlKem),
+        }
+    }
+
+    fn all() -> impl Iterator<Item = Kem> {
+        ParameterSet::ALL
+            .into_iter()
+            .map(Kem::MlKem)
+            .chain([Kem::Hybrid])
+    }
+
+    pub(crate) fn secret_key_bytes(self) -> usize {
+        match self {
+            Kem::MlKem(set) => set.secret_key_bytes(),
+            Kem::Hybrid => crate::hybrid::SECRET_KEY_BYTES,
+        }
+    }
+
+    fn enc_bytes(self) -> usize {
+        match self {
+            Kem::MlKem(set) => set.ciphertext_bytes(),
+            Kem::Hybrid => kem::CIPHERTEXT_BYTES + crate::hybrid::X25519_BYTES,
+        }
+    }
+
+    fn encap(self, pk: &[u8]) -> Result<(Vec<u8>, kem::SharedSecret), String> {
+        match self {
+            Kem::MlKem(set) => Ok(set.encaps(pk)?),
+            Kem::Hybrid => {
+                let pk = <&crate::hybrid::PublicKey>::try_from(pk)
+                    .map_err(|_| "Invalid public key length".to_string())?;
+                let (ct, ss) = crate::hybrid::encaps(pk)?;
+                Ok(([&ct.mlkem[..], &ct.x25519[..]].concat(), ss))
+            }
+        }
+    }
+
+    fn decap(self, enc: &[u8], sk: &[u8]) -> Result<kem::SharedSecret, String> {
+        if enc.len() != self.enc_bytes() {
+            return Err(format!("HPKE enc must be {} bytes", self.enc_bytes()));
+        }
+        match self {
+            Kem::MlKem(set) => Ok(set.decaps(sk, enc)?),
+            Kem::Hybrid => {
+                let (mlkem, x25519) = enc.split_at(kem::CIPHERTEXT_BYTES);
+                let ct = crate::hybrid::Ciphertext {
+                    mlkem: mlkem.try_into().expect("split at the ML-KEM length"),
+                    x25519: x25519.try_into().expect("rest is the X25519 key"),
+                };
+                Ok(crate::hybrid::decaps(sk, &ct)?)
+            }
+        }
+    }
+}
+
+/// The key derivation function of a suite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    HkdfSha256,
+    HkdfSha512,
+}
+
+impl Kdf {
+    pub fn id(self) -> u16 {
+        match self {
+            Kdf::HkdfSha256 => 0x0001,
+            Kdf::HkdfSha512 => 0x0003,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        [Kdf::HkdfSha256, Kdf::HkdfSha512]
+            .into_iter()
+            .find(|kdf| kdf.id() == id)
+    }
+
+    /// Parses `hkdf-sha256` / `hkdf-sha512`, with or without the `hkdf-`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().trim_start_matches("hkdf-") {
+            "sha256" => Some(Kdf::HkdfSha256),
+            "sha512" => Some(Kdf::HkdfSha512),
+            _ => None,
+        }
+    }
+
+    fn hash_bytes(self) -> usize {
+        match self {
+            Kdf::HkdfSha256 => 32,
+            Kdf::HkdfSha512 => 64,
+        }
+    }
+
+    fn extract(self, salt: &[u8], ikm: &[&[u8]]) -> Zeroizing<Vec<u8>> {
+        macro_rules! extract {
+            ($hash:ty) => {{
+                let mut hkdf = hkdf::HkdfExtract::<$hash>::new(Some(salt));
+                ikm.iter().for_each(|part| hkdf.input_ikm(part));
+                Zeroizing::new(hkdf.finalize().0.to_vec())
+            }};
+        }
+        match self {
+            Kdf::HkdfSha256 => extract!(Sha256),
+            Kdf::HkdfSha512 => extract!(Sha512),
+        }
+    }
+
+    fn expand(self, prk: &[u8], info: &[&[u8]], out: &mut [u8]) -> Result<(), String> {
+        let expanded = match self {
+            Kdf::HkdfSha256 => hkdf::Hkdf::<Sha256>::from_prk(prk)
+                .map_err(|_| "Invalid HPKE PRK length".to_string())?
+                .expand_multi_info(info, out),
+            Kdf::HkdfSha512 => hkdf::Hkdf::<Sha512>::from_prk(prk)
+                .map_err(|_| "Invalid HPKE PRK length".to_string())?
+                .expand_multi_info(info, out),
+        };
+        expanded.map_err(|_| format!("HPKE cannot expand to {} bytes", out.len()))
+    }
+}
+
+/// The AEAD of a suite. `ExportOnly` contexts can only `export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aead {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+    ExportOnly,
+}
+
+impl Aead {
+    pub fn id(self) -> u16 {
+        match self {
+            Aead::Aes128Gcm => 0x0001,
+            Aead::Aes256Gcm => 0x0002,
+            Aead::ChaCha20Poly1305 => 0x0003,
+            Aead::ExportOnly => 0xffff,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        [
+            Aead::Aes128Gcm,
+            Aead::Aes256Gcm,
+            Aead::ChaCha20Poly1305,
+            Aead::ExportOnly,
+        ]
+        .into_iter()
+        .find(|aead| aead.id() == id)
+    }
+
+    /// Parses `aes-128-gcm`, `aes-256-gcm`, `chacha20-poly1305` or `export-only`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "aes-128-gcm" => Some(Aead::Aes128Gcm),
+            "aes-256-gcm" => Some(Aead::Aes256Gcm),
+            "chacha20-poly1305" => Some(Aead::ChaCha20Poly1305),
+            "export-only" => Some(Aead::ExportOnly),
+            _ => None,
+        }
+    }
+
+    fn key_bytes(self) -> usize {
+        match self {
+            Aead::Aes128Gcm => 16,
+            Aead::Aes256Gcm | Aead::ChaCha20Poly1305 => 32,
+            Aead::ExportOnly => 0,
+        }
+    }
+
+    /// Encrypts `buf` in place and returns the tag.
+    fn seal(
+        self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        buf: &mut [u8],
+    ) -> Result<[u8; TAG_BYTES], String> {
+        macro_rules! seal {
+            ($cipher:ty) => {
+                <$cipher>::new_from_slice(key)
+                    .map_err(|_| "Invalid AEAD key length".to_string())?
+                    .encrypt_in_place_detached(nonce.into(), aad, buf)
+                    .map(Into::into)
+            };
+        }
+        let tag = match self {
+            Aead::Aes128Gcm => seal!(aes_gcm::Aes128Gcm),
+            Aead::Aes256Gcm => seal!(aes_gcm::Aes256Gcm),
+            Aead::ChaCha20Poly1305 => seal!(chacha20poly1305::ChaCha20Poly1305),
+            Aead::ExportOnly => return Err("An export-only HPKE context cannot seal".into()),
+        };
+        tag.map_err(|_| "HPKE encryption failed".to_string())
+    }
+
+    /// Decrypts `buf` in place after checking `tag`.
+    fn open(
+        self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), String> {
+        macro_rules! open {
+            ($cipher:ty) => {
+                <$cipher>::new_from_slice(key)
+                    .map_err(|_| "Invalid AEAD key length".to_string())?
+                    .decrypt_in_place_detached(nonce.into(), aad, buf, tag.into())
+            };
+        }
+        let opened = match self {
+            Aead::Aes128Gcm => open!(aes_gcm::Aes128Gcm),
+            Aead::Aes256Gcm => open!(aes_gcm::Aes256Gcm),
+            Aead::ChaCha20Poly1305 => open!(chacha20poly1305::ChaCha20Poly1305),
+            Aead::ExportOnly => return Err("An export-only HPKE context cannot open".into()),
+        };
+        opened.map_err(|_| "Integrity Check Failed".to_string())
+    }
+}
+
+/// A ciphersuite: one KEM, KDF and AEAD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Suite {
+    pub kem: Kem,
+    pub kdf: Kdf,
+    pub aead: Aead,
+}
+
+impl Suite {
+    fn schedule(&self) -> Schedule {
+        Schedule::new(self.kem.id(), self.kdf, self.aead)
+    }
+}
+
+/// What the key schedule and a context need of a suite: its ID for the
+/// labels, the KDF and the AEAD. The KEM itself plays no further part.
+#[derive(Clone, Copy)]
+struct Schedule {
+    suite_id: [u8; 10],
+    kdf: Kdf,
+    aead: Aead,
+}
+
+impl Schedule {
+    /// `suite_id = "HPKE" || kem_id || kdf_id || aead_id`.
+    fn new(kem_id: u16, kdf: Kdf, aead: Aead) -> Self {
+        let mut suite_id = *b"HPKE\0\0\0\0\0\0";
+        suite_id[4..6].copy_from_slice(&kem_id.to_be_bytes());
+        suite_id[6..8].copy_from_slice(&kdf.id().to_be_bytes());
+        suite_id[8..].copy_from_slice(&aead.id().to_be_bytes());
+        Schedule {
+            suite_id,
+            kdf,
+            aead,
+        }
+    }
+
+    fn labeled_extract(&self, salt: &[u8], label: &[u8], ikm: &[u8]) -> Zeroizing<Vec<u8>> {
+        self.kdf
+            .extract(salt, &[b"HPKE-v1", &self.suite_id, label, ikm])
+    }
+
+    fn labeled_expand(
+        &self,
+        prk: &[u8],
+        label: &[u8],
+        info: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), String> {
+        let len = u16::try_from(out.len())
+            .map_err(|_| format!("HPKE cannot expand to {} bytes", out.len()))?;
+        self.kdf.expand(
+            prk,
+            &[&len.to_be_bytes(), b"HPKE-v1", &self.suite_id, label, info],
+            out,
+        )
+    }
+
+    /// The RFC 9180 key schedule, for any mode.
+    fn run(
+        self,
+        mode: Mode,
+        shared_secret: &[u8],
+        info: &[u8],
+        psk: Option<Psk<'_>>,
+    ) -> Result<Context, String> {
+        let (psk, psk_id) = match (mode, psk) {
+            (Mode::Base | Mode::Auth, None) => (&[][..], &[][..]),
+            (Mode::Psk | Mode::AuthPsk, Some(p)) if p.key.len() >= 32 && !p.id.is_empty() => {
+                (p.key, p.id)
+            }
+            (Mode::Psk | Mode::AuthPsk, Some(_)) => {
+                return Err("HPKE PSK must be at least 32 bytes with a non-empty ID".into())
+            }
+            (Mode::Psk | Mode::AuthPsk, None) => return Err("HPKE PSK mode needs a PSK".into()),
+            (Mode::Base | Mode::Auth, Some(_)) => {
+                return Err("HPKE PSK given for a mode without one".into())
+            }
+        };
+
+        let psk_id_hash = self.labeled_extract(b"", b"psk_id_hash", psk_id);
+        let info_hash = self.labeled_extract(b"", b"info_hash", info);
+        let context = [&[mode as u8][..], &psk_id_hash, &info_hash].concat();
+        let secret = self.labeled_extract(shared_secret, b"secret", psk);
+
+        let mut key = Zeroizing::new(vec![0u8; self.aead.key_bytes()]);
+        let mut base_nonce = [0u8; NONCE_BYTES];
+        if self.aead != Aead::ExportOnly {
+            self.labeled_expand(&secret, b"key", &context, &mut key)?;
+            self.labeled_expand(&secret, b"base_nonce", &context, &mut base_nonce)?;
+        }
+        let mut exporter_secret = Zeroizing::new(vec![0u8; self.kdf.hash_bytes()]);
+        self.labeled_expand(&secret, b"exp", &context, &mut exporter_secret)?;
+        Ok(Context {
+            schedule: self,
+            key,
+            base_nonce,
+            seq: 0,
+            exporter_secret,
+        })
+    }
+}
+
+/// A pre-shared key and its identifier, for PSK mode.
+#[derive(Clone, Copy)]
+pub struct Psk<'a> {
+    pub key: &'a [u8],
+    pub id: &'a [u8],
+}
+
+/// An encryption context: the AEAD key, base nonce, sequence number and
+/// exporter secret the key schedule produced.
+pub struct Context {
+    schedule: Schedule,
+    key: Zeroizing<Vec<u8>>,
+    base_nonce: [u8; NONCE_BYTES],
+    seq: u64,
+    exporter_secret: Zeroizing<Vec<u8>>,
+}
+
+impl Context {
+    fn nonce(&self) -> [u8; NONCE_BYTES] {
+        let mut nonce = self.base_nonce;
+        for (n, s) in nonce[NONCE_BYTES - 8..]
+            .iter_mut()
+            .zip(self.seq.to_be_bytes())
+        {
+            *n ^= s;
+        }
+        nonce
+    }
+
+    fn advance(&mut self) -> Result<(), String> {
+        self.seq = self
+            .seq
+            .checked_add(1)
+            .ok_or("HPKE message limit reached")?;
+        Ok(())
+    }
+
+    /// Encrypts the next message, returning ciphertext and tag.
+    pub fn seal(&mut self, aad: &[u8], pt: &[u8]) -> Result<Vec<u8>, String> {
+        let mut ct = Vec::with_capacity(pt.len() + TAG_BYTES);
+        ct.extend_from_slice(pt);
+        let tag = self
+            .schedule
+            .aead
+            .seal(&self.key, &self.nonce(), aad, &mut ct)?;
+        ct.extend_from_slice(&tag);
+        self.advance()?;
+        Ok(ct)
+    }
+
+    /// Decrypts the next message into a buffer that is wiped on drop.
+    pub fn open(&mut self, aad: &[u8], ct: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+        let body_len = ct
+            .len()
+            .checked_sub(TAG_BYTES)
+            .ok_or("HPKE ciphertext is shorter than its tag")?;
+        let (body, tag) = ct.split_at(body_len);
+        let mut pt = Zeroizing::new(body.to_vec());
+        self.schedule
+            .aead
+            .open(&self.key, &self.nonce(), aad, &mut pt, tag)?;
+        self.advance()?;
+        Ok(pt)
+    }
+
+    /// Derives `len` bytes bound to this context and `exporter_context`.
+    pub fn export(
+        &self,
+        exporter_context: &[u8],
+        len: usize,
+    ) -> Result<Zeroizing<Vec<u8>>, String> {
+        let mut out = Zeroizing::new(vec![0u8; len]);
+        self.schedule
+            .labeled_expand(&self.exporter_secret, b"sec", exporter_context, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// The RFC 9180 key schedule, for any mode. `psk` must be given exactly for
+/// the PSK modes, and be at least 32 bytes.
+pub fn key_schedule(
+    suite: Suite,
+    mode: Mode,
+    shared_secret: &[u8],
+    info: &[u8],
+    psk: Option<Psk<'_>>,
+) -> Result<Context, String> {
+    suite.schedule().run(mode, shared_secret, info, psk)
+}
+
+fn mode(psk: Option<Psk<'_>>) -> Mode {
+    match psk {
+        Some(_) => Mode::Psk,
+        None => Mode::Base,
+    }
+}
+
+/// SetupBaseS / SetupPSKS: encapsulates to `pk` and returns `enc` with the
+/// sender context.
+pub fn setup_sender(
+    suite: Suite,
+    pk: &[u8],
+    info: &[u8],
+    psk: Option<Psk<'_>>,
+) -> Result<(Vec<u8>, Context), String> {
+    if Kem::from_public_key_len(pk.len()) != Some(suite.kem) {
+        return Err(format!(
+            "Public key does not belong to HPKE KEM 0x{:04x}",
+            suite.kem.id()
+        ));
+    }
+    let (enc, ss) = suite.kem.encap(pk)?;
+    Ok((enc, key_schedule(suite, mode(psk), &ss[..], info, psk)?))
+}
+
+/// SetupBaseR / SetupPSKR: decapsulates `enc` with `sk` and returns the
+/// receiver context.
+pub fn setup_receiver(
+    suite: Suite,
+    enc: &[u8],
+    sk: &[u8],
+    info: &[u8],
+    psk: Option<Psk<'_>>,
+) -> Result<Context, String> {
+    if sk.len() != suite.kem.secret_key_bytes() {
+        return Err(format!(
+            "Secret key does not belong to HPKE KEM 0x{:04x}",
+            suite.kem.id()
+        ));
+    }
+    let ss = suite.kem.decap(enc, sk)?;
+    key_schedule(suite, mode(psk), &ss[..], info, psk)
+}
+
+/// Single-shot encryption: `(enc, ct)`.
+pub fn seal(
+    suite: Suite,
+    pk: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    pt: &[u8],
+    psk: Option<Psk<'_>>,
+) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let (enc, mut ctx) = setup_sender(suite, pk, info, psk)?;
+    Ok((enc, ctx.seal(aad, pt)?))
+}
+
+/// Single-shot decryption.
+pub fn open(
+    suite: Suite,
+    enc: &[u8],
+    sk: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    ct: &[u8],
+    psk: Option<Psk<'_>>,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    setup_receiver(suite, enc, sk, info, psk)?.open(aad, ct)
+}
+
+/// What both sides of a single-shot exchange supply besides the keys. The
+/// receiver takes the KDF and AEAD from the envelope instead.
+pub struct Params {
+    pub kdf: Kdf,
+    pub aead: Aead,
+    pub info: Vec<u8>,
+    pub aad: Vec<u8>,
+    /// PSK mode: the key and its ID.
+    pub psk: Option<(Zeroizing<Vec<u8>>, Vec<u8>)>,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            kdf: Kdf::HkdfSha256,
+            aead: Aead::Aes256Gcm,
+            info: Vec::new(),
+            aad: Vec::new(),
+            psk: None,
+        }
+    }
+}
+
+impl Params {
+    pub fn psk(&self) -> Option<Psk<'_>> {
+        self.psk.as_ref().map(|(key, id)| Psk { key, id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_INFO: &str = "4f6465206f6e2061204772656369616e2055726e";
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    /// RFC 9180 A.1 (DHKEM(X25519), HKDF-SHA256, AES-128-GCM), from the
+    /// published shared secret: key, base nonce and exporter secret.
+    fn rfc_a1(mode: Mode, ss: &str, psk: Option<Psk<'_>>, expected: [&str; 3]) -> Context {
+        let schedule = Schedule::new(0x0020, Kdf::HkdfSha256, Aead::Aes128Gcm);
+        let ctx = schedule.run(mode, &h(ss), &h(RFC_INFO), psk).unwrap();
+        assert_eq!(hex::encode(&ctx.key[..]), expected[0]);
+        assert_eq!(hex::encode(ctx.base_nonce), expected[1]);
+        assert_eq!(hex::encode(&ctx.exporter_secret[..]), expected[2]);
+        ctx
+    }
+
+    #[test]
+    fn key_schedule_matches_rfc9180_base() {
+        let mut ctx = rfc_a1(
+            Mode::Base,
+            "fe0e18c9f024ce43799ae393c7e8fe8fce9d218875e8227b0187c04e7d2ea1fc",
+            None,
+            [
+                "4531685d41d65f03dc48f6b8302c05b0",
+                "56d890e5accaaf011cff4b7d",
+                "45ff1c2e220db587171952c0592d5f5ebe103f1561a2614e38f2ffd47e99e3f8",
+            ],
+        );
+        let ct = ctx
+            .seal(b"Count-0", b"Beauty is truth, truth beauty")
+            .unwrap();
+        assert_eq!(
+            hex::encode(ct),
+            "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a"
+        );
+        assert_eq!(
+            hex::encode(&ctx.export(b"", 32).unwrap()[..]),
+            "3853fe2b4035195a573ffc53856e77058e15d9ea064de3e59f4961d0095250ee"
+        );
+    }
+
+    #[test]
+    fn key_schedule_matches_rfc9180_psk() {
+        let key = h("0247fd33b913760fa1fa51e1892d9f307fbe65eb171e8132c2af18555a738b82");
+        let id = h("456e6e796e20447572696e206172616e204d6f726961");
+        rfc_a1(
+            Mode::Psk,
+            "727699f009ffe3c076315019c69648366b69171439bd7dd0807743bde76986cd",
+            Some(Psk { key: &key, id: &id }),
+            [
+                "15026dba546e3ae05836fc7de5a7bb26",
+                "9518635eba129d5ce0914555",
+                "3d76025dbbedc49448ec3f9080a1abab6b06e91c0b11ad23c912f043a0ee7655",
+            ],
+        );
+    }
+
+    #[test]
+    fn every_kem_and_aead_round_trips() {
+        let aeads = [Aead::Aes128Gcm, Aead::Aes256Gcm, Aead::ChaCha20Poly1305];
+        for (kem, aead) in Kem::all().zip(aeads.into_iter().cycle()) {
+            let (pk, sk) = match kem {
+                Kem::MlKem(set) => set.keypair().unwrap(),
+                Kem::Hybrid => {
+                    let (pk, sk) = crate::hybrid::keypair().unwrap();
+                    (pk.to_vec(), Zeroizing::new(sk.to_vec()))
+                }
+            };
+            let suite = Suite {
+                kem,
+                kdf: Kdf::HkdfSha512,
+                aead,
+            };
+            let (enc, mut sender) = setup_sender(suite, &pk, b"info", None).unwrap();
+            let mut receiver = setup_receiver(suite, &enc, &sk, b"info", None).unwrap();
+            for i in 0..3u8 {
+                let ct = sender.seal(&[i], b"message").unwrap();
+                assert_eq!(&receiver.open(&[i], &ct).unwrap()[..], b"message");
+            }
+            assert_eq!(
+                sender.export(b"ctx", 48).unwrap(),
+                receiver.export(b"ctx", 48).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn psk_and_info_must_match() {
+        let (pk, sk) = ParameterSet::MlKem1024.keypair().unwrap();
+        let suite = Suite {
+            kem: Kem::MlKem(ParameterSet::MlKem1024),
+            kdf: Kdf::HkdfSha256,
+            aead: Aead::Aes256Gcm,
+        };
+        let psk = Psk {
+            key: &[7; 32],
+            id: b"id",
+        };
+        let (enc, ct) = seal(suite, &pk, b"info", b"aad", b"hi", Some(psk)).unwrap();
+        let pt = open(suite, &enc, &sk, b"info", b"aad", &ct, Some(psk)).unwrap();
+        assert_eq!(&pt[..], b"hi");
+
+        assert!(open(suite, &enc, &sk, b"info", b"aad", &ct, None).is_err());
+        assert!(open(suite, &enc, &sk, b"other", b"aad", &ct, Some(psk)).is_err());
+        assert!(open(suite, &enc, &sk, b"info", b"", &ct, Some(psk)).is_err());
+        let short = Psk {
+            key: &[7; 16],
+            ..psk
+        };
+        assert!(seal(suite, &pk, b"", b"", b"", Some(short)).is_err());
+        // The key must belong to the suite's KEM
+        let (pk768, _) = ParameterSet::MlKem768.keypair().unwrap();
+        assert!(seal(suite, &pk768, b"", b"", b"", None).is_err());
+    }
+}
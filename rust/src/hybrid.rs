@@ -0,0 +1,192 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Hybrid X25519 + ML-KEM-1024 KEM
+ *
+ * Runs an ephemeral-static X25519 exchange alongside ML-KEM-1024 so the
+ * envelope stays confidential if either primitive holds. Keys are the two
+ * component keys concatenated, classical part first:
+ *
+ *   pk = X25519 pk (32)  || ML-KEM ek (1568)    1600 bytes
+ *   sk = X25519 sk (32)  || ML-KEM dk (3168)    3200 bytes
+ *
+ * The lengths differ from plain ML-KEM keys, so the engine picks the mode
+ * from the key it is given. The ciphertext is the ML-KEM ciphertext plus the
+ * 32-byte ephemeral X25519 public key (`xct` in the envelope).
+ *
+ * Combiner (the X-Wing construction, draft-connolly-cfrg-xwing-kem, with our
+ * own label since the ML-KEM parameter set differs):
+ *
+ *   ss = SHA3-256(ss_mlkem || ss_x25519 || ct_x25519 || pk_x25519 || LABEL)
+ *
+ * The X25519 ciphertext and public key are hashed so the classical half
+ * cannot be mauled; ML-KEM ciphertexts are already bound by its FO transform.
+ */
+
+use crate::error::Error;
+use crate::kem;
+use sha3::{Digest, Sha3_256};
+use x25519_dalek::{PublicKey as X25519Public, StaticSecret};
+use zeroize::Zeroizing;
+
+pub const X25519_BYTES: usize = 32;
+pub const PUBLIC_KEY_BYTES: usize = X25519_BYTES + kem::PUBLIC_KEY_BYTES;
+pub const SECRET_KEY_BYTES: usize = X25519_BYTES + kem::SECRET_KEY_BYTES;
+
+const LABEL: &[u8] = b"D-SPNA-512 X25519+ML-KEM-1024";
+
+pub type PublicKey = [u8; PUBLIC_KEY_BYTES];
+pub type SecretKey = Zeroizing<[u8; SECRET_KEY_BYTES]>;
+
+/// Both halves of a hybrid ciphertext.
+#[derive(Clone)]
+pub struct Ciphertext {
+    pub mlkem: kem::Ciphertext,
+    pub x25519: [u8; X25519_BYTES],
+}
+
+/// Generates a hybrid keypair. The ML-KEM half gets the pairwise consistency
+/// test; the X25519 secret comes from the same DRBG.
+pub fn keypair() -> Result<(PublicKey, SecretKey), Error> {
+    let (ek, dk) = kem::keypair()?;
+    let x_sk = StaticSecret::from(*crate::drbg::random::<X25519_BYTES>()?);
+    let x_pk = X25519Public::from(&x_sk);
+
+    let mut pk = [0u8; PUBLIC_KEY_BYTES];
+    pk[..X25519_BYTES].copy_from_slice(x_pk.as_bytes());
+    pk[X25519_BYTES..].copy_from_slice(&ek);
+    let mut sk = Zeroizing::new([0u8; SECRET_KEY_BYTES]);
+    sk[..X25519_BYTES].copy_from_slice(x_sk.as_bytes());
+    sk[X25519_BYTES..].copy_from_slice(&dk[..]);
+    Ok((pk, sk))
+}
+
+fn split_pk(pk: &PublicKey) -> (X25519Public, &kem::PublicKey) {
+    let (x, ek) = pk.split_at(X25519_BYTES);
+    let x: [u8; X25519_BYTES] = x.try_into().expect("split at X25519_BYTES");
+    (
+        X25519Public::from(x),
+        ek.try_into().expect("rest is an ML-KEM key"),
+    )
+}
+
+fn exchange(secret: &StaticSecret, peer: &X25519Public) -> Result<Zeroizing<[u8; 32]>, Error> {
+    let shared = secret.diffie_hellman(peer);
+    // A low-order peer point forces an all-zero secret; refuse it
+    if !shared.was_contributory() {
+        return Err("Invalid X25519 public key".into());
+    }
+    Ok(Zeroizing::new(shared.to_bytes()))
+}
+
+fn combine(
+    ss_mlkem: &[u8],
+    ss_x25519: &[u8],
+    ct_x25519: &[u8; X25519_BYTES],
+    pk_x25519: &X25519Public,
+) -> kem::SharedSecret {
+    let mut h = Sha3_256::new();
+    h.update(ss_mlkem);
+    h.update(ss_x25519);
+    h.update(ct_x25519);
+    h.update(pk_x25519.as_bytes());
+    h.update(LABEL);
+    Zeroizing::new(h.finalize().into())
+}
+
+/// Encapsulates to a hybrid public key with fresh DRBG seeds.
+pub fn encaps(pk: &PublicKey) -> Result<(Ciphertext, kem::SharedSecret), Error> {
+    let m = crate::drbg::random::<{ kem::ENCAPS_SEED_BYTES }>()?;
+    let eph = crate::drbg::random::<X25519_BYTES>()?;
+    encaps_derand(pk, &m, &eph)
+}
+
+/// Deterministic encapsulation: `m` seeds ML-KEM, `eph` is the ephemeral
+/// X25519 secret.
+pub fn encaps_derand(
+    pk: &PublicKey,
+    m: &[u8; kem::ENCAPS_SEED_BYTES],
+    eph: &[u8; X25519_BYTES],
+) -> Result<(Ciphertext, kem::SharedSecret), Error> {
+    let (x_pk, ek) = split_pk(pk);
+    let (mlkem, ss_m) = kem::encaps_derand(ek, m)?;
+    let eph = StaticSecret::from(*eph);
+    let ct_x = *X25519Public::from(&eph).as_bytes();
+    let ss_x = exchange(&eph, &x_pk)?;
+    let ss = combine(&ss_m[..], &ss_x[..], &ct_x, &x_pk);
+    Ok((
+        Ciphertext {
+            mlkem,
+            x25519: ct_x,
+        },
+        ss,
+    ))
+}
+
+/// Recovers the combined secret. A wrong ML-KEM ciphertext still yields a
+/// (wrong) secret through implicit rejection, so failures surface at the MAC.
+pub fn decaps(sk: &[u8], ct: &Ciphertext) -> Result<kem::SharedSecret, Error> {
+    if sk.len() != SECRET_KEY_BYTES {
+        return Err("Invalid hybrid secret key length".into());
+    }
+    let (x, dk) = sk.split_at(X25519_BYTES);
+    let x_sk =
+        StaticSecret::from(<[u8; X25519_BYTES]>::try_from(x).expect("split at X25519_BYTES"));
+    let x_pk = X25519Public::from(&x_sk);
+    let ss_m = kem::decaps(dk.try_into().expect("rest is an ML-KEM key"), &ct.mlkem)?;
+    let ss_x = exchange(&x_sk, &X25519Public::from(ct.x25519))?;
+    Ok(combine(&ss_m[..], &ss_x[..], &ct.x25519, &x_pk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_both_halves_matter() {
+        let (pk, sk) = keypair().unwrap();
+        let (ct, ss) = encaps(&pk).unwrap();
+        assert_eq!(decaps(&sk[..], &ct).unwrap(), ss);
+
+        // Tampering with either ciphertext changes the combined secret
+        let mut bad = ct.clone();
+        bad.mlkem[0] ^= 1;
+        assert_ne!(decaps(&sk[..], &bad).unwrap(), ss);
+        let mut bad = ct.clone();
+        bad.x25519[0] ^= 1;
+        assert_ne!(decaps(&sk[..], &bad).unwrap(), ss);
+    }
+
+    #[test]
+    fn derand_is_reproducible_and_pinned() {
+        let (ek, dk) = kem::keypair_derand(&[0x01; kem::KEYPAIR_SEED_BYTES]).unwrap();
+        let x_sk = StaticSecret::from([0x02; X25519_BYTES]);
+        let mut pk = [0u8; PUBLIC_KEY_BYTES];
+        pk[..32].copy_from_slice(X25519Public::from(&x_sk).as_bytes());
+        pk[32..].copy_from_slice(&ek);
+        let mut sk = [0u8; SECRET_KEY_BYTES];
+        sk[..32].copy_from_slice(x_sk.as_bytes());
+        sk[32..].copy_from_slice(&dk[..]);
+
+        let (ct, ss) = encaps_derand(&pk, &[0x03; 32], &[0x04; 32]).unwrap();
+        assert_eq!(encaps_derand(&pk, &[0x03; 32], &[0x04; 32]).unwrap().1, ss);
+        assert_eq!(decaps(&sk, &ct).unwrap(), ss);
+        // Cross-checked against pyca/cryptography X25519 and hashlib SHA3-256
+        assert_eq!(
+            hex::encode(&ss[..]),
+            "d1b272b3a489ba6bb10c65f59b58e3452a40f63e33cba62b4ffd49868cb35ee4"
+        );
+    }
+
+    #[test]
+    fn low_order_points_are_rejected() {
+        let (mut pk, sk) = keypair().unwrap();
+        let (mut ct, _) = encaps(&pk).unwrap();
+        ct.x25519 = [0u8; 32];
+        assert!(decaps(&sk[..], &ct).is_err());
+        pk[..32].fill(0);
+        assert!(encaps(&pk).is_err());
+        assert!(decaps(&sk[1..], &ct).is_err());
+    }
+}
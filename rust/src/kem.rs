@@ -0,0 +1,498 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * ML-KEM Primitives
+ *
+ * Byte-level keygen, encapsulation and decapsulation for ML-KEM-1024, the
+ * parameter set behind `crypto_kem_*`, the hybrid KEM and the ACVP runner.
+ * [`ParameterSet`] offers the same operations on variable-length buffers for
+ * all three FIPS 203 sets, so the engine can follow whichever one a key or
+ * envelope names:
+ *
+ *   set           pk      sk      ct
+ *   ML-KEM-512    800     1632    768
+ *   ML-KEM-768    1184    2400    1088
+ *   ML-KEM-1024   1568    3168    1568
+ *
+ * Besides the randomized
+ * calls behind `crypto_kem_*`, the derandomized forms take their seeds
+ * explicitly, matching the `*_derand` functions of PQClean and liboqs and
+ * FIPS 203's ML-KEM.KeyGen_internal / Encaps_internal:
+ *
+ *   keypair_derand(d || z) -> (ek, dk)     64-byte seed
+ *   encaps_derand(ek, m)   -> (ct, ss)     32-byte message seed
+ *
+ * With the seeds fixed, outputs can be checked against NIST vectors or the C
+ * engine byte for byte; `kat_vectors` generates such a set reproducibly.
+ *
+ * `check_encapsulation_key` and `check_decapsulation_key` are the input
+ * checks of FIPS 203 sections 7.2 and 7.3, for keys from an untrusted source.
+ */
+
+use crate::drbg::HmacDrbg;
+use crate::error::Error;
+use ml_kem::kem::{Decapsulate, DecapsulationKey, EncapsulationKey};
+use ml_kem::{
+    EncapsulateDeterministic, Encoded, EncodedSizeUser, KemCore, MlKem1024, MlKem1024Params,
+    MlKem512, MlKem768,
+};
+use zeroize::{Zeroize, Zeroizing};
+
+pub const PUBLIC_KEY_BYTES: usize = 1568;
+pub const SECRET_KEY_BYTES: usize = 3168;
+pub const CIPHERTEXT_BYTES: usize = 1568;
+pub const SHARED_SECRET_BYTES: usize = 32;
+/// `d || z` for [`keypair_derand`].
+pub const KEYPAIR_SEED_BYTES: usize = 64;
+/// The message `m` for [`encaps_derand`].
+pub const ENCAPS_SEED_BYTES: usize = 32;
+
+pub type PublicKey = [u8; PUBLIC_KEY_BYTES];
+pub type SecretKey = Zeroizing<[u8; SECRET_KEY_BYTES]>;
+pub type Ciphertext = [u8; CIPHERTEXT_BYTES];
+pub type SharedSecret = Zeroizing<[u8; SHARED_SECRET_BYTES]>;
+
+/// One of the three FIPS 203 parameter sets. ML-KEM-1024 is the default and
+/// the only one envelopes leave unnamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParameterSet {
+    MlKem512,
+    MlKem768,
+    #[default]
+    MlKem1024,
+}
+
+impl ParameterSet {
+    pub const ALL: [ParameterSet; 3] = [
+        ParameterSet::MlKem512,
+        ParameterSet::MlKem768,
+        ParameterSet::MlKem1024,
+    ];
+
+    /// The FIPS 203 name, as recorded in envelopes and ACVP files.
+    pub fn name(self) -> &'static str {
+        match self {
+            ParameterSet::MlKem512 => "ML-KEM-512",
+            ParameterSet::MlKem768 => "ML-KEM-768",
+            ParameterSet::MlKem1024 => "ML-KEM-1024",
+        }
+    }
+
+    /// Parses a FIPS 203 name, or just its number ("768").
+    pub fn from_name(name: &str) -> Option<Self> {
+        let level = name.strip_prefix("ML-KEM-").unwrap_or(name);
+        Self::ALL
+            .into_iter()
+            .find(|set| set.name()["ML-KEM-".len()..] == *level)
+    }
+
+    pub const fn public_key_bytes(self) -> usize {
+        match self {
+            ParameterSet::MlKem512 => 800,
+            ParameterSet::MlKem768 => 1184,
+            ParameterSet::MlKem1024 => PUBLIC_KEY_BYTES,
+        }
+    }
+
+    pub const fn secret_key_bytes(self) -> usize {
+        match self {
+            ParameterSet::MlKem512 => 1632,
+            ParameterSet::MlKem768 => 2400,
+            ParameterSet::MlKem1024 => SECRET_KEY_BYTES,
+        }
+    }
+
+    pub const fn ciphertext_bytes(self) -> usize {
+        match self {
+            ParameterSet::MlKem512 => 768,
+            ParameterSet::MlKem768 => 1088,
+            ParameterSet::MlKem1024 => CIPHERTEXT_BYTES,
+        }
+    }
+
+    /// The set a public key belongs to; the three key lengths are distinct.
+    pub fn from_public_key_len(len: usize) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|set| set.public_key_bytes() == len)
+    }
+
+    /// The set a secret key belongs to.
+    pub fn from_secret_key_len(len: usize) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|set| set.secret_key_bytes() == len)
+    }
+
+    /// Generates a keypair from the DRBG, with the pairwise consistency test.
+    pub fn keypair(self) -> Result<(Vec<u8>, Zeroizing<Vec<u8>>), Error> {
+        match self {
+            ParameterSet::MlKem512 => keypair_for::<MlKem512>(),
+            ParameterSet::MlKem768 => keypair_for::<MlKem768>(),
+            ParameterSet::MlKem1024 => keypair_for::<MlKem1024>(),
+        }
+    }
+
+    /// Deterministic keypair from `seed = d || z`, as [`keypair_derand`].
+    pub fn keypair_derand(
+        self,
+        seed: &[u8; KEYPAIR_SEED_BYTES],
+    ) -> Result<(Vec<u8>, Zeroizing<Vec<u8>>), Error> {
+        match self {
+            ParameterSet::MlKem512 => keypair_derand_for::<MlKem512>(seed),
+            ParameterSet::MlKem768 => keypair_derand_for::<MlKem768>(seed),
+            ParameterSet::MlKem1024 => keypair_derand_for::<MlKem1024>(seed),
+        }
+    }
+
+    /// Encapsulates to `pk` with a message seed from the DRBG.
+    pub fn encaps(self, pk: &[u8]) -> Result<(Vec<u8>, SharedSecret), Error> {
+        let m = crate::drbg::random::<ENCAPS_SEED_BYTES>()?;
+        self.encaps_derand(pk, &m)
+    }
+
+    /// Deterministic encapsulation with the message seed `m`.
+    pub fn encaps_derand(
+        self,
+        pk: &[u8],
+        m: &[u8; ENCAPS_SEED_BYTES],
+    ) -> Result<(Vec<u8>, SharedSecret), Error> {
+        match self {
+            ParameterSet::MlKem512 => encaps_for::<MlKem512>(pk, m),
+            ParameterSet::MlKem768 => encaps_for::<MlKem768>(pk, m),
+            ParameterSet::MlKem1024 => encaps_for::<MlKem1024>(pk, m),
+        }
+    }
+
+    /// Decapsulates `ct`, with implicit rejection as for [`decaps`].
+    pub fn decaps(self, sk: &[u8], ct: &[u8]) -> Result<SharedSecret, Error> {
+        match self {
+            ParameterSet::MlKem512 => decaps_for::<MlKem512>(sk, ct),
+            ParameterSet::MlKem768 => decaps_for::<MlKem768>(sk, ct),
+            ParameterSet::MlKem1024 => decaps_for::<MlKem1024>(sk, ct),
+        }
+    }
+
+    /// FIPS 203 encapsulation key check: the right length, and every 12-bit
+    /// coefficient of the encoded vector `t` already reduced modulo q.
+    pub fn check_encapsulation_key(self, ek: &[u8]) -> bool {
+        let t_len = self.public_key_bytes() - 32;
+        ek.len() == self.public_key_bytes()
+            && ek[..t_len].chunks_exact(3).all(|b| {
+                let lo = u16::from(b[0]) | (u16::from(b[1] & 0x0f) << 8);
+                let hi = u16::from(b[1] >> 4) | (u16::from(b[2]) << 4);
+                lo < 3329 && hi < 3329
+            })
+    }
+
+    /// FIPS 203 decapsulation key check: the right length, and the embedded
+    /// `H(ek)` matching the embedded encapsulation key.
+    pub fn check_decapsulation_key(self, dk: &[u8]) -> bool {
+        use sha3::{Digest, Sha3_256};
+        // dk = dk_pke || ek || H(ek) || z
+        let ek_len = self.public_key_bytes();
+        let ek_at = self.secret_key_bytes() - ek_len - 64;
+        dk.len() == self.secret_key_bytes()
+            && Sha3_256::digest(&dk[ek_at..ek_at + ek_len])[..]
+                == dk[ek_at + ek_len..ek_at + ek_len + 32]
+    }
+}
+
+impl std::fmt::Display for ParameterSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn keypair_for<K: KemCore>() -> Result<(Vec<u8>, Zeroizing<Vec<u8>>), Error> {
+    let (dk, ek) = crate::selftest::generate_keypair_for::<K>()?;
+    Ok(encode_for::<K>(&dk, &ek))
+}
+
+fn encode_for<K: KemCore>(
+    dk: &K::DecapsulationKey,
+    ek: &K::EncapsulationKey,
+) -> (Vec<u8>, Zeroizing<Vec<u8>>) {
+    let mut dk_bytes = dk.as_bytes();
+    let sk = Zeroizing::new(dk_bytes.to_vec());
+    dk_bytes.zeroize();
+    (ek.as_bytes().to_vec(), sk)
+}
+
+fn keypair_derand_for<K: KemCore>(
+    seed: &[u8; KEYPAIR_SEED_BYTES],
+) -> Result<(Vec<u8>, Zeroizing<Vec<u8>>), Error> {
+    crate::selftest::check()?;
+    let mut d = [0u8; 32];
+    let mut z = [0u8; 32];
+    d.copy_from_slice(&seed[..32]);
+    z.copy_from_slice(&seed[32..]);
+    let (dk, ek) = K::generate_deterministic(&d.into(), &z.into());
+    d.zeroize();
+    z.zeroize();
+    Ok(encode_for::<K>(&dk, &ek))
+}
+
+fn encaps_for<K: KemCore>(
+    pk: &[u8],
+    m: &[u8; ENCAPS_SEED_BYTES],
+) -> Result<(Vec<u8>, SharedSecret), Error> {
+    crate::selftest::check()?;
+    let pk = Encoded::<K::EncapsulationKey>::try_from(pk)
+        .map_err(|_| "Invalid public key length".to_string())?;
+    let ek = K::EncapsulationKey::from_bytes(&pk);
+    let (ct, mut ss) = ek
+        .encapsulate_deterministic(&(*m).into())
+        .map_err(|e| format!("KEM Encapsulation failed: {:?}", e))?;
+    let mut shared = Zeroizing::new([0u8; SHARED_SECRET_BYTES]);
+    shared.copy_from_slice(&ss);
+    ss.zeroize();
+    Ok((ct.to_vec(), shared))
+}
+
+fn decaps_for<K: KemCore>(sk: &[u8], ct: &[u8]) -> Result<SharedSecret, Error> {
+    crate::selftest::check()?;
+    let ct = ml_kem::Ciphertext::<K>::try_from(ct)
+        .map_err(|_| "Invalid ciphertext length".to_string())?;
+    let mut sk = Encoded::<K::DecapsulationKey>::try_from(sk)
+        .map_err(|_| "Invalid secret key length".to_string())?;
+    let dk = K::DecapsulationKey::from_bytes(&sk);
+    sk.zeroize();
+    let mut ss = dk
+        .decapsulate(&ct)
+        .map_err(|_| "KEM decapsulation failed".to_string())?;
+    let mut shared = Zeroizing::new([0u8; SHARED_SECRET_BYTES]);
+    shared.copy_from_slice(&ss);
+    ss.zeroize();
+    Ok(shared)
+}
+
+fn encode(
+    dk: &DecapsulationKey<MlKem1024Params>,
+    ek: &EncapsulationKey<MlKem1024Params>,
+) -> (PublicKey, SecretKey) {
+    let mut sk = Zeroizing::new([0u8; SECRET_KEY_BYTES]);
+    let mut dk_bytes = dk.as_bytes();
+    sk.copy_from_slice(&dk_bytes);
+    dk_bytes.zeroize();
+    (ek.as_bytes().into(), sk)
+}
+
+/// Generates a keypair from the DRBG, with the pairwise consistency test.
+pub fn keypair() -> Result<(PublicKey, SecretKey), Error> {
+    let (dk, ek) = crate::selftest::generate_keypair()?;
+    Ok(encode(&dk, &ek))
+}
+
+/// Deterministic keypair from `seed = d || z`. There is no pairwise test: the
+/// output is fixed by the seed and meant to be checked against known answers.
+pub fn keypair_derand(seed: &[u8; KEYPAIR_SEED_BYTES]) -> Result<(PublicKey, SecretKey), Error> {
+    crate::selftest::check()?;
+    let mut d = [0u8; 32];
+    let mut z = [0u8; 32];
+    d.copy_from_slice(&seed[..32]);
+    z.copy_from_slice(&seed[32..]);
+    let (dk, ek) = MlKem1024::generate_deterministic(&d.into(), &z.into());
+    d.zeroize();
+    z.zeroize();
+    Ok(encode(&dk, &ek))
+}
+
+/// Encapsulates to `pk` with a message seed from the DRBG.
+pub fn encaps(pk: &PublicKey) -> Result<(Ciphertext, SharedSecret), Error> {
+    let m = crate::drbg::random::<ENCAPS_SEED_BYTES>()?;
+    encaps_derand(pk, &m)
+}
+
+/// Deterministic encapsulation with the message seed `m`.
+pub fn encaps_derand(
+    pk: &PublicKey,
+    m: &[u8; ENCAPS_SEED_BYTES],
+) -> Result<(Ciphertext, SharedSecret), Error> {
+    let (ct, ss) = encaps_for::<MlKem1024>(pk, m)?;
+    Ok((ct.try_into().expect("ML-KEM-1024 ciphertext"), ss))
+}
+
+/// Decapsulates `ct`. A ciphertext that does not match the key yields the
+/// implicit-rejection secret rather than an error, as FIPS 203 requires.
+pub fn decaps(sk: &[u8; SECRET_KEY_BYTES], ct: &Ciphertext) -> Result<SharedSecret, Error> {
+    decaps_for::<MlKem1024>(sk, ct)
+}
+
+/// The ML-KEM-1024 encapsulation key check of FIPS 203 section 7.2.
+pub fn check_encapsulation_key(ek: &[u8]) -> bool {
+    ParameterSet::MlKem1024.check_encapsulation_key(ek)
+}
+
+/// The ML-KEM-1024 decapsulation key check of FIPS 203 section 7.3.
+pub fn check_decapsulation_key(dk: &[u8]) -> bool {
+    ParameterSet::MlKem1024.check_decapsulation_key(dk)
+}
+
+/// One known-answer record: the seeds and everything they determine.
+pub struct KatVector {
+    pub d: [u8; 32],
+    pub z: [u8; 32],
+    pub m: [u8; ENCAPS_SEED_BYTES],
+    pub pk: PublicKey,
+    pub sk: SecretKey,
+    pub ct: Ciphertext,
+    pub ss: SharedSecret,
+}
+
+/// Generates `count` vectors with seeds drawn from an HMAC_DRBG instantiated
+/// on `entropy`, so the same input always reproduces the same file.
+pub fn kat_vectors(entropy: &[u8], count: usize) -> Result<Vec<KatVector>, Error> {
+    let mut drbg = HmacDrbg::from_seed(entropy, &[], b"ML-KEM-1024 KAT");
+    (0..count)
+        .map(|_| {
+            let mut seed = Zeroizing::new([0u8; KEYPAIR_SEED_BYTES]);
+            let mut m = [0u8; ENCAPS_SEED_BYTES];
+            drbg.generate(&mut seed[..], &[])?;
+            drbg.generate(&mut m, &[])?;
+            let (pk, sk) = keypair_derand(&seed)?;
+            let (ct, ss) = encaps_derand(&pk, &m)?;
+            let mut d = [0u8; 32];
+            let mut z = [0u8; 32];
+            d.copy_from_slice(&seed[..32]);
+            z.copy_from_slice(&seed[32..]);
+            Ok(KatVector {
+                d,
+                z,
+                m,
+                pk,
+                sk,
+                ct,
+                ss,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn seed(d: u8, z: u8) -> [u8; KEYPAIR_SEED_BYTES] {
+        let mut seed = [d; KEYPAIR_SEED_BYTES];
+        seed[32..].fill(z);
+        seed
+    }
+
+    #[test]
+    fn derand_matches_self_test_answers() {
+        let (pk, sk) = keypair_derand(&seed(0x01, 0x02)).unwrap();
+        let (ct, ss) = encaps_derand(&pk, &[0x03; 32]).unwrap();
+        assert_eq!(
+            hex::encode(Sha256::digest(pk)),
+            "05227acb49aefea81141d2bbc32ed84178283517d724ebc04d570ce84725f656"
+        );
+        assert_eq!(
+            hex::encode(Sha256::digest(&sk[..])),
+            "915abe97d618f15d1c32828816f335c3ef5f6ed590c65d1f5b2ffaeb90c6a99f"
+        );
+        assert_eq!(
+            hex::encode(Sha256::digest(ct)),
+            "c4064e9589a17679f66af906a0bcea93e0944af384c5f48d92740d015fb9b1fc"
+        );
+        assert_eq!(
+            hex::encode(&ss[..]),
+            "d1180e60410880516e234bbebf268aa76a1e0c4802c1af0fc0f6846d3274db8a"
+        );
+        assert_eq!(decaps(&sk, &ct).unwrap(), ss);
+    }
+
+    #[test]
+    fn ffi_derand_exports_match() {
+        let coins = seed(0x11, 0x22);
+        let (mut pk, mut sk) = ([0u8; PUBLIC_KEY_BYTES], [0u8; SECRET_KEY_BYTES]);
+        let rc = crate::crypto_kem_keypair_derand(pk.as_mut_ptr(), sk.as_mut_ptr(), coins.as_ptr());
+        assert_eq!(rc, 0);
+        let (rust_pk, rust_sk) = keypair_derand(&coins).unwrap();
+        assert_eq!((pk, sk), (rust_pk, *rust_sk));
+
+        let (mut ct, mut ss) = ([0u8; CIPHERTEXT_BYTES], [0u8; SHARED_SECRET_BYTES]);
+        let m = [0x33; ENCAPS_SEED_BYTES];
+        let rc =
+            crate::crypto_kem_enc_derand(ct.as_mut_ptr(), ss.as_mut_ptr(), pk.as_ptr(), m.as_ptr());
+        assert_eq!(rc, 0);
+        let (rust_ct, rust_ss) = encaps_derand(&pk, &m).unwrap();
+        assert_eq!((ct, ss), (rust_ct, *rust_ss));
+
+        let mut ss_dec = [0u8; SHARED_SECRET_BYTES];
+        assert_eq!(
+            crate::crypto_kem_dec(ss_dec.as_mut_ptr(), ct.as_ptr(), sk.as_ptr()),
+            0
+        );
+        assert_eq!(ss_dec, ss);
+    }
+
+    #[test]
+    fn every_parameter_set_round_trips() {
+        for set in ParameterSet::ALL {
+            let (pk, sk) = set.keypair().unwrap();
+            assert_eq!(ParameterSet::from_public_key_len(pk.len()), Some(set));
+            assert_eq!(ParameterSet::from_secret_key_len(sk.len()), Some(set));
+            assert!(set.check_encapsulation_key(&pk));
+            assert!(set.check_decapsulation_key(&sk));
+            let (ct, ss) = set.encaps(&pk).unwrap();
+            assert_eq!(ct.len(), set.ciphertext_bytes());
+            assert_eq!(set.decaps(&sk, &ct).unwrap(), ss);
+            assert!(set.decaps(&sk, &ct[1..]).is_err());
+            assert_eq!(ParameterSet::from_name(set.name()), Some(set));
+        }
+        assert_eq!(ParameterSet::from_name("768"), Some(ParameterSet::MlKem768));
+        assert_eq!(ParameterSet::from_name("ML-KEM-76"), None);
+
+        // The generic path is the one behind the fixed-size ML-KEM-1024 calls
+        let seed = seed(0x01, 0x02);
+        let (pk, sk) = ParameterSet::MlKem1024.keypair_derand(&seed).unwrap();
+        assert_eq!(keypair_derand(&seed).unwrap().0.to_vec(), pk);
+        assert_eq!(sk.len(), SECRET_KEY_BYTES);
+    }
+
+    #[test]
+    fn randomized_calls_round_trip() {
+        let (pk, sk) = keypair().unwrap();
+        let (ct, ss) = encaps(&pk).unwrap();
+        assert_eq!(decaps(&sk, &ct).unwrap(), ss);
+        assert_ne!(encaps(&pk).unwrap().0, ct);
+    }
+
+    #[test]
+    fn key_checks_reject_malformed_keys() {
+        let (pk, sk) = keypair_derand(&seed(0x44, 0x55)).unwrap();
+        assert!(check_encapsulation_key(&pk));
+        assert!(check_decapsulation_key(&sk[..]));
+        assert!(!check_encapsulation_key(&pk[1..]));
+        assert!(!check_decapsulation_key(&sk[1..]));
+
+        // Coefficient 0 set to q, one past the largest reduced value
+        let mut bad = pk;
+        bad[0] = 0x01;
+        bad[1] = (bad[1] & 0xf0) | 0x0d;
+        assert!(!check_encapsulation_key(&bad));
+
+        let mut bad = *sk;
+        bad[SECRET_KEY_BYTES - 40] ^= 1;
+        assert!(!check_decapsulation_key(&bad));
+    }
+
+    #[test]
+    fn kat_vectors_are_reproducible() {
+        let entropy: Vec<u8> = (0..48).collect();
+        let a = kat_vectors(&entropy, 2).unwrap();
+        let b = kat_vectors(&entropy, 2).unwrap();
+        assert_eq!(a[1].pk, b[1].pk);
+        assert_eq!(a[1].ct, b[1].ct);
+        assert_ne!(a[0].d, a[1].d);
+        for v in &a {
+            let (pk, _) = keypair_derand(&[v.d, v.z].concat().try_into().unwrap()).unwrap();
+            assert_eq!(pk, v.pk);
+            assert_eq!(encaps_derand(&pk, &v.m).unwrap().0, v.ct);
+            assert_eq!(decaps(&v.sk, &v.ct).unwrap(), v.ss);
+        }
+    }
+}
@@ -3,9 +3,22 @@
  * Implementation: Rust (Reference "Gold" Implementation)
  */
 
-use ml_kem::{EncodedSizeUser, KemCore, MlKem1024};
-pub mod engine;
-use engine::DarkstarCrypt;
+use dasp_crypto::acvp;
+use dasp_crypto::channel;
+use dasp_crypto::cli;
+use dasp_crypto::codec;
+use dasp_crypto::engine::DarkstarCrypt;
+use dasp_crypto::hpke;
+use dasp_crypto::hybrid;
+use dasp_crypto::kem;
+#[cfg(feature = "ml-dsa")]
+use dasp_crypto::mldsa;
+use dasp_crypto::seal;
+use dasp_crypto::selftest;
+use dasp_crypto::session::{SessionReceiver, SessionSender, DEFAULT_REKEY_INTERVAL};
+use ml_kem::EncodedSizeUser;
+use std::error::Error;
+use zeroize::Zeroizing;
 
 fn print_usage() {
     println!("Usage: darkstar <command> [args]");
@@ -15,56 +28,271 @@ fn print_usage() {
     println!("  decrypt <json_data> <sk_hex> Decrypt using D-SPNA-512");
     println!("  stream-decrypt <sk_hex>      Stream decrypt JSON from stdin");
     println!("  rebind <payload> <sk> <new_pk> Rebind a payload to a new key/HWID");
-    println!("  keygen                       Generate ML-KEM-1024 keys");
+    println!("  keygen [--seal] [--hybrid]   Generate ML-KEM-1024 (or X25519 hybrid) keys, optionally sealing SK");
+    println!("    [--mldsa]                  Generate an ML-DSA-65 channel identity instead (ml-dsa feature)");
+    println!("    [--kem 512|768|1024]       ML-KEM parameter set for the keys (default: 1024)");
+    println!(
+        "  hpke-encrypt <payload> <pk_hex> Encrypt with HPKE (RFC 9180) instead of D-SPNA-512"
+    );
+    println!("  hpke-decrypt <json_data> <sk_hex> Decrypt an HPKE envelope");
+    println!("  connect <addr> <sk> <peer_pk> Open a secure channel; stdin is sent, the peer's data printed");
+    println!("  listen <addr> <sk> <peer_pk>  Accept one secure channel connection on <addr>");
+    println!("    <addr> is host:port, or unix:<path> for a Unix socket");
+    println!("  seal <data>                  Seal data to this machine's HWID");
+    println!("  unseal <sealed_json>         Unseal data on the machine it was sealed to");
     println!("  test                         Run D-SPNA-512 self-test");
+    println!("  self-test                    Run the power-on KATs and report module status");
+    println!("  kem-kat [count] [seed_hex]   Print deterministic ML-KEM-1024 KAT records");
+    println!(
+        "  acvp <prompt> [expected]     Run an ACVP ML-KEM vector set on the Rust and C engines"
+    );
+    println!("Options:");
+    println!("  --hwid <hex>                 Bind to a hardware ID (repeat to allow a fleet)");
+    println!("  --hwid-part <hex>            Add a k-of-n binding component (repeatable)");
+    println!("  --threshold <k>              Components required to decrypt (default: all)");
+    println!("  --new-hwid / --new-hwid-part Target binding for rebind");
+    println!("  --pin <pin>                  PIN for seal/unseal and sealed secret keys");
+    println!("  --session                    bulk-encrypt / stream-decrypt: one KEM encapsulation for all lines");
+    println!("  --rekey <n>                  Fresh KEM encapsulation every n session messages (default: 1024, 0: never)");
+    println!("  --dem <name>                 Body cipher for encrypt: dasp (default), aes-256-gcm, chacha20-poly1305");
+    println!("  --kdf hkdf-sha256|hkdf-sha512 HPKE KDF (default: hkdf-sha256)");
+    println!("  --aead <name>                HPKE AEAD: aes-128-gcm, aes-256-gcm (default), chacha20-poly1305");
+    println!("  --info <hex> / --aad <hex>   HPKE info and associated data");
+    println!("  --psk <hex> --psk-id <hex>   HPKE PSK mode");
+    println!(
+        "  --identity <seed_hex>        ML-DSA-65 identity that signs connect/listen handshakes"
+    );
+    println!("  --peer-identity <pk_hex>     ML-DSA-65 key the channel peer must sign with");
+    println!("  --c-lib <path>               C engine library for acvp (default: ../c/dspna512)");
+    println!("  --out <dir>                  Directory for acvp response files");
 }
 
-fn clean_hex(s: &str) -> String {
-    s.chars().filter(|c| c.is_ascii_hexdigit()).collect()
+/// Generates a keypair (with its pairwise consistency test) or exits.
+fn generate_keypair() -> (
+    ml_kem::kem::DecapsulationKey<ml_kem::MlKem1024Params>,
+    ml_kem::kem::EncapsulationKey<ml_kem::MlKem1024Params>,
+) {
+    selftest::generate_keypair().unwrap_or_else(|e| {
+        eprintln!("Keygen Failed: {}", e);
+        std::process::exit(1);
+    })
 }
 
-fn resolve_arg(arg: &str) -> String {
-    if let Some(stripped) = arg.strip_prefix('@') {
-        let path = std::path::Path::new(stripped);
-        let content = std::fs::read_to_string(path)
-            .unwrap_or_else(|e| panic!("Error reading argument @file '{}': {}", path.display(), e));
-        content.trim().to_string()
-    } else {
-        arg.to_string()
+/// The `connect` / `listen` endpoint, with ML-DSA identities when given.
+fn channel_endpoint(
+    sk: &str,
+    peer_pk: &str,
+    dem: Option<&'static dyn dasp_crypto::dem::Dem>,
+    identity: Option<String>,
+    peer_identity: Option<Vec<u8>>,
+) -> Result<channel::Endpoint, Box<dyn Error>> {
+    let mut endpoint = channel::Endpoint::new(sk, peer_pk)?;
+    if let Some(dem) = dem {
+        endpoint = endpoint.with_dem(dem);
+    }
+    #[cfg(feature = "ml-dsa")]
+    {
+        if let Some(seed) = identity {
+            let seed = codec::decode_secret(&seed, mldsa::SEED_BYTES)?;
+            let seed: &[u8; mldsa::SEED_BYTES] = seed[..].try_into()?;
+            endpoint = endpoint.with_identity(Box::new(mldsa::MlDsaIdentity::from_seed(seed)));
+        }
+        if let Some(pk) = peer_identity {
+            endpoint = endpoint.with_peer_identity(Box::new(mldsa::MlDsaPeer::from_bytes(&pk)?));
+        }
     }
+    #[cfg(not(feature = "ml-dsa"))]
+    if identity.is_some() || peer_identity.is_some() {
+        return Err("ML-DSA identities need a build with the `ml-dsa` feature".into());
+    }
+    Ok(endpoint)
 }
 
-fn main() {
-    let mut raw_args: Vec<String> = std::env::args().skip(1).collect();
-    let mut hwid: Option<Vec<u8>> = None;
-    let mut new_hwid: Option<Vec<u8>> = None;
-    let mut ttl_secs: Option<u64> = None;
-    let mut telemetry = false;
-
-    let mut i = 0;
-    while i < raw_args.len() {
-        if raw_args[i] == "--hwid" && i + 1 < raw_args.len() {
-            let hw_hex = resolve_arg(&raw_args.remove(i + 1));
-            raw_args.remove(i);
-            hwid = Some(hex::decode(clean_hex(&hw_hex)).expect("Invalid HWID hex"));
-        } else if raw_args[i] == "--new-hwid" && i + 1 < raw_args.len() {
-            let hw_hex = resolve_arg(&raw_args.remove(i + 1));
-            raw_args.remove(i);
-            new_hwid = Some(hex::decode(clean_hex(&hw_hex)).expect("Invalid New HWID hex"));
-        } else if raw_args[i] == "--ttl" && i + 1 < raw_args.len() {
-            let t_str = resolve_arg(&raw_args.remove(i + 1));
-            raw_args.remove(i);
-            ttl_secs = Some(t_str.parse::<u64>().expect("Invalid TTL value"));
-        } else if raw_args[i] == "--diagnostic" {
-            raw_args.remove(i);
-            std::env::set_var("DASP_DIAGNOSTIC", "1");
-        } else if raw_args[i] == "--telemetry" {
-            raw_args.remove(i);
-            telemetry = true;
+/// Dials or accepts one connection on `addr` (host:port or unix:<path>) and
+/// relays it.
+fn run_channel(
+    command: &str,
+    addr: &str,
+    endpoint: &channel::Endpoint,
+) -> Result<(), Box<dyn Error>> {
+    #[cfg(unix)]
+    if let Some(path) = addr.strip_prefix("unix:") {
+        use std::os::unix::net::{UnixListener, UnixStream};
+        let stream = if command == "connect" {
+            UnixStream::connect(path)?
         } else {
-            i += 1;
+            let listener = UnixListener::bind(path)?;
+            let (stream, _) = listener.accept()?;
+            // One connection per run; nobody else should find the socket
+            std::fs::remove_file(path)?;
+            stream
+        };
+        return relay(command, stream, endpoint);
+    }
+    let stream = if command == "connect" {
+        std::net::TcpStream::connect(addr)?
+    } else {
+        let (stream, _) = std::net::TcpListener::bind(addr)?.accept()?;
+        stream
+    };
+    relay(command, stream, endpoint)
+}
+
+/// Runs the handshake, then sends stdin and prints what the peer sends until
+/// both sides have closed.
+fn relay<S>(command: &str, stream: S, endpoint: &channel::Endpoint) -> Result<(), Box<dyn Error>>
+where
+    S: channel::Duplex + Send + 'static,
+{
+    let secure = if command == "connect" {
+        endpoint.connect(stream)?
+    } else {
+        endpoint.accept(stream)?
+    };
+    eprintln!("Channel established ({})", secure.dem());
+    let (mut reader, mut writer) = secure.split()?;
+    let sending = std::thread::spawn(move || -> std::io::Result<()> {
+        std::io::copy(&mut std::io::stdin().lock(), &mut writer)?;
+        writer.close()
+    });
+    std::io::copy(&mut reader, &mut std::io::stdout().lock())?;
+    sending.join().map_err(|_| "Sender thread panicked")??;
+    Ok(())
+}
+
+fn resolve_arg(arg: &str) -> String {
+    cli::resolve_arg(arg).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Secret keys may be given as hex, base64 or a sealed blob; unseal the latter
+/// with the local HWID. Decoding is left to the engine's constant-time codec.
+fn resolve_sk(arg: &str, pin: Option<&[u8]>) -> Zeroizing<String> {
+    let sk = Zeroizing::new(resolve_arg(arg));
+    if !seal::is_sealed(&sk) {
+        return sk;
+    }
+    let hwid = seal::local_hwid().unwrap_or_else(|e| {
+        eprintln!("Unseal Failed: {}", e);
+        std::process::exit(1);
+    });
+    match seal::unseal(&sk, &hwid, pin) {
+        Ok(bytes) => Zeroizing::new(
+            std::str::from_utf8(&bytes)
+                .unwrap_or_else(|_| {
+                    eprintln!("Unseal Failed: sealed key is not hex text");
+                    std::process::exit(1);
+                })
+                .to_string(),
+        ),
+        Err(e) => {
+            eprintln!("Unseal Failed: {}", e);
+            std::process::exit(1);
         }
     }
+}
+
+fn read_json(path: &str) -> Result<serde_json::Value, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Runs an ACVP vector set on every available engine and writes one response
+/// file per engine. Returns whether every checked case passed.
+fn run_acvp(args: &[String], c_lib: Option<&str>, out: Option<&str>) -> Result<bool, String> {
+    let prompt_path = std::path::Path::new(&args[0]);
+    let prompt = read_json(&args[0])?;
+    let expected = args.get(1).map(|p| read_json(p)).transpose()?;
+
+    let mut engines: Vec<Box<dyn acvp::KemEngine>> = vec![Box::new(acvp::RustEngine)];
+    match c_lib {
+        Some(path) => engines.push(Box::new(acvp::CEngine::load(path.as_ref())?)),
+        None => {
+            let name = if cfg!(windows) {
+                "dspna512.dll"
+            } else {
+                "dspna512.so"
+            };
+            let default = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("..")
+                .join("c")
+                .join(name);
+            match acvp::CEngine::load(&default) {
+                Ok(engine) => engines.push(Box::new(engine)),
+                Err(_) => println!("c: skipped ({} not built; see --c-lib)", default.display()),
+            }
+        }
+    }
+
+    let dir = match out {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => prompt_path.parent().unwrap_or(".".as_ref()).to_path_buf(),
+    };
+    let stem = prompt_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("acvp");
+    let mut all_passed = true;
+    for engine in &engines {
+        let report = acvp::run(&prompt, expected.as_ref(), engine.as_ref())?;
+        let path = dir.join(format!("{}.{}.response.json", stem, engine.name()));
+        let body = serde_json::to_string_pretty(&report.response).map_err(|e| e.to_string())?;
+        std::fs::write(&path, body).map_err(|e| format!("{}: {}", path.display(), e))?;
+        println!(
+            "{}: {} passed, {} failed, {} skipped, {} unchecked -> {}",
+            engine.name(),
+            report.passed,
+            report.failed,
+            report.skipped,
+            report.unchecked,
+            path.display()
+        );
+        all_passed &= report.failed == 0;
+    }
+    Ok(all_passed)
+}
+
+fn main() {
+    let opts = cli::parse_args(std::env::args().skip(1).collect(), cli::resolve_arg)
+        .unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            print_usage();
+            std::process::exit(1);
+        });
+    if opts.diagnostic {
+        std::env::set_var("DASP_DIAGNOSTIC", "1");
+    }
+    let cli::CliOptions {
+        args: mut raw_args,
+        hwids,
+        new_hwids,
+        hwid_parts,
+        new_hwid_parts,
+        threshold,
+        pin,
+        seal_sk,
+        hybrid,
+        kem: kem_set,
+        dem,
+        session,
+        rekey,
+        hpke_kdf,
+        hpke_aead,
+        hpke_info,
+        hpke_aad,
+        hpke_psk,
+        hpke_psk_id,
+        identity,
+        peer_identity,
+        mldsa,
+        ttl_secs,
+        telemetry,
+        c_lib,
+        out,
+        ..
+    } = opts;
 
     if raw_args.is_empty() {
         print_usage();
@@ -72,7 +300,54 @@ fn main() {
     }
 
     let command = raw_args.remove(0);
-    let dc = DarkstarCrypt::new();
+    let dc = match dem {
+        Some(dem) => DarkstarCrypt::new().with_dem(dem),
+        None => DarkstarCrypt::new(),
+    };
+    let pin = pin.as_deref().map(str::as_bytes);
+
+    // Fuzzy (k-of-n) binding takes over whenever components are supplied
+    let encrypt_with = |payload: &str, pk_hex: &str, hwids: &[Vec<u8>], parts: &[Vec<u8>]| {
+        if parts.is_empty() {
+            dc.encrypt(payload, pk_hex, hwids, telemetry)
+        } else {
+            let k = threshold.unwrap_or(parts.len() as u8);
+            dc.encrypt_fuzzy(payload, pk_hex, parts, k, telemetry)
+        }
+    };
+    // Decryption always runs on one machine, so only the first --hwid applies
+    let hwid = hwids.first().cloned();
+    let decrypt_with = |data: &str, sk_hex: &str, hwid: Option<Vec<u8>>| {
+        if hwid_parts.is_empty() {
+            dc.decrypt(data, sk_hex, hwid, telemetry, ttl_secs)
+        } else {
+            dc.decrypt_fuzzy(data, sk_hex, &hwid_parts, telemetry, ttl_secs)
+        }
+    };
+
+    let hpke_params = || {
+        let psk = match (hpke_psk.clone(), hpke_psk_id.clone()) {
+            (Some(key), Some(id)) => Some((Zeroizing::new(key), id)),
+            (None, None) => None,
+            _ => {
+                eprintln!("Error: --psk and --psk-id must be given together");
+                std::process::exit(1);
+            }
+        };
+        let defaults = hpke::Params::default();
+        hpke::Params {
+            kdf: hpke_kdf.unwrap_or(defaults.kdf),
+            aead: hpke_aead.unwrap_or(defaults.aead),
+            info: hpke_info.clone(),
+            aad: hpke_aad.clone(),
+            psk,
+        }
+    };
+
+    if session && (hwids.len() > 1 || !hwid_parts.is_empty()) {
+        eprintln!("Error: --session supports a single --hwid");
+        std::process::exit(1);
+    }
 
     match command.as_str() {
         "encrypt" => {
@@ -83,7 +358,7 @@ fn main() {
             let payload = resolve_arg(&raw_args[0]);
             let pk_hex = resolve_arg(&raw_args[1]);
 
-            match dc.encrypt(&payload, &pk_hex, hwid, telemetry) {
+            match encrypt_with(&payload, &pk_hex, &hwids, &hwid_parts) {
                 Ok(res_json) => println!("{}", res_json),
                 Err(e) => {
                     eprintln!("Encryption Failed: {}", e);
@@ -96,17 +371,42 @@ fn main() {
                 print_usage();
                 return;
             }
-            let count: usize = raw_args[0].parse().unwrap();
+            let count = cli::parse_count(&raw_args[0]).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
             let payload = resolve_arg(&raw_args[1]);
             let pk_hex = resolve_arg(&raw_args[2]);
 
+            // One encapsulation up front; each line then carries only sid/seq
+            let mut sender = if session {
+                let started = SessionSender::start(
+                    &pk_hex,
+                    hwid.as_deref(),
+                    dem.unwrap_or(dasp_crypto::dem::default_dem()),
+                    rekey.unwrap_or(DEFAULT_REKEY_INTERVAL),
+                );
+                match started {
+                    Ok((sender, header)) => {
+                        println!("{}", header);
+                        Some(sender)
+                    }
+                    Err(e) => {
+                        eprintln!("Session Setup Failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                None
+            };
+
             for i in 0..count {
-                let varying_payload = if payload.len() > 10 {
-                    format!("{}{:010}", &payload[..payload.len() - 10], i)
-                } else {
-                    format!("{}{}", payload, i)
+                let varying_payload = cli::bulk_payload(&payload, i);
+                let sealed = match sender.as_mut() {
+                    Some(sender) => sender.seal(varying_payload.as_bytes()),
+                    None => encrypt_with(&varying_payload, &pk_hex, &hwids, &hwid_parts),
                 };
-                match dc.encrypt(&varying_payload, &pk_hex, hwid.clone(), telemetry) {
+                match sealed {
                     Ok(res_json) => println!("{}", res_json),
                     Err(e) => {
                         eprintln!("Bulk Encryption Failed at index {}: {}", i, e);
@@ -121,9 +421,9 @@ fn main() {
                 return;
             }
             let data = resolve_arg(&raw_args[0]);
-            let sk_hex = resolve_arg(&raw_args[1]);
+            let sk_hex = resolve_sk(&raw_args[1], pin);
 
-            match dc.decrypt(&data, &sk_hex, hwid, telemetry, ttl_secs) {
+            match decrypt_with(&data, &sk_hex, hwid) {
                 Ok(decrypted) => println!("{}", decrypted),
                 Err(e) => {
                     eprintln!("Decryption Failed: {}", e);
@@ -136,31 +436,82 @@ fn main() {
                 print_usage();
                 return;
             }
-            let sk_hex = resolve_arg(&raw_args[0]);
+            let sk_hex = resolve_sk(&raw_args[0], pin);
             let stdin = std::io::stdin();
+            let mut receiver: Option<SessionReceiver> = None;
             for data in stdin.lines().map_while(Result::ok) {
                 let data = data.trim();
                 if data.is_empty() {
                     continue;
                 }
-                match dc.decrypt(data, &sk_hex, hwid.clone(), telemetry, ttl_secs) {
+                if session {
+                    // The first line is the session header, the rest are messages
+                    let Some(rx) = receiver.as_mut() else {
+                        match SessionReceiver::accept(data, &sk_hex, hwid.as_deref()) {
+                            Ok(rx) => receiver = Some(rx),
+                            Err(e) => {
+                                eprintln!("Session Setup Failed: {}", e);
+                                std::process::exit(1);
+                            }
+                        }
+                        continue;
+                    };
+                    match rx.open(data) {
+                        Ok(opened) => println!("{}", String::from_utf8_lossy(&opened.plaintext)),
+                        Err(e) => println!("{}", serde_json::json!({ "error": e.to_string() })),
+                    }
+                    continue;
+                }
+                match decrypt_with(data, &sk_hex, hwid.clone()) {
                     Ok(decrypted) => println!("{}", decrypted),
                     Err(_) => println!("{{\"error\":\"MAC Failed\"}}"),
                 }
             }
         }
+        "hpke-encrypt" => {
+            if raw_args.len() < 2 {
+                print_usage();
+                return;
+            }
+            let payload = resolve_arg(&raw_args[0]);
+            let pk_hex = resolve_arg(&raw_args[1]);
+
+            match dc.hpke_encrypt(payload.as_bytes(), &pk_hex, &hpke_params()) {
+                Ok(res_json) => println!("{}", res_json),
+                Err(e) => {
+                    eprintln!("Encryption Failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        "hpke-decrypt" => {
+            if raw_args.len() < 2 {
+                print_usage();
+                return;
+            }
+            let data = resolve_arg(&raw_args[0]);
+            let sk_hex = resolve_sk(&raw_args[1], pin);
+
+            match dc.hpke_decrypt(&data, &sk_hex, &hpke_params()) {
+                Ok(decrypted) => println!("{}", String::from_utf8_lossy(&decrypted)),
+                Err(e) => {
+                    eprintln!("Decryption Failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         "rebind" => {
             if raw_args.len() < 3 {
                 print_usage();
                 return;
             }
             let data = resolve_arg(&raw_args[0]);
-            let sk_hex = resolve_arg(&raw_args[1]);
+            let sk_hex = resolve_sk(&raw_args[1], pin);
             let pk_hex = resolve_arg(&raw_args[2]);
 
-            match dc.decrypt(&data, &sk_hex, hwid, telemetry, ttl_secs) {
+            match decrypt_with(&data, &sk_hex, hwid) {
                 Ok(mut decrypted) => {
-                    match dc.encrypt(&decrypted, &pk_hex, new_hwid, telemetry) {
+                    match encrypt_with(&decrypted, &pk_hex, &new_hwids, &new_hwid_parts) {
                         Ok(res_json) => {
                             println!("{}", res_json);
                         }
@@ -179,19 +530,107 @@ fn main() {
                 }
             }
         }
+        "keygen" if mldsa => {
+            #[cfg(feature = "ml-dsa")]
+            match mldsa::keypair() {
+                Ok((pk, seed)) => {
+                    println!("ML-DSA-PK: {}", hex::encode(pk));
+                    println!("ML-DSA-SEED: {}", codec::encode_hex(&seed[..]).as_str());
+                }
+                Err(e) => {
+                    eprintln!("Keygen Failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            #[cfg(not(feature = "ml-dsa"))]
+            {
+                eprintln!("Keygen Failed: built without the `ml-dsa` feature");
+                std::process::exit(1);
+            }
+        }
+        "connect" | "listen" => {
+            if raw_args.len() < 3 {
+                print_usage();
+                return;
+            }
+            let sk = resolve_sk(&raw_args[1], pin);
+            let peer_pk = resolve_arg(&raw_args[2]);
+            let result = channel_endpoint(&sk, &peer_pk, dem, identity, peer_identity)
+                .and_then(|endpoint| run_channel(&command, &raw_args[0], &endpoint));
+            if let Err(e) = result {
+                eprintln!("Channel Failed: {}", e);
+                std::process::exit(1);
+            }
+        }
         "keygen" => {
-            let (dk, ek) = MlKem1024::generate(&mut rand::rngs::OsRng);
-            println!("PK: {}", hex::encode(ek.as_bytes()));
-            println!("SK: {}", hex::encode(dk.as_bytes()));
+            let set = kem_set.unwrap_or_default();
+            let keys = if hybrid && set != kem::ParameterSet::MlKem1024 {
+                Err("Hybrid keys use ML-KEM-1024".into())
+            } else if hybrid {
+                hybrid::keypair().map(|(pk, sk)| (pk.to_vec(), Zeroizing::new(sk.to_vec())))
+            } else {
+                set.keypair()
+            };
+            let (pk, sk) = keys.unwrap_or_else(|e| {
+                eprintln!("Keygen Failed: {}", e);
+                std::process::exit(1);
+            });
+            let (pk_hex, sk_hex) = (hex::encode(pk), codec::encode_hex(&sk));
+            println!("PK: {}", pk_hex);
+            if seal_sk {
+                let sealed =
+                    seal::local_hwid().and_then(|local| seal::seal(sk_hex.as_bytes(), &local, pin));
+                match sealed {
+                    Ok(blob) => println!("SK-SEALED: {}", blob),
+                    Err(e) => {
+                        eprintln!("Seal Failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                println!("SK: {}", sk_hex.as_str());
+            }
+        }
+        "seal" | "unseal" => {
+            if raw_args.is_empty() {
+                print_usage();
+                return;
+            }
+            let input = resolve_arg(&raw_args[0]);
+            let target = match hwids.first() {
+                Some(h) => h.clone(),
+                None => seal::local_hwid().unwrap_or_else(|e| {
+                    eprintln!("Seal Failed: {}", e);
+                    std::process::exit(1);
+                }),
+            };
+
+            if command == "seal" {
+                match seal::seal(input.as_bytes(), &target, pin) {
+                    Ok(blob) => println!("{}", blob),
+                    Err(e) => {
+                        eprintln!("Seal Failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                match seal::unseal(&input, &target, pin) {
+                    Ok(data) => println!("{}", String::from_utf8_lossy(&data)),
+                    Err(e) => {
+                        eprintln!("Unseal Failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
         }
         "test" => {
             let payload = "apple banana cherry date elderberry fig grape honeydew";
-            let (dk, ek) = MlKem1024::generate(&mut rand::rngs::OsRng);
+            let (dk, ek) = generate_keypair();
             let pk_hex = hex::encode(ek.as_bytes());
-            let sk_hex = hex::encode(dk.as_bytes());
+            let sk_hex = codec::encode_hex(&dk.as_bytes());
 
             println!("--- D-SPNA-512 Self-Test ---");
-            match dc.encrypt(payload, &pk_hex, None, telemetry) {
+            match dc.encrypt(payload, &pk_hex, &[], telemetry) {
                 Ok(res_json) => {
                     println!("Encrypted: {}", res_json);
 
@@ -217,6 +656,62 @@ fn main() {
                 }
             }
         }
+        "self-test" => {
+            let result = selftest::check();
+            println!("--- D-SPNA-512 Power-On Self-Tests ---");
+            for kat in &selftest::POWER_ON_TESTS {
+                println!("  {}", kat.name);
+            }
+            println!("Status: {}", selftest::status());
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        "kem-kat" => {
+            let count = raw_args.first().map_or(Ok(10), |c| c.parse::<usize>());
+            // Same default as NIST's PQCgenKAT: entropy bytes 0, 1, ..., 47
+            let entropy = raw_args
+                .get(1)
+                .map_or_else(|| Ok((0..48).collect()), hex::decode);
+            let vectors = match (count, entropy) {
+                (Ok(count), Ok(entropy)) => kem::kat_vectors(&entropy, count),
+                _ => Err("Invalid count or seed hex".into()),
+            };
+            match vectors {
+                Ok(vectors) => {
+                    println!("# ML-KEM-1024\n");
+                    for (i, v) in vectors.iter().enumerate() {
+                        println!("count = {}", i);
+                        println!("d = {}", hex::encode_upper(v.d));
+                        println!("z = {}", hex::encode_upper(v.z));
+                        println!("msg = {}", hex::encode_upper(v.m));
+                        println!("pk = {}", hex::encode_upper(v.pk));
+                        println!("sk = {}", hex::encode_upper(&v.sk[..]));
+                        println!("ct = {}", hex::encode_upper(v.ct));
+                        println!("ss = {}\n", hex::encode_upper(&v.ss[..]));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("KAT Generation Failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        "acvp" => {
+            if raw_args.is_empty() {
+                print_usage();
+                return;
+            }
+            match run_acvp(&raw_args, c_lib.as_deref(), out.as_deref()) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("ACVP Run Failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         _ => {
             eprintln!("Error: Unknown command '{}'", command);
             print_usage();
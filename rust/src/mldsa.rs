@@ -0,0 +1,97 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * ML-DSA-65 Channel Identities (`ml-dsa` feature)
+ *
+ * FIPS 204 signatures over the secure channel's handshake transcript, for
+ * deployments that name peers by a signing key rather than, or as well as,
+ * their KEM key. The secret key is kept in its 32-byte seed form (FIPS 204
+ * xi) and expanded when loaded; the public key is the 1952-byte encoded
+ * verifying key. Signing is deterministic with an empty context string.
+ */
+
+use crate::channel::{Identity, PeerIdentity};
+use ml_dsa::signature::{Signer, Verifier};
+use ml_dsa::{EncodedSignature, EncodedVerifyingKey, KeyGen, MlDsa65, Signature, VerifyingKey};
+use zeroize::Zeroizing;
+
+pub const SEED_BYTES: usize = 32;
+
+/// Generates an identity from the DRBG: (public key, seed).
+pub fn keypair() -> Result<(Vec<u8>, Zeroizing<[u8; SEED_BYTES]>), String> {
+    let seed = crate::drbg::random::<SEED_BYTES>()?;
+    let identity = MlDsaIdentity::from_seed(&seed);
+    Ok((identity.public_key(), seed))
+}
+
+/// This endpoint's ML-DSA-65 signing key.
+pub struct MlDsaIdentity {
+    keypair: ml_dsa::KeyPair<MlDsa65>,
+}
+
+impl MlDsaIdentity {
+    pub fn from_seed(seed: &[u8; SEED_BYTES]) -> Self {
+        MlDsaIdentity {
+            keypair: MlDsa65::key_gen_internal(&(*seed).into()),
+        }
+    }
+
+    pub fn public_key(&self) -> Vec<u8> {
+        self.keypair.verifying_key().encode().to_vec()
+    }
+}
+
+impl Identity for MlDsaIdentity {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, String> {
+        Ok(self.keypair.signing_key().sign(message).encode().to_vec())
+    }
+}
+
+/// The ML-DSA-65 key a peer must sign with.
+pub struct MlDsaPeer {
+    key: VerifyingKey<MlDsa65>,
+}
+
+impl MlDsaPeer {
+    pub fn from_bytes(public_key: &[u8]) -> Result<Self, String> {
+        let encoded = EncodedVerifyingKey::<MlDsa65>::try_from(public_key)
+            .map_err(|_| "Invalid ML-DSA-65 public key length".to_string())?;
+        Ok(MlDsaPeer {
+            key: VerifyingKey::decode(&encoded),
+        })
+    }
+}
+
+impl PeerIdentity for MlDsaPeer {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), String> {
+        let signature = EncodedSignature::<MlDsa65>::try_from(signature)
+            .ok()
+            .and_then(|encoded| Signature::decode(&encoded))
+            .ok_or_else(|| "Invalid ML-DSA-65 signature".to_string())?;
+        self.key
+            .verify(message, &signature)
+            .map_err(|_| "Peer signature is invalid".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_verify_only_under_their_key() {
+        let (pk, seed) = keypair().unwrap();
+        let identity = MlDsaIdentity::from_seed(&seed);
+        assert_eq!(identity.public_key(), pk);
+
+        let sig = identity.sign(b"transcript").unwrap();
+        let peer = MlDsaPeer::from_bytes(&pk).unwrap();
+        peer.verify(b"transcript", &sig).unwrap();
+        assert!(peer.verify(b"transcripT", &sig).is_err());
+
+        let (other, _) = keypair().unwrap();
+        let other = MlDsaPeer::from_bytes(&other).unwrap();
+        assert!(other.verify(b"transcript", &sig).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * HWID Sealing ("seal to this machine")
+ *
+ * Sealed blobs need no KEM keypair: the HUB blend runs with the local hardware
+ * ID as the extract salt and a random per-blob seed (plus an optional, stretched
+ * PIN) as the input keying material. The D-ASP body cipher and HMAC then work
+ * exactly as in a regular envelope.
+ */
+
+use crate::engine::{ctr_xor, hub_blend, DaspKeys};
+use crate::secret::SecretBuffer;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Format tag stored in the `v` field of every sealed blob.
+pub const SEAL_VERSION: &str = "dasp-seal-1";
+
+/// PBKDF2-HMAC-SHA256 rounds applied to the PIN.
+const PIN_ROUNDS: u32 = 100_000;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SealedBlob {
+    v: String,
+    s: String,
+    pin: bool,
+    data: String,
+    mac: String,
+}
+
+fn seal_keys(hwid: &[u8], seed: &[u8; 32], pin: Option<&[u8]>) -> Result<DaspKeys, String> {
+    let mut ikm = Zeroizing::new(Vec::with_capacity(12 + 32 + 32));
+    ikm.extend_from_slice(b"dasp-seal-v1");
+    ikm.extend_from_slice(seed);
+    if let Some(pin) = pin {
+        let mut pin_key = Zeroizing::new([0u8; 32]);
+        pbkdf2::pbkdf2_hmac::<Sha256>(pin, seed, PIN_ROUNDS, &mut pin_key[..]);
+        ikm.extend_from_slice(&pin_key[..]);
+    }
+
+    let blended = hub_blend(hwid, &ikm)?;
+    DaspKeys::derive(&blended)
+}
+
+fn seal_mac(keys: &DaspKeys, seed: &[u8], pin: bool, data: &[u8]) -> Result<[u8; 32], String> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(&keys.hmac_key)
+        .map_err(|e| format!("HMAC error: {:?}", e))?;
+    mac.update(SEAL_VERSION.as_bytes());
+    mac.update(seed);
+    mac.update(&[pin as u8]);
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().into())
+}
+
+/// Seals `data` to a hardware ID, optionally behind a PIN.
+///
+/// # Arguments
+/// * `data` - The secret to protect (e.g. an ML-KEM secret key).
+/// * `hwid` - The machine identity; see [`local_hwid`].
+/// * `pin` - Optional PIN, stretched with PBKDF2 before it enters the HUB blend.
+pub fn seal(
+    data: &[u8],
+    hwid: &[u8],
+    pin: Option<&[u8]>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    crate::selftest::check()?;
+    let seed = *crate::drbg::random::<32>()?;
+
+    let keys = seal_keys(hwid, &seed, pin)?;
+    let mut body = SecretBuffer::from_slice(data);
+    ctr_xor(&mut body, &keys);
+    let tag = seal_mac(&keys, &seed, pin.is_some(), &body)?;
+
+    let blob = SealedBlob {
+        v: SEAL_VERSION.to_string(),
+        s: hex::encode(seed),
+        pin: pin.is_some(),
+        data: hex::encode(&body),
+        mac: hex::encode(tag),
+    };
+    Ok(serde_json::to_string(&blob)?)
+}
+
+/// Opens a blob produced by [`seal`] on the machine (and with the PIN) it was sealed to.
+///
+/// The secret is decrypted in place in a locked buffer that is wiped on drop.
+pub fn unseal(
+    sealed: &str,
+    hwid: &[u8],
+    pin: Option<&[u8]>,
+) -> Result<SecretBuffer, Box<dyn std::error::Error>> {
+    crate::selftest::check()?;
+    let blob: SealedBlob = serde_json::from_str(sealed)?;
+    if blob.v != SEAL_VERSION {
+        return Err(format!("Unsupported seal version '{}'", blob.v).into());
+    }
+    if blob.pin && pin.is_none() {
+        return Err("Sealed blob requires a PIN".into());
+    }
+
+    let seed: [u8; 32] = hex::decode(&blob.s)?
+        .try_into()
+        .map_err(|_| "Invalid seal seed length")?;
+    let mut body = crate::codec::decode_hex(&blob.data)?;
+    let expected = hex::decode(&blob.mac)?;
+    if expected.len() != 32 {
+        return Err("Invalid seal MAC length".into());
+    }
+
+    let pin = if blob.pin { pin } else { None };
+    let keys = seal_keys(hwid, &seed, pin)?;
+    let actual = seal_mac(&keys, &seed, blob.pin, &body)?;
+
+    let mut diff = 0u8;
+    for i in 0..32 {
+        diff |= actual[i] ^ expected[i];
+    }
+    if unsafe { std::ptr::read_volatile(&diff) } != 0 {
+        return Err("Integrity Check Failed (wrong machine or PIN)".into());
+    }
+
+    ctr_xor(&mut body, &keys);
+    Ok(body)
+}
+
+/// Returns true when `s` looks like a blob produced by [`seal`].
+pub fn is_sealed(s: &str) -> bool {
+    let s = s.trim_start();
+    s.starts_with('{') && s.contains(SEAL_VERSION)
+}
+
+/// Reads the platform machine identity and hashes it to a 32-byte HWID.
+///
+/// Linux uses `/etc/machine-id`, Windows the `MachineGuid` registry value and
+/// macOS the `IOPlatformUUID`.
+pub fn local_hwid() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let raw = platform_machine_id()?;
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err("Platform machine identity is empty".into());
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(b"dasp-hwid-v1");
+    hasher.update(raw.as_bytes());
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(target_os = "linux")]
+fn platform_machine_id() -> Result<String, Box<dyn std::error::Error>> {
+    std::fs::read_to_string("/etc/machine-id")
+        .or_else(|_| std::fs::read_to_string("/var/lib/dbus/machine-id"))
+        .map_err(|e| format!("Cannot read machine-id: {}", e).into())
+}
+
+#[cfg(target_os = "windows")]
+fn platform_machine_id() -> Result<String, Box<dyn std::error::Error>> {
+    let out = std::process::Command::new("reg")
+        .args([
+            "query",
+            "HKLM\\SOFTWARE\\Microsoft\\Cryptography",
+            "/v",
+            "MachineGuid",
+        ])
+        .output()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    text.lines()
+        .find(|l| l.contains("MachineGuid"))
+        .and_then(|l| l.split_whitespace().last())
+        .map(|s| s.to_string())
+        .ok_or_else(|| "MachineGuid not found".into())
+}
+
+#[cfg(target_os = "macos")]
+fn platform_machine_id() -> Result<String, Box<dyn std::error::Error>> {
+    let out = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    text.lines()
+        .find(|l| l.contains("IOPlatformUUID"))
+        .and_then(|l| l.split('"').nth(3))
+        .map(|s| s.to_string())
+        .ok_or_else(|| "IOPlatformUUID not found".into())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn platform_machine_id() -> Result<String, Box<dyn std::error::Error>> {
+    Err("No platform machine identity on this target; pass an explicit HWID".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HWID: [u8; 32] = [7u8; 32];
+
+    fn tweak(sealed: &str, field: &str, value: &str) -> String {
+        let mut blob: serde_json::Value = serde_json::from_str(sealed).unwrap();
+        blob[field] = value.into();
+        blob.to_string()
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // 100k PBKDF2 rounds per PIN
+    fn round_trips_with_and_without_a_pin() {
+        for pin in [None, Some(&b"1234"[..])] {
+            let sealed = seal(b"ml-kem secret key", &HWID, pin).unwrap();
+            assert!(is_sealed(&sealed));
+            assert!(!sealed.contains(&hex::encode(b"ml-kem secret key")));
+            let opened = unseal(&sealed, &HWID, pin).unwrap();
+            assert_eq!(&opened[..], b"ml-kem secret key");
+        }
+    }
+
+    #[test]
+    fn another_machine_cannot_unseal() {
+        let sealed = seal(b"secret", &HWID, None).unwrap();
+        let err = unseal(&sealed, &[8u8; 32], None).unwrap_err();
+        assert!(err.to_string().starts_with("Integrity Check Failed"));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // 100k PBKDF2 rounds
+    fn a_pin_blob_needs_its_pin() {
+        let sealed = seal(b"secret", &HWID, Some(b"1234")).unwrap();
+        let err = unseal(&sealed, &HWID, None).unwrap_err();
+        assert_eq!(err.to_string(), "Sealed blob requires a PIN");
+    }
+
+    #[test]
+    fn malformed_blobs_are_rejected() {
+        let sealed = seal(b"secret", &HWID, None).unwrap();
+        let err = unseal(&tweak(&sealed, "v", "dasp-seal-0"), &HWID, None).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported seal version 'dasp-seal-0'");
+        let err = unseal(&tweak(&sealed, "s", "abcd"), &HWID, None).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid seal seed length");
+        let err = unseal(&tweak(&sealed, "mac", "abcd"), &HWID, None).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid seal MAC length");
+    }
+
+    #[test]
+    fn only_seal_blobs_are_recognised() {
+        let sealed = seal(b"secret", &HWID, None).unwrap();
+        assert!(is_sealed(&format!("  {}", sealed)));
+        assert!(!is_sealed(&"ab".repeat(32)));
+        assert!(!is_sealed(r#"{"data":"00","ct":"00"}"#));
+    }
+}
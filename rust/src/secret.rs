@@ -0,0 +1,407 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Locked, Guarded Memory for Key Material
+ *
+ * Every SecretBuffer owns whole pages so that `mlock`/`VirtualLock` never
+ * shares a page with unrelated data (locks do not nest, so a shared page would
+ * be unlocked by the first neighbour to drop). Contents are wiped with
+ * volatile writes before the pages are unlocked and released.
+ *
+ * A buffer costs a mapping and a lock per allocation, so it is meant for key
+ * material only. Payloads and frames use `Zeroizing<Vec<u8>>`.
+ */
+
+use std::ops::{Deref, DerefMut};
+use zeroize::Zeroize;
+
+/// Page-backed, memory-locked byte buffer that is zeroed on drop.
+pub struct SecretBuffer {
+    /// Start of the whole allocation, including guard pages.
+    base: *mut u8,
+    /// Total bytes allocated at `base`.
+    map_len: usize,
+    /// Start of the usable (page-aligned) region.
+    data: *mut u8,
+    /// Usable bytes, always a whole number of pages.
+    cap: usize,
+    /// Logical length exposed through `Deref`.
+    len: usize,
+    locked: bool,
+    guarded: bool,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Buffers allocated and not yet wiped on this thread; tests use it to prove
+    /// that error paths release (and therefore wipe) every piece of key material.
+    pub(crate) static LIVE_BUFFERS: std::cell::Cell<isize> = const { std::cell::Cell::new(0) };
+}
+
+// The buffer exclusively owns its pages; nothing is shared behind the pointers.
+unsafe impl Send for SecretBuffer {}
+unsafe impl Sync for SecretBuffer {}
+
+impl SecretBuffer {
+    /// Allocates `len` zeroed bytes and locks them into RAM (best effort).
+    pub fn new(len: usize) -> Self {
+        Self::allocate(len, false)
+    }
+
+    /// Like [`SecretBuffer::new`], with an inaccessible page on either side so
+    /// linear overruns fault instead of reading or corrupting key material.
+    /// Falls back to an unguarded buffer where page protection is unavailable.
+    pub fn with_guard_pages(len: usize) -> Self {
+        Self::allocate(len, true)
+    }
+
+    /// Copies `bytes` into a new locked buffer.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut buf = Self::new(bytes.len());
+        buf.copy_from_slice(bytes);
+        buf
+    }
+
+    fn allocate(len: usize, guard: bool) -> Self {
+        let page = sys::page_size();
+        let cap = len.max(1).div_ceil(page) * page;
+        let guard_len = if guard && sys::CAN_GUARD { page } else { 0 };
+        let map_len = cap + 2 * guard_len;
+
+        let base = sys::alloc_pages(map_len);
+        if base.is_null() {
+            std::alloc::handle_alloc_error(
+                std::alloc::Layout::from_size_align(map_len, page).unwrap(),
+            );
+        }
+        let data = unsafe { base.add(guard_len) };
+
+        let mut guarded = false;
+        if guard_len > 0 {
+            let end = unsafe { data.add(cap) };
+            guarded =
+                unsafe { sys::protect_none(base, guard_len) && sys::protect_none(end, guard_len) };
+        }
+        let locked = unsafe { sys::lock(data, cap) };
+        #[cfg(test)]
+        LIVE_BUFFERS.with(|n| n.set(n.get() + 1));
+
+        SecretBuffer {
+            base,
+            map_len,
+            data,
+            cap,
+            len,
+            locked,
+            guarded,
+        }
+    }
+
+    /// Whether the pages were successfully locked against swapping.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whether guard pages surround the buffer.
+    pub fn is_guarded(&self) -> bool {
+        self.guarded
+    }
+
+    /// Shrinks the logical length, wiping the bytes that fall off the end.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.as_mut_slice()[len..].zeroize();
+            self.len = len;
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.data, self.len) }
+    }
+
+    /// Views the buffer as native-endian `u64` words (`len / 8` of them).
+    pub fn as_words(&self) -> &[u64] {
+        // Page alignment satisfies u64 alignment on every supported target
+        unsafe { std::slice::from_raw_parts(self.data as *const u64, self.len / 8) }
+    }
+
+    pub fn as_words_mut(&mut self) -> &mut [u64] {
+        unsafe { std::slice::from_raw_parts_mut(self.data as *mut u64, self.len / 8) }
+    }
+}
+
+impl Deref for SecretBuffer {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for SecretBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+impl AsRef<[u8]> for SecretBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl std::fmt::Debug for SecretBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretBuffer([REDACTED; {}])", self.len)
+    }
+}
+
+impl Zeroize for SecretBuffer {
+    fn zeroize(&mut self) {
+        self.as_mut_slice().zeroize();
+    }
+}
+
+impl Drop for SecretBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            std::slice::from_raw_parts_mut(self.data, self.cap).zeroize();
+            if self.locked {
+                sys::unlock(self.data, self.cap);
+            }
+            sys::free_pages(self.base, self.map_len);
+        }
+        #[cfg(test)]
+        LIVE_BUFFERS.with(|n| n.set(n.get() - 1));
+    }
+}
+
+#[cfg(all(unix, not(miri)))]
+mod sys {
+    pub const CAN_GUARD: bool = true;
+
+    pub fn page_size() -> usize {
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if size > 0 {
+            size as usize
+        } else {
+            4096
+        }
+    }
+
+    pub fn alloc_pages(len: usize) -> *mut u8 {
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return std::ptr::null_mut();
+        }
+        #[cfg(target_os = "linux")]
+        unsafe {
+            // Keep key pages out of core dumps
+            libc::madvise(ptr, len, libc::MADV_DONTDUMP);
+        }
+        ptr as *mut u8
+    }
+
+    pub unsafe fn free_pages(ptr: *mut u8, len: usize) {
+        libc::munmap(ptr as *mut libc::c_void, len);
+    }
+
+    pub unsafe fn protect_none(ptr: *mut u8, len: usize) -> bool {
+        libc::mprotect(ptr as *mut libc::c_void, len, libc::PROT_NONE) == 0
+    }
+
+    pub unsafe fn lock(ptr: *mut u8, len: usize) -> bool {
+        libc::mlock(ptr as *const libc::c_void, len) == 0
+    }
+
+    pub unsafe fn unlock(ptr: *mut u8, len: usize) {
+        libc::munlock(ptr as *const libc::c_void, len);
+    }
+}
+
+#[cfg(all(windows, not(miri)))]
+mod sys {
+    use windows_sys::Win32::System::Memory::{
+        VirtualAlloc, VirtualFree, VirtualLock, VirtualProtect, VirtualUnlock, MEM_COMMIT,
+        MEM_RELEASE, MEM_RESERVE, PAGE_NOACCESS, PAGE_READWRITE,
+    };
+    use windows_sys::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+
+    pub const CAN_GUARD: bool = true;
+
+    pub fn page_size() -> usize {
+        let mut info: SYSTEM_INFO = unsafe { std::mem::zeroed() };
+        unsafe { GetSystemInfo(&mut info) };
+        if info.dwPageSize > 0 {
+            info.dwPageSize as usize
+        } else {
+            4096
+        }
+    }
+
+    pub fn alloc_pages(len: usize) -> *mut u8 {
+        unsafe {
+            VirtualAlloc(
+                std::ptr::null(),
+                len,
+                MEM_COMMIT | MEM_RESERVE,
+                PAGE_READWRITE,
+            ) as *mut u8
+        }
+    }
+
+    pub unsafe fn free_pages(ptr: *mut u8, _len: usize) {
+        VirtualFree(ptr as *mut core::ffi::c_void, 0, MEM_RELEASE);
+    }
+
+    pub unsafe fn protect_none(ptr: *mut u8, len: usize) -> bool {
+        let mut old = 0u32;
+        VirtualProtect(
+            ptr as *const core::ffi::c_void,
+            len,
+            PAGE_NOACCESS,
+            &mut old,
+        ) != 0
+    }
+
+    pub unsafe fn lock(ptr: *mut u8, len: usize) -> bool {
+        VirtualLock(ptr as *const core::ffi::c_void, len) != 0
+    }
+
+    pub unsafe fn unlock(ptr: *mut u8, len: usize) {
+        VirtualUnlock(ptr as *const core::ffi::c_void, len);
+    }
+}
+
+#[cfg(any(miri, not(any(unix, windows))))]
+mod sys {
+    // WASM and other targets have no page protection or locking, and Miri cannot
+    // model mlock/mprotect; keep the page-granular layout so behaviour
+    // (alignment, wiping) stays identical.
+    pub const CAN_GUARD: bool = false;
+    const PAGE: usize = 4096;
+
+    pub fn page_size() -> usize {
+        PAGE
+    }
+
+    pub fn alloc_pages(len: usize) -> *mut u8 {
+        let layout = std::alloc::Layout::from_size_align(len, PAGE).unwrap();
+        unsafe { std::alloc::alloc_zeroed(layout) }
+    }
+
+    pub unsafe fn free_pages(ptr: *mut u8, len: usize) {
+        let layout = std::alloc::Layout::from_size_align(len, PAGE).unwrap();
+        std::alloc::dealloc(ptr, layout);
+    }
+
+    pub unsafe fn protect_none(_ptr: *mut u8, _len: usize) -> bool {
+        false
+    }
+
+    pub unsafe fn lock(_ptr: *mut u8, _len: usize) -> bool {
+        false
+    }
+
+    pub unsafe fn unlock(_ptr: *mut u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The whole usable region, including bytes past the logical length.
+    fn capacity_bytes(buf: &SecretBuffer) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(buf.data, buf.cap) }
+    }
+
+    #[test]
+    fn empty_and_odd_lengths_get_whole_zeroed_pages() {
+        let page = sys::page_size();
+        for len in [0, 1, 100, page - 1, page, page + 1] {
+            let buf = SecretBuffer::new(len);
+            assert_eq!(buf.len(), len);
+            assert_eq!(buf.cap, len.max(1).div_ceil(page) * page);
+            assert_eq!(buf.data as usize % page, 0);
+            assert!(capacity_bytes(&buf).iter().all(|&b| b == 0));
+        }
+        assert!(SecretBuffer::new(0).is_empty());
+    }
+
+    #[test]
+    fn from_slice_round_trips() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        let buf = SecretBuffer::from_slice(&bytes);
+        assert_eq!(&buf[..], &bytes[..]);
+        assert_eq!(buf.as_ref(), buf.as_slice());
+        assert_eq!(&SecretBuffer::from_slice(&[])[..], &[] as &[u8]);
+    }
+
+    #[test]
+    fn truncate_wipes_the_tail() {
+        let mut buf = SecretBuffer::new(64);
+        buf.as_mut_slice().fill(0xaa);
+        assert!(buf.as_mut_slice().iter().all(|&b| b == 0xaa));
+
+        buf.truncate(10);
+        assert_eq!(buf.len(), 10);
+        assert!(buf.iter().all(|&b| b == 0xaa));
+        assert!(capacity_bytes(&buf)[10..].iter().all(|&b| b == 0));
+
+        // Growing is not truncation
+        buf.truncate(20);
+        assert_eq!(buf.len(), 10);
+    }
+
+    #[test]
+    #[cfg(all(unix, not(miri)))]
+    fn unix_buffers_are_guarded_and_locked() {
+        assert!(SecretBuffer::with_guard_pages(32).is_guarded());
+        assert!(!SecretBuffer::new(32).is_guarded());
+
+        // mlock only fails when RLIMIT_MEMLOCK is too small for the test run
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        let roomy = unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } == 0
+            && (limit.rlim_cur == libc::RLIM_INFINITY || limit.rlim_cur >= 1 << 20);
+        if roomy {
+            assert!(SecretBuffer::new(32).is_locked());
+            assert!(SecretBuffer::with_guard_pages(32).is_locked());
+        }
+    }
+
+    #[test]
+    fn words_view_covers_whole_words_only() {
+        for len in [0, 7, 8, 63, 64, 1024 + 5] {
+            let mut buf = SecretBuffer::new(len);
+            assert_eq!(buf.as_words().len(), len / 8);
+            assert_eq!(buf.as_words_mut().len(), len / 8);
+        }
+        let mut buf = SecretBuffer::new(16);
+        buf.as_words_mut()[1] = u64::from_ne_bytes(*b"abcdefgh");
+        assert_eq!(&buf[8..], b"abcdefgh");
+    }
+
+    #[test]
+    fn debug_redacts_the_contents() {
+        let buf = SecretBuffer::from_slice(b"hunter2");
+        let shown = format!("{:?}", buf);
+        assert_eq!(shown, "SecretBuffer([REDACTED; 7])");
+        assert!(!shown.contains("hunter2") && !shown.contains("104"));
+    }
+}
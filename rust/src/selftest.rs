@@ -0,0 +1,372 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Power-On and Conditional Self-Tests
+ *
+ * Modelled on FIPS 140-3 (ISO/IEC 19790) self-testing. The power-on tests run
+ * once per process, on the first cryptographic operation, and cover every
+ * primitive the engine depends on with known-answer tests. Every freshly
+ * generated ML-KEM keypair additionally gets a pairwise consistency test.
+ *
+ * Any failure moves the module into a sticky error state: from then on every
+ * operation (engine, seal, FFI) is refused until the process restarts.
+ */
+
+use crate::error::{Error, ErrorKind};
+use hmac::{Hmac, Mac};
+use ml_kem::kem::{Decapsulate, DecapsulationKey, EncapsulationKey};
+use ml_kem::{EncapsulateDeterministic, EncodedSizeUser, KemCore, MlKem1024, MlKem1024Params};
+use sha2::{Digest, Sha256, Sha512};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Once, OnceLock};
+
+/// Self-test state of the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// No cryptographic operation has run yet.
+    NotRun,
+    /// All power-on self-tests passed and no conditional test has failed.
+    Passed,
+    /// A self-test failed; every operation is refused.
+    Error,
+}
+
+impl Status {
+    /// Status code used by the C ABI: `0` passed, `1` not run, `-1` error state.
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Passed => 0,
+            Status::NotRun => 1,
+            Status::Error => -1,
+        }
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Status::NotRun => "NOT RUN",
+            Status::Passed => "PASSED",
+            Status::Error => "ERROR",
+        })
+    }
+}
+
+/// A single known-answer test.
+pub struct Kat {
+    pub name: &'static str,
+    run: fn() -> bool,
+}
+
+/// The power-on self-tests, in execution order.
+pub static POWER_ON_TESTS: [Kat; 8] = [
+    Kat {
+        name: "static-constants",
+        run: crate::engine::verify_constants,
+    },
+    Kat {
+        name: "sha-256",
+        run: kat_sha256,
+    },
+    Kat {
+        name: "sha-512",
+        run: kat_sha512,
+    },
+    Kat {
+        name: "hmac-sha-256",
+        run: kat_hmac_sha256,
+    },
+    Kat {
+        name: "hmac-sha-512",
+        run: kat_hmac_sha512,
+    },
+    Kat {
+        name: "ml-kem-1024",
+        run: kat_ml_kem_1024,
+    },
+    Kat {
+        name: "chacha-prng",
+        run: kat_chacha_prng,
+    },
+    Kat {
+        name: "dasp-cascade-64",
+        run: kat_cascade_64,
+    },
+];
+
+const NOT_RUN: u8 = 0;
+const PASSED: u8 = 1;
+const ERROR: u8 = 2;
+
+struct Module {
+    state: AtomicU8,
+    reason: OnceLock<String>,
+    power_on: Once,
+}
+
+impl Module {
+    const fn new() -> Self {
+        Module {
+            state: AtomicU8::new(NOT_RUN),
+            reason: OnceLock::new(),
+            power_on: Once::new(),
+        }
+    }
+
+    fn status(&self) -> Status {
+        match self.state.load(Ordering::SeqCst) {
+            NOT_RUN => Status::NotRun,
+            PASSED => Status::Passed,
+            _ => Status::Error,
+        }
+    }
+
+    /// Enters the error state. Only the first failure reason is kept.
+    fn fail(&self, reason: String) {
+        let _ = self.reason.set(reason);
+        self.state.store(ERROR, Ordering::SeqCst);
+    }
+
+    fn error(&self) -> Error {
+        Error::new(
+            ErrorKind::SelfTest,
+            format!(
+                "SELF_TEST_ERROR: {}",
+                self.reason.get().map_or("module in error state", |r| r)
+            ),
+        )
+    }
+
+    fn check(&self, tests: &[Kat]) -> Result<(), Error> {
+        self.power_on
+            .call_once(|| match tests.iter().find(|kat| !(kat.run)()) {
+                Some(kat) => self.fail(format!("Power-on self-test '{}' failed", kat.name)),
+                None => {
+                    let _ = self.state.compare_exchange(
+                        NOT_RUN,
+                        PASSED,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    );
+                }
+            });
+        match self.status() {
+            Status::Passed => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn pairwise_consistency<K: KemCore>(
+        &self,
+        dk: &K::DecapsulationKey,
+        ek: &K::EncapsulationKey,
+    ) -> Result<(), Error> {
+        let m = crate::drbg::random::<32>()?;
+        let consistent = match ek.encapsulate_deterministic(&(*m).into()) {
+            Ok((ct, ss)) => dk.decapsulate(&ct).is_ok_and(|ss2| ss2 == ss),
+            Err(_) => false,
+        };
+        if !consistent {
+            self.fail("Pairwise consistency test failed on a generated keypair".into());
+        }
+        match self.status() {
+            Status::Passed => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+}
+
+static MODULE: Module = Module::new();
+
+/// Runs the power-on self-tests if they have not run in this process yet.
+///
+/// Returns an error if the module is (or just entered) the error state.
+/// Every engine, seal and FFI entry point calls this first.
+pub fn check() -> Result<(), Error> {
+    MODULE.check(&POWER_ON_TESTS)
+}
+
+/// The current self-test state, without running anything.
+pub fn status() -> Status {
+    MODULE.status()
+}
+
+/// Conditional self-test for a freshly generated keypair of parameter set `K`:
+/// encapsulates to `ek` and checks that `dk` recovers the same shared secret.
+/// A mismatch puts the module into the error state.
+pub fn pairwise_consistency<K: KemCore>(
+    dk: &K::DecapsulationKey,
+    ek: &K::EncapsulationKey,
+) -> Result<(), Error> {
+    MODULE.check(&POWER_ON_TESTS)?;
+    MODULE.pairwise_consistency::<K>(dk, ek)
+}
+
+/// Generates a keypair of parameter set `K` and runs the pairwise consistency
+/// test on it.
+pub fn generate_keypair_for<K: KemCore>(
+) -> Result<(K::DecapsulationKey, K::EncapsulationKey), Error> {
+    check()?;
+    let d = crate::drbg::random::<32>()?;
+    let z = crate::drbg::random::<32>()?;
+    let (dk, ek) = K::generate_deterministic(&(*d).into(), &(*z).into());
+    pairwise_consistency::<K>(&dk, &ek)?;
+    Ok((dk, ek))
+}
+
+/// Generates an ML-KEM-1024 keypair and runs the pairwise consistency test on it.
+pub fn generate_keypair() -> Result<
+    (
+        DecapsulationKey<MlKem1024Params>,
+        EncapsulationKey<MlKem1024Params>,
+    ),
+    Error,
+> {
+    generate_keypair_for::<MlKem1024>()
+}
+
+fn kat_sha256() -> bool {
+    hex::encode(Sha256::digest(b"abc")) == KAT_SHA256
+}
+
+fn kat_sha512() -> bool {
+    hex::encode(Sha512::digest(b"abc")) == KAT_SHA512
+}
+
+// RFC 4231, test case 2
+fn kat_hmac_sha256() -> bool {
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(b"Jefe") else {
+        return false;
+    };
+    mac.update(b"what do ya want for nothing?");
+    hex::encode(mac.finalize().into_bytes()) == KAT_HMAC_SHA256
+}
+
+fn kat_hmac_sha512() -> bool {
+    let Ok(mut mac) = Hmac::<Sha512>::new_from_slice(b"Jefe") else {
+        return false;
+    };
+    mac.update(b"what do ya want for nothing?");
+    hex::encode(mac.finalize().into_bytes()) == KAT_HMAC_SHA512
+}
+
+/// Deterministic keygen and encapsulation from fixed seeds, decapsulation of the
+/// result, and implicit rejection of a corrupted ciphertext.
+fn kat_ml_kem_1024() -> bool {
+    let (dk, ek) = MlKem1024::generate_deterministic(&[0x01; 32].into(), &[0x02; 32].into());
+    let Ok((ct, ss)) = ek.encapsulate_deterministic(&[0x03; 32].into()) else {
+        return false;
+    };
+    if hex::encode(Sha256::digest(ek.as_bytes())) != KAT_ML_KEM_EK
+        || hex::encode(Sha256::digest(dk.as_bytes())) != KAT_ML_KEM_DK
+        || hex::encode(Sha256::digest(ct)) != KAT_ML_KEM_CT
+        || hex::encode(ss) != KAT_ML_KEM_SS
+    {
+        return false;
+    }
+    let mut bad_ct = ct;
+    bad_ct[0] ^= 0x01;
+    match (dk.decapsulate(&ct), dk.decapsulate(&bad_ct)) {
+        (Ok(ok), Ok(rejected)) => ok == ss && hex::encode(rejected) == KAT_ML_KEM_REJECT,
+        _ => false,
+    }
+}
+
+fn kat_chacha_prng() -> bool {
+    // 40 words crosses two block refills
+    let mut rng = crate::engine::DarkstarChaChaPRNG::new("D-SPNA-512 self-test");
+    let mut hasher = Sha256::new();
+    for _ in 0..40 {
+        hasher.update(rng.next().to_le_bytes());
+    }
+    hex::encode(hasher.finalize()) == KAT_CHACHA_PRNG
+}
+
+fn kat_cascade_64() -> bool {
+    let mut round_keys = [0u64; 128];
+    for (i, rk) in round_keys.iter_mut().enumerate() {
+        *rk = 0x0123_4567_89ab_cdefu64.wrapping_mul(i as u64 + 1);
+    }
+    let mut block = [0u8; 64];
+    for (i, b) in block.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    crate::engine::dasp_cascade_64(&mut block, &round_keys);
+    hex::encode(block) == KAT_CASCADE_64
+}
+
+// SHA-2: FIPS 180-2 "abc" vectors. HMAC: RFC 4231 test case 2. The ML-KEM
+// entries are SHA-256 digests of the keys and ciphertext (the shared secrets are
+// given in full); the PRNG entry is the SHA-256 of its first 40 output words.
+const KAT_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const KAT_SHA512: &str = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                          2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+const KAT_HMAC_SHA256: &str = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+const KAT_HMAC_SHA512: &str = "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+                               9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737";
+const KAT_ML_KEM_EK: &str = "05227acb49aefea81141d2bbc32ed84178283517d724ebc04d570ce84725f656";
+const KAT_ML_KEM_DK: &str = "915abe97d618f15d1c32828816f335c3ef5f6ed590c65d1f5b2ffaeb90c6a99f";
+const KAT_ML_KEM_CT: &str = "c4064e9589a17679f66af906a0bcea93e0944af384c5f48d92740d015fb9b1fc";
+const KAT_ML_KEM_SS: &str = "d1180e60410880516e234bbebf268aa76a1e0c4802c1af0fc0f6846d3274db8a";
+const KAT_ML_KEM_REJECT: &str = "30e3b6c63718cdb5d605d3d4b31a8f08d97c35b81d29f8ba3fe41a77ba465db5";
+const KAT_CHACHA_PRNG: &str = "f6d0c34b7383563632b3ff4feb88f38ad99e244a40398644d5e30d311903cdde";
+const KAT_CASCADE_64: &str = "bd911eae246fb7774e95f8207f9a32358263660c2fcad42219e7c847de3cbc69\
+                              1303fde6070b5203f8919292091953708a0073281c5cd93bbde02a6c5605fbd7";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ml_kem::kem::Encapsulate;
+
+    #[test]
+    fn power_on_tests_pass() {
+        for kat in &POWER_ON_TESTS {
+            assert!((kat.run)(), "{} failed", kat.name);
+        }
+        assert_eq!(check(), Ok(()));
+        assert_eq!(status(), Status::Passed);
+    }
+
+    #[test]
+    fn failed_kat_enters_sticky_error_state() {
+        let module = Module::new();
+        assert_eq!(module.status(), Status::NotRun);
+        let broken = [Kat {
+            name: "broken",
+            run: || false,
+        }];
+        let err = module.check(&broken).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SelfTest);
+        assert!(err.to_string().contains("'broken'"), "{}", err);
+        assert_eq!(module.status(), Status::Error);
+        // The tests do not rerun and the module stays refused
+        assert!(module.check(&POWER_ON_TESTS).is_err());
+    }
+
+    #[test]
+    fn pairwise_mismatch_enters_error_state() {
+        let module = Module::new();
+        let passing = [Kat {
+            name: "ok",
+            run: || true,
+        }];
+        module.check(&passing).unwrap();
+
+        let (dk, ek) = MlKem1024::generate(&mut rand::rngs::OsRng);
+        module.pairwise_consistency::<MlKem1024>(&dk, &ek).unwrap();
+        let (other_dk, _) = MlKem1024::generate(&mut rand::rngs::OsRng);
+        assert!(module
+            .pairwise_consistency::<MlKem1024>(&other_dk, &ek)
+            .is_err());
+        assert_eq!(module.status(), Status::Error);
+        assert!(module.check(&passing).is_err());
+    }
+
+    #[test]
+    fn generated_keypairs_are_consistent() {
+        let (dk, ek) = generate_keypair().unwrap();
+        let (ct, ss) = ek.encapsulate(&mut rand::rngs::OsRng).unwrap();
+        assert_eq!(dk.decapsulate(&ct).unwrap(), ss);
+    }
+}
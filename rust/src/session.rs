@@ -0,0 +1,677 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * Sessions: One Encapsulation, Many Messages
+ *
+ * A sender encapsulates once and publishes a session header; every later
+ * message carries only the session ID, its sequence number and the body:
+ *
+ *   header  {"sid","ct"[,"xct"][,"kem"][,"dem"][,"rk"]}
+ *   message {"sid","seq"[,"ct"][,"xct"],"data","mac"}
+ *
+ * Keys come from a KDF chain, the symmetric step of the Double Ratchet, built
+ * from the same HMAC-SHA256 derivations as the rest of the KDF:
+ *
+ *   blended = HUB blend of the shared secret (HWID or all-zero salt)
+ *   CK_0    = HMAC(blended, "dasp-session-v1" || sid || rk || ct || xct || kem || dem)
+ *   MK_n    = HMAC(CK_n, "dasp-ratchet-msg")
+ *   CK_n+1  = HMAC(CK_n, "dasp-ratchet-chain")
+ *
+ * MK_n drives the session's DEM, whose tag also covers `sid || n`, so a
+ * message cannot be moved to another position or session. A chain key is
+ * overwritten as soon as the next one exists, and a message key is wiped once
+ * its message has been sealed or opened, so whoever captures a session's
+ * state later cannot go back and decrypt what it already handled.
+ *
+ * Every `rk` messages (message rk, 2rk, ...) the sender encapsulates afresh
+ * and sends the new ciphertext along with that message. Both sides mix it in
+ * before deriving MK_n:
+ *
+ *   CK_n   = HMAC(CK_n, "dasp-ratchet-rekey" || blended' || ct' || xct')
+ *
+ * so a captured session state stops decrypting at the next re-key. The KEM
+ * secret key itself can still decapsulate every recorded ciphertext: keeping
+ * old traffic safe from a leaked long-term key needs ephemeral keys from the
+ * receiver, which a one-way stream cannot provide.
+ *
+ * The receiver keeps the highest sequence number seen and a 64-message
+ * window behind it, holding message keys for gaps until they arrive or drop
+ * out of the window. Duplicates and messages older than the window are
+ * rejected; late messages inside the window open but are marked as such.
+ * Messages past a re-key the receiver has not seen yet cannot be opened until
+ * it arrives.
+ */
+
+use crate::dem::Dem;
+use crate::engine::{decapsulate, encapsulate, hub_blend, mac_header};
+use crate::kem::ParameterSet;
+use crate::secret::SecretBuffer;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::error::Error;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SESSION_ID_BYTES: usize = 16;
+
+/// How far behind the newest message a late one may still arrive.
+pub const REPLAY_WINDOW: u64 = 64;
+
+/// Messages between fresh encapsulations unless the sender picks otherwise.
+pub const DEFAULT_REKEY_INTERVAL: u64 = 1024;
+
+/// How far ahead of the chain a message may be; bounds the work an
+/// unauthenticated sequence number can cause.
+const MAX_SKIP: u64 = 4096;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    sid: String,
+    ct: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xct: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kem: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dem: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rk: Option<u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Message {
+    sid: String,
+    seq: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ct: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xct: Option<String>,
+    data: String,
+    mac: String,
+}
+
+pub(crate) fn hmac(key: &[u8], parts: &[&[u8]]) -> Result<SecretBuffer, String> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key)
+        .map_err(|e| format!("HMAC init error: {:?}", e))?;
+    for part in parts {
+        mac.update(part);
+    }
+    Ok(SecretBuffer::from_slice(&mac.finalize().into_bytes()))
+}
+
+/// One step of the chain: `(MK_n, CK_n+1)`.
+pub(crate) fn step(chain: &[u8]) -> Result<(SecretBuffer, SecretBuffer), String> {
+    Ok((
+        hmac(chain, &[b"dasp-ratchet-msg"])?,
+        hmac(chain, &[b"dasp-ratchet-chain"])?,
+    ))
+}
+
+/// What both ends agree on for the whole session.
+struct Params {
+    sid: [u8; SESSION_ID_BYTES],
+    dem: &'static dyn Dem,
+    /// HUB salt: the HWID, or all zeros when unbound.
+    salt: Vec<u8>,
+    /// Re-key every this many messages; 0 never re-keys.
+    rekey_every: u64,
+}
+
+impl Params {
+    fn is_rekey(&self, seq: u64) -> bool {
+        self.rekey_every != 0 && seq != 0 && seq.is_multiple_of(self.rekey_every)
+    }
+
+    fn root(&self, ss: &[u8], header: &[u8]) -> Result<SecretBuffer, String> {
+        let blended = hub_blend(&self.salt, ss)?;
+        hmac(
+            &blended,
+            &[
+                b"dasp-session-v1",
+                &self.sid,
+                &self.rekey_every.to_be_bytes(),
+                header,
+            ],
+        )
+    }
+
+    fn rekey(
+        &self,
+        chain: &[u8],
+        ss: &[u8],
+        ct: &[u8],
+        x_ct: Option<&[u8; 32]>,
+    ) -> Result<SecretBuffer, String> {
+        let blended = hub_blend(&self.salt, ss)?;
+        hmac(
+            chain,
+            &[
+                b"dasp-ratchet-rekey",
+                &blended,
+                ct,
+                x_ct.map_or(&[][..], |x| &x[..]),
+            ],
+        )
+    }
+
+    /// What the DEM tag covers ahead of the body: `sid || seq`.
+    fn message_header(&self, seq: u64) -> [u8; SESSION_ID_BYTES + 8] {
+        let mut header = [0u8; SESSION_ID_BYTES + 8];
+        header[..SESSION_ID_BYTES].copy_from_slice(&self.sid);
+        header[SESSION_ID_BYTES..].copy_from_slice(&seq.to_be_bytes());
+        header
+    }
+}
+
+/// The sending end of a session.
+pub struct SessionSender {
+    params: Params,
+    pk: Vec<u8>,
+    /// CK_next_seq.
+    chain: SecretBuffer,
+    next_seq: u64,
+}
+
+impl SessionSender {
+    /// Encapsulates to `pk_hex` and returns the sender with the session header
+    /// the receiver needs before any message.
+    ///
+    /// # Arguments
+    /// * `pk_hex` - ML-KEM (any parameter set) or hybrid public key, hex encoded.
+    /// * `hwid` - Optional hardware ID binding, as for single envelopes.
+    /// * `dem` - Body cipher for every message of the session.
+    /// * `rekey_every` - Messages between fresh encapsulations; 0 never re-keys.
+    pub fn start(
+        pk_hex: &str,
+        hwid: Option<&[u8]>,
+        dem: &'static dyn Dem,
+        rekey_every: u64,
+    ) -> Result<(Self, String), Box<dyn Error>> {
+        crate::selftest::check()?;
+        let pk = hex::decode(pk_hex).map_err(|_| "Invalid public key hex")?;
+        let (ct, x_ct, kem_set, ss) =
+            encapsulate(&pk).map_err(|e| format!("{} (Arg length: {})", e, pk_hex.len()))?;
+        let params = Params {
+            sid: *crate::drbg::random::<SESSION_ID_BYTES>()?,
+            dem,
+            salt: hwid.unwrap_or(&[0u8; 32]).to_vec(),
+            rekey_every,
+        };
+
+        let kem_name = (kem_set != ParameterSet::default()).then(|| kem_set.name());
+        let dem_name = (dem.name() != crate::dem::default_dem().name()).then(|| dem.name());
+        let header = Header {
+            sid: hex::encode(params.sid),
+            ct: hex::encode(&ct),
+            xct: x_ct.map(hex::encode),
+            kem: kem_name.map(str::to_string),
+            dem: dem_name.map(str::to_string),
+            rk: (rekey_every != 0).then_some(rekey_every),
+        };
+        let bound = mac_header(&ct, x_ct.as_ref(), kem_name, dem_name);
+        let chain = params.root(&ss[..], &bound)?;
+        Ok((
+            SessionSender {
+                params,
+                pk,
+                chain,
+                next_seq: 0,
+            },
+            serde_json::to_string(&header)?,
+        ))
+    }
+
+    /// The session ID, hex encoded as in every message.
+    pub fn session_id(&self) -> String {
+        hex::encode(self.params.sid)
+    }
+
+    /// Encrypts the next message of the session, re-keying first when it is due.
+    pub fn seal(&mut self, payload: &[u8]) -> Result<String, Box<dyn Error>> {
+        let seq = self.next_seq;
+        let next_seq = seq.checked_add(1).ok_or("Session message limit reached")?;
+
+        let mut rekey = None;
+        if self.params.is_rekey(seq) {
+            let (ct, x_ct, _, ss) = encapsulate(&self.pk)?;
+            self.chain = self
+                .params
+                .rekey(&self.chain, &ss[..], &ct, x_ct.as_ref())?;
+            rekey = Some((ct, x_ct));
+        }
+        let (message_key, chain) = step(&self.chain)?;
+        // CK_seq is gone from here on; MK_seq is wiped when this returns
+        self.chain = chain;
+        self.next_seq = next_seq;
+
+        let mut body = Zeroizing::new(payload.to_vec());
+        let tag = self.params.dem.seal(
+            &message_key,
+            &self.params.message_header(seq),
+            &mut body,
+            &[],
+        )?;
+        let (ct, xct) = match rekey {
+            Some((ct, x_ct)) => (Some(hex::encode(ct)), x_ct.map(hex::encode)),
+            None => (None, None),
+        };
+        Ok(serde_json::to_string(&Message {
+            sid: self.session_id(),
+            seq,
+            ct,
+            xct,
+            data: hex::encode(&body[..]),
+            mac: hex::encode(tag),
+        })?)
+    }
+}
+
+/// A message opened by [`SessionReceiver::open`].
+pub struct Opened {
+    pub seq: u64,
+    pub plaintext: Zeroizing<Vec<u8>>,
+    /// True when a later message had already been opened.
+    pub late: bool,
+}
+
+/// The receiving end of a session.
+pub struct SessionReceiver {
+    params: Params,
+    sk: SecretBuffer,
+    kem_set: ParameterSet,
+    hybrid: bool,
+    /// CK_next.
+    chain: SecretBuffer,
+    next: u64,
+    /// Keys of messages the chain has passed but that have not arrived yet.
+    skipped: BTreeMap<u64, SecretBuffer>,
+    /// Highest sequence number opened so far, if any.
+    newest: Option<u64>,
+    /// Bit `i` set: message `newest - i` has been opened.
+    window: u64,
+}
+
+impl SessionReceiver {
+    /// Decapsulates a session header from [`SessionSender::start`].
+    ///
+    /// # Arguments
+    /// * `header` - The session header JSON.
+    /// * `sk_hex` - The matching secret key, hex or base64 encoded. It is kept
+    ///   for the session's re-keys.
+    /// * `hwid` - The hardware ID the session was bound to, if any.
+    pub fn accept(header: &str, sk_hex: &str, hwid: Option<&[u8]>) -> Result<Self, Box<dyn Error>> {
+        crate::selftest::check()?;
+        let header: Header = serde_json::from_str(header)?;
+        let sid: [u8; SESSION_ID_BYTES] = hex::decode(&header.sid)
+            .ok()
+            .and_then(|sid| sid.try_into().ok())
+            .ok_or("Invalid session ID")?;
+        let x_ct = decode_x_ct(header.xct.as_deref())?;
+        let kem_set = match &header.kem {
+            Some(name) => ParameterSet::from_name(name)
+                .filter(|set| set.name() == name)
+                .ok_or_else(|| format!("Unsupported KEM parameter set '{}'", name))?,
+            None => ParameterSet::default(),
+        };
+        if x_ct.is_some() && kem_set != ParameterSet::MlKem1024 {
+            return Err("Hybrid sessions use ML-KEM-1024".into());
+        }
+        let dem = match &header.dem {
+            Some(name) => crate::dem::ALL
+                .into_iter()
+                .find(|dem| dem.name() == name)
+                .ok_or_else(|| format!("Unsupported DEM '{}'", name))?,
+            None => crate::dem::default_dem(),
+        };
+        let params = Params {
+            sid,
+            dem,
+            salt: hwid.unwrap_or(&[0u8; 32]).to_vec(),
+            rekey_every: header.rk.unwrap_or(0),
+        };
+
+        let sk_len = match x_ct {
+            Some(_) => crate::hybrid::SECRET_KEY_BYTES,
+            None => kem_set.secret_key_bytes(),
+        };
+        let sk = crate::codec::decode_secret(sk_hex, sk_len)
+            .map_err(|e| format!("{} (Arg length: {})", e, sk_hex.len()))?;
+        let ct = decode_ct(&header.ct, kem_set)?;
+        let ss = decapsulate(&sk, &ct, x_ct, kem_set)?;
+
+        let bound = mac_header(
+            &ct,
+            x_ct.as_ref(),
+            header.kem.as_deref(),
+            header.dem.as_deref(),
+        );
+        let chain = params.root(&ss[..], &bound)?;
+        Ok(SessionReceiver {
+            params,
+            sk,
+            kem_set,
+            hybrid: x_ct.is_some(),
+            chain,
+            next: 0,
+            skipped: BTreeMap::new(),
+            newest: None,
+            window: 0,
+        })
+    }
+
+    /// The session ID, hex encoded as in every message.
+    pub fn session_id(&self) -> String {
+        hex::encode(self.params.sid)
+    }
+
+    /// Checks and decrypts one message. Each sequence number opens at most
+    /// once, and its key is wiped afterwards.
+    pub fn open(&mut self, message: &str) -> Result<Opened, Box<dyn Error>> {
+        let message: Message = serde_json::from_str(message)?;
+        if message.sid != self.session_id() {
+            return Err("Message belongs to another session".into());
+        }
+        let seq = message.seq;
+        let late = self.check_replay(seq)?;
+        if message.ct.is_some() != self.params.is_rekey(seq) {
+            return Err(format!("Session message {} has the wrong re-key fields", seq).into());
+        }
+
+        let tag = hex::decode(&message.mac).map_err(|_| "Invalid MAC hex")?;
+        if tag.len() != self.params.dem.tag_bytes() {
+            return Err(format!("Invalid MAC length (Arg length: {})", message.mac.len()).into());
+        }
+        let mut body = crate::codec::decode_hex_vec(&message.data)
+            .map_err(|e| format!("Invalid payload data: {}", e))?;
+
+        // Work on a copy of the chain; nothing changes unless the tag checks out
+        let mut advanced = None;
+        let message_key = if seq < self.next {
+            self.skipped
+                .get(&seq)
+                .ok_or_else(|| format!("Key for session message {} is gone", seq))?
+        } else {
+            advanced = Some(self.advance_to(seq, &message)?);
+            &advanced.as_ref().expect("just set").0
+        };
+        self.params.dem.open(
+            message_key,
+            &self.params.message_header(seq),
+            &mut body,
+            &[],
+            &tag,
+        )?;
+
+        match advanced {
+            Some((_, chain, passed)) => {
+                self.chain = chain;
+                self.next = seq + 1;
+                self.skipped.extend(passed);
+            }
+            None => {
+                self.skipped.remove(&seq);
+            }
+        }
+        self.record(seq);
+        // Keys that fell out of the window can never be used again
+        let oldest = seq.saturating_sub(REPLAY_WINDOW - 1);
+        self.skipped = self.skipped.split_off(&oldest);
+        Ok(Opened {
+            seq,
+            plaintext: body,
+            late,
+        })
+    }
+
+    /// Steps a copy of the chain up to `seq`, re-keying there if it is due.
+    /// Returns MK_seq, CK_seq+1 and the keys of the messages passed on the way.
+    #[allow(clippy::type_complexity)]
+    fn advance_to(
+        &self,
+        seq: u64,
+        message: &Message,
+    ) -> Result<(SecretBuffer, SecretBuffer, Vec<(u64, SecretBuffer)>), String> {
+        if seq - self.next > MAX_SKIP {
+            return Err(format!("Session message {} is too far ahead", seq));
+        }
+        let mut chain = SecretBuffer::from_slice(&self.chain);
+        let mut passed = Vec::new();
+        for n in self.next..seq {
+            if self.params.is_rekey(n) {
+                return Err(format!(
+                    "Session message {} follows re-key message {}, which has not arrived",
+                    seq, n
+                ));
+            }
+            let (message_key, next) = step(&chain)?;
+            if n + REPLAY_WINDOW > seq {
+                passed.push((n, message_key));
+            }
+            chain = next;
+        }
+        if let Some(ct_hex) = &message.ct {
+            let ct = decode_ct(ct_hex, self.kem_set)?;
+            let x_ct = decode_x_ct(message.xct.as_deref())?;
+            if x_ct.is_some() != self.hybrid {
+                return Err(format!(
+                    "Session message {} has the wrong re-key fields",
+                    seq
+                ));
+            }
+            let ss = decapsulate(&self.sk, &ct, x_ct, self.kem_set)?;
+            chain = self.params.rekey(&chain, &ss[..], &ct, x_ct.as_ref())?;
+        }
+        let (message_key, next) = step(&chain)?;
+        Ok((message_key, next, passed))
+    }
+
+    /// Rejects duplicates and messages behind the window; returns whether
+    /// `seq` is late.
+    fn check_replay(&self, seq: u64) -> Result<bool, String> {
+        let Some(newest) = self.newest else {
+            return Ok(false);
+        };
+        if seq > newest {
+            return Ok(false);
+        }
+        let age = newest - seq;
+        if age >= REPLAY_WINDOW {
+            return Err(format!(
+                "Session message {} is outside the replay window",
+                seq
+            ));
+        }
+        if self.window & (1 << age) != 0 {
+            return Err(format!("Duplicate session message {}", seq));
+        }
+        Ok(true)
+    }
+
+    fn record(&mut self, seq: u64) {
+        match self.newest {
+            Some(newest) if seq <= newest => self.window |= 1 << (newest - seq),
+            Some(newest) => {
+                let shift = seq - newest;
+                self.window = if shift >= REPLAY_WINDOW {
+                    1
+                } else {
+                    (self.window << shift) | 1
+                };
+                self.newest = Some(seq);
+            }
+            None => {
+                self.window = 1;
+                self.newest = Some(seq);
+            }
+        }
+    }
+}
+
+pub(crate) fn decode_ct(ct_hex: &str, set: ParameterSet) -> Result<Vec<u8>, String> {
+    let ct = hex::decode(ct_hex).map_err(|_| "Invalid ciphertext hex".to_string())?;
+    if ct.len() != set.ciphertext_bytes() {
+        return Err(format!(
+            "Invalid ciphertext length (Arg length: {})",
+            ct_hex.len()
+        ));
+    }
+    Ok(ct)
+}
+
+pub(crate) fn decode_x_ct(x_ct_hex: Option<&str>) -> Result<Option<[u8; 32]>, String> {
+    x_ct_hex
+        .map(|x| {
+            hex::decode(x)
+                .ok()
+                .and_then(|x| x.try_into().ok())
+                .ok_or_else(|| "Invalid X25519 ciphertext".to_string())
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(dem: &'static dyn Dem, rekey_every: u64) -> (SessionSender, SessionReceiver) {
+        let (pk, sk) = crate::kem::keypair().unwrap();
+        let (tx, header) = SessionSender::start(&hex::encode(pk), None, dem, rekey_every).unwrap();
+        let rx = SessionReceiver::accept(&header, &hex::encode(&sk[..]), None).unwrap();
+        (tx, rx)
+    }
+
+    #[test]
+    fn messages_round_trip_without_a_kem_ciphertext() {
+        for dem in crate::dem::ALL {
+            let (mut tx, mut rx) = pair(dem, 0);
+            assert_eq!(tx.session_id(), rx.session_id());
+            for i in 0..5u64 {
+                let msg = tx.seal(format!("line {}", i).as_bytes()).unwrap();
+                assert!(!msg.contains("\"ct\""));
+                let opened = rx.open(&msg).unwrap();
+                assert_eq!((opened.seq, opened.late), (i, false));
+                assert_eq!(&opened.plaintext[..], format!("line {}", i).as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn duplicates_and_stale_messages_are_rejected() {
+        let (mut tx, mut rx) = pair(crate::dem::default_dem(), 0);
+        let msgs: Vec<String> = (0..70).map(|i| tx.seal(&[i]).unwrap()).collect();
+
+        rx.open(&msgs[1]).unwrap();
+        let err = rx.open(&msgs[1]).err().unwrap();
+        assert_eq!(err.to_string(), "Duplicate session message 1");
+        // Late but inside the window
+        let opened = rx.open(&msgs[0]).unwrap();
+        assert!(opened.late);
+        assert!(rx.open(&msgs[0]).is_err());
+
+        rx.open(&msgs[69]).unwrap();
+        assert!(rx.open(&msgs[10]).unwrap().late);
+        let err = rx.open(&msgs[2]).err().unwrap();
+        assert!(err.to_string().contains("outside the replay window"));
+    }
+
+    #[test]
+    fn used_and_expired_message_keys_are_wiped() {
+        let (mut tx, mut rx) = pair(crate::dem::default_dem(), 0);
+        let msgs: Vec<String> = (0..100).map(|i| tx.seal(&[i]).unwrap()).collect();
+
+        rx.open(&msgs[3]).unwrap();
+        assert_eq!(rx.skipped.keys().copied().collect::<Vec<_>>(), [0, 1, 2]);
+        rx.open(&msgs[1]).unwrap();
+        assert_eq!(rx.skipped.keys().copied().collect::<Vec<_>>(), [0, 2]);
+
+        // Only keys still inside the window are kept across a jump
+        rx.open(&msgs[99]).unwrap();
+        assert_eq!(rx.skipped.len(), (REPLAY_WINDOW - 1) as usize);
+        assert_eq!(rx.skipped.keys().next(), Some(&(99 - REPLAY_WINDOW + 1)));
+    }
+
+    #[test]
+    fn rekeys_carry_a_fresh_encapsulation() {
+        let (mut tx, mut rx) = pair(&crate::dem::Aes256Gcm, 3);
+        let msgs: Vec<String> = (0..8).map(|i| tx.seal(&[i]).unwrap()).collect();
+        for (i, msg) in msgs.iter().enumerate() {
+            assert_eq!(msg.contains("\"ct\""), i == 3 || i == 6, "message {}", i);
+        }
+
+        rx.open(&msgs[0]).unwrap();
+        // Message 4 needs the re-key carried by message 3
+        let err = rx.open(&msgs[4]).err().unwrap();
+        assert!(err.to_string().contains("re-key message 3"));
+        rx.open(&msgs[2]).unwrap();
+        rx.open(&msgs[3]).unwrap();
+        rx.open(&msgs[4]).unwrap();
+        rx.open(&msgs[1]).unwrap();
+        for msg in &msgs[5..] {
+            rx.open(msg).unwrap();
+        }
+
+        // A re-key ciphertext that is swapped or dropped fails the tag
+        let (mut tx, mut rx) = pair(&crate::dem::Aes256Gcm, 1);
+        tx.seal(b"0").unwrap();
+        let real = tx.seal(b"1").unwrap();
+        let (mut tx2, _) = pair(&crate::dem::Aes256Gcm, 1);
+        tx2.seal(b"0").unwrap();
+        let other = tx2.seal(b"1").unwrap();
+        let ct = |m: &str| serde_json::from_str::<Message>(m).unwrap().ct.unwrap();
+        let swapped = real.replace(&ct(&real), &ct(&other));
+        assert!(rx.open(&swapped).is_err());
+        let v: serde_json::Value = serde_json::from_str(&real).unwrap();
+        let mut dropped = v.clone();
+        dropped.as_object_mut().unwrap().remove("ct");
+        assert!(rx.open(&dropped.to_string()).is_err());
+        assert_eq!(&rx.open(&real).unwrap().plaintext[..], b"1");
+    }
+
+    #[test]
+    fn forged_messages_leave_the_state_alone() {
+        let (mut tx, mut rx) = pair(&crate::dem::ChaCha20Poly1305, 0);
+        let msg = tx.seal(b"real").unwrap();
+
+        // Renumbering breaks the tag, and does not burn the sequence number
+        let moved = msg.replace("\"seq\":0", "\"seq\":1");
+        let err = rx.open(&moved).err().unwrap();
+        assert_eq!(err.to_string(), "Integrity Check Failed");
+        assert_eq!((rx.newest, rx.next), (None, 0));
+        assert!(rx.skipped.is_empty());
+        assert_eq!(&rx.open(&msg).unwrap().plaintext[..], b"real");
+
+        // A message from another session is refused outright
+        let (mut other, _) = pair(&crate::dem::ChaCha20Poly1305, 0);
+        assert!(rx.open(&other.seal(b"x").unwrap()).is_err());
+        let far = tx
+            .seal(b"x")
+            .unwrap()
+            .replace("\"seq\":1", "\"seq\":999999");
+        assert!(rx
+            .open(&far)
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("too far ahead"));
+    }
+
+    #[test]
+    fn tampered_header_fails_on_the_first_message() {
+        let (pk, sk) = crate::kem::keypair().unwrap();
+        let (mut tx, header) =
+            SessionSender::start(&hex::encode(pk), None, &crate::dem::Aes256Gcm, 16).unwrap();
+        let sk = hex::encode(&sk[..]);
+        for relabeled in [
+            header.replace("aes-256-gcm", "chacha20-poly1305"),
+            header.replace("\"rk\":16", "\"rk\":17"),
+        ] {
+            let mut rx = SessionReceiver::accept(&relabeled, &sk, None).unwrap();
+            assert!(rx.open(&tx.seal(b"x").unwrap()).is_err());
+        }
+    }
+}
@@ -0,0 +1,150 @@
+/*
+ * D-ASP (ASP Cascade 16)
+ * Implementation: Rust (Reference "Gold" Implementation)
+ *
+ * JavaScript API (wasm-bindgen)
+ *
+ * Built with `--features js` on `wasm32-unknown-unknown` and post-processed by
+ * `wasm-bindgen`, which emits the JS glue and a `.d.ts`:
+ *
+ *   keygen(kemLevel?: number): Keypair
+ *   keygenHybrid(): Keypair
+ *   encrypt(payload: string, pk: string, hwid?: Uint8Array): string
+ *   encryptBytes(payload: Uint8Array, pk: string, hwid?: Uint8Array): string
+ *   decrypt(envelope: string, sk: string, hwid?: Uint8Array, ttlSecs?: number): string
+ *   decryptBytes(envelope: string, sk: string, hwid?: Uint8Array, ttlSecs?: number): Uint8Array
+ *
+ * Failures throw an `Error` carrying the engine message. Secret keys and
+ * payloads are passed by value so their copies in linear memory are wiped
+ * when dropped, and plaintext is copied straight from a wiped-on-drop buffer
+ * into a JS value instead of through a freed (unwiped) Rust allocation.
+ *
+ * The raw pointer/length exports in `wasm_bindings` stay available for hosts
+ * without wasm-bindgen.
+ */
+
+use crate::engine::DarkstarCrypt;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+/// An ML-KEM keypair, hex encoded. Call `free()` to wipe it from WASM memory.
+#[wasm_bindgen]
+pub struct Keypair {
+    pk: String,
+    sk: Zeroizing<String>,
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    #[wasm_bindgen(getter, js_name = publicKey)]
+    pub fn public_key(&self) -> js_sys::JsString {
+        js_sys::JsString::from(self.pk.as_str())
+    }
+
+    #[wasm_bindgen(getter, js_name = secretKey)]
+    pub fn secret_key(&self) -> js_sys::JsString {
+        js_sys::JsString::from(self.sk.as_str())
+    }
+}
+
+fn js_error(e: impl std::fmt::Display) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+/// Generates a keypair after the power-on and pairwise self-tests. `kemLevel`
+/// picks ML-KEM-512, 768 or 1024 (the default); envelopes record the set.
+#[wasm_bindgen]
+pub fn keygen(
+    #[wasm_bindgen(js_name = kemLevel)] kem_level: Option<u32>,
+) -> Result<Keypair, JsError> {
+    let set = match kem_level {
+        Some(level) => crate::kem::ParameterSet::from_name(&level.to_string())
+            .ok_or_else(|| js_error(format!("Unsupported ML-KEM parameter set: {}", level)))?,
+        None => crate::kem::ParameterSet::default(),
+    };
+    let (pk, sk) = set.keypair().map_err(js_error)?;
+    Ok(Keypair {
+        pk: hex::encode(pk),
+        sk: crate::codec::encode_hex(&sk),
+    })
+}
+
+/// Generates an X25519 + ML-KEM-1024 hybrid keypair. `encrypt` switches to
+/// the hybrid KEM when given its public key.
+#[wasm_bindgen(js_name = keygenHybrid)]
+pub fn keygen_hybrid() -> Result<Keypair, JsError> {
+    let (pk, sk) = crate::hybrid::keypair().map_err(js_error)?;
+    Ok(Keypair {
+        pk: hex::encode(pk),
+        sk: crate::codec::encode_hex(&sk[..]),
+    })
+}
+
+fn hwids(hwid: Option<Box<[u8]>>) -> Zeroizing<Vec<Vec<u8>>> {
+    Zeroizing::new(hwid.map(|h| vec![h.into_vec()]).unwrap_or_default())
+}
+
+/// Encrypts a string; the envelope is the same JSON the native engine emits.
+#[wasm_bindgen]
+pub fn encrypt(payload: String, pk: &str, hwid: Option<Box<[u8]>>) -> Result<String, JsError> {
+    let payload = Zeroizing::new(payload);
+    DarkstarCrypt::new()
+        .encrypt(&payload, pk, &hwids(hwid), false)
+        .map_err(js_error)
+}
+
+/// Encrypts arbitrary bytes.
+#[wasm_bindgen(js_name = encryptBytes)]
+pub fn encrypt_bytes(
+    payload: Box<[u8]>,
+    pk: &str,
+    hwid: Option<Box<[u8]>>,
+) -> Result<String, JsError> {
+    let payload = Zeroizing::new(payload);
+    DarkstarCrypt::new()
+        .encrypt_bytes(&payload, pk, &hwids(hwid), false)
+        .map_err(js_error)
+}
+
+/// Decrypts an envelope whose payload is UTF-8 text.
+#[wasm_bindgen]
+pub fn decrypt(
+    envelope: &str,
+    sk: String,
+    hwid: Option<Box<[u8]>>,
+    #[wasm_bindgen(js_name = ttlSecs)] ttl_secs: Option<u32>,
+) -> Result<js_sys::JsString, JsError> {
+    let pt = open(envelope, sk, hwid, ttl_secs)?;
+    let text = std::str::from_utf8(&pt).map_err(js_error)?;
+    Ok(js_sys::JsString::from(text))
+}
+
+/// Decrypts an envelope into raw bytes.
+#[wasm_bindgen(js_name = decryptBytes)]
+pub fn decrypt_bytes(
+    envelope: &str,
+    sk: String,
+    hwid: Option<Box<[u8]>>,
+    #[wasm_bindgen(js_name = ttlSecs)] ttl_secs: Option<u32>,
+) -> Result<js_sys::Uint8Array, JsError> {
+    let pt = open(envelope, sk, hwid, ttl_secs)?;
+    Ok(js_sys::Uint8Array::from(&pt[..]))
+}
+
+fn open(
+    envelope: &str,
+    sk: String,
+    hwid: Option<Box<[u8]>>,
+    ttl_secs: Option<u32>,
+) -> Result<Zeroizing<Vec<u8>>, JsError> {
+    let sk = Zeroizing::new(sk);
+    let hwid = hwid.map(Zeroizing::new);
+    DarkstarCrypt::new()
+        .decrypt_bytes(
+            envelope,
+            &sk,
+            hwid.as_deref().map(|h| &h[..]),
+            ttl_secs.map(u64::from),
+        )
+        .map_err(js_error)
+}
@@ -1,35 +1,75 @@
 use crate::engine::DarkstarCrypt;
 use getrandom::{register_custom_getrandom, Error};
 
-extern "C" {
-    fn host_getrandom(ptr: *mut u8, len: usize);
-}
-
+// `entropy::fill` health-tests every byte drawn through here, so a stuck or
+// constant host is caught even when `host_getrandom` reports success.
 fn custom_getrandom(buf: &mut [u8]) -> Result<(), Error> {
-    unsafe {
-        host_getrandom(buf.as_mut_ptr(), buf.len());
-    }
-    Ok(())
+    crate::host::random_fill(buf).map_err(|_| Error::from(HOST_RANDOM_FAILED))
 }
 
+const HOST_RANDOM_FAILED: core::num::NonZeroU32 =
+    match core::num::NonZeroU32::new(Error::CUSTOM_START + 1) {
+        Some(code) => code,
+        None => unreachable!(),
+    };
+
 register_custom_getrandom!(custom_getrandom);
 
+// Panics cannot be caught on wasm32 (they trap), so these exports validate
+// every pointer and size up front and report failures as values instead.
+
+/// Allocates `size` zeroed bytes, or returns NULL if the allocation fails.
 #[no_mangle]
 pub extern "C" fn wasm_alloc(size: usize) -> *mut u8 {
-    let mut buf = vec![0u8; size];
-    buf.shrink_to_fit();
-    let ptr = buf.as_mut_ptr();
-    std::mem::forget(buf);
-    ptr
+    let mut buf = Vec::new();
+    if buf.try_reserve_exact(size).is_err() {
+        return std::ptr::null_mut();
+    }
+    buf.resize(size, 0u8);
+    Box::into_raw(buf.into_boxed_slice()) as *mut u8
 }
 
+/// Frees a buffer from [`wasm_alloc`] or a result string (`size` then counts
+/// the trailing NUL). The contents are wiped first, since they are usually
+/// keys or plaintext.
+///
+/// Both are handed out as boxed slices, so `size` is the whole allocation and
+/// no capacity has to be recovered.
 #[no_mangle]
 pub extern "C" fn wasm_dealloc(ptr: *mut u8, size: usize) {
-    unsafe {
-        let _ = Vec::from_raw_parts(ptr, size, size);
+    if ptr.is_null() {
+        return;
+    }
+    let mut buf = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, size)) };
+    zeroize::Zeroize::zeroize(&mut buf[..]);
+}
+
+/// Borrows a host buffer; a zero length may pass NULL.
+fn host_bytes<'a>(ptr: *const u8, len: usize, what: &str) -> Result<&'a [u8], String> {
+    if len == 0 {
+        Ok(&[])
+    } else if ptr.is_null() || len > isize::MAX as usize {
+        Err(format!("{} is not a valid buffer", what))
+    } else {
+        Ok(unsafe { std::slice::from_raw_parts(ptr, len) })
     }
 }
 
+/// Borrows a host buffer as UTF-8, rejecting invalid input instead of trusting it.
+fn host_str<'a>(ptr: *const u8, len: usize, what: &str) -> Result<&'a str, String> {
+    std::str::from_utf8(host_bytes(ptr, len, what)?)
+        .map_err(|_| format!("{} is not valid UTF-8", what))
+}
+
+/// Hands a result to the host as a NUL-terminated string; errors become
+/// `{"error":"..."}` with the message JSON-escaped.
+fn into_host_string(result: Result<String, String>) -> *mut u8 {
+    let s = result.unwrap_or_else(|e| serde_json::json!({ "error": e }).to_string());
+    let mut buf = s.into_bytes();
+    buf.push(0);
+    Box::into_raw(buf.into_boxed_slice()) as *mut u8
+}
+
 #[no_mangle]
 pub extern "C" fn wasm_encrypt(
     payload_ptr: *const u8,
@@ -40,30 +80,19 @@ pub extern "C" fn wasm_encrypt(
     hwid_len: usize,
     telemetry: u32,
 ) -> *mut u8 {
-    let payload = unsafe {
-        std::str::from_utf8_unchecked(std::slice::from_raw_parts(payload_ptr, payload_len))
-    };
-    let pk_hex =
-        unsafe { std::str::from_utf8_unchecked(std::slice::from_raw_parts(pk_ptr, pk_len)) };
-
-    let hwid = if hwid_len > 0 {
-        Some(unsafe { std::slice::from_raw_parts(hwid_ptr, hwid_len) }.to_vec())
-    } else {
-        None
-    };
-
-    let dc = DarkstarCrypt::new();
-    let result = match dc.encrypt(payload, pk_hex, hwid, telemetry != 0) {
-        Ok(json) => json,
-        Err(e) => format!("{{\"error\":\"{}\"}}", e),
-    };
-
-    let mut buf = result.into_bytes();
-    buf.push(0);
-    buf.shrink_to_fit();
-    let ptr = buf.as_mut_ptr();
-    std::mem::forget(buf);
-    ptr
+    into_host_string((|| {
+        let hwid = host_bytes(hwid_ptr, hwid_len, "HWID")?;
+        let hwids = if hwid.is_empty() {
+            Vec::new()
+        } else {
+            vec![hwid.to_vec()]
+        };
+        let payload = host_str(payload_ptr, payload_len, "Payload")?;
+        let pk_hex = host_str(pk_ptr, pk_len, "Public key")?;
+        DarkstarCrypt::new()
+            .encrypt(payload, pk_hex, &hwids, telemetry != 0)
+            .map_err(|e| e.to_string())
+    })())
 }
 
 #[no_mangle]
@@ -76,27 +105,55 @@ pub extern "C" fn wasm_decrypt(
     hwid_len: usize,
     telemetry: u32,
 ) -> *mut u8 {
-    let payload =
-        unsafe { std::str::from_utf8_unchecked(std::slice::from_raw_parts(data_ptr, data_len)) };
-    let sk_hex =
-        unsafe { std::str::from_utf8_unchecked(std::slice::from_raw_parts(sk_ptr, sk_len)) };
-
-    let hwid = if hwid_len > 0 {
-        Some(unsafe { std::slice::from_raw_parts(hwid_ptr, hwid_len) }.to_vec())
-    } else {
-        None
-    };
+    decrypt_with(
+        data_ptr, data_len, sk_ptr, sk_len, hwid_ptr, hwid_len, telemetry, None,
+    )
+}
 
-    let dc = DarkstarCrypt::new();
-    let result = match dc.decrypt(payload, sk_hex, hwid, telemetry != 0, None) {
-        Ok(res) => res,
-        Err(e) => format!("{{\"error\":\"{}\"}}", e),
-    };
+/// [`wasm_decrypt`] with replay protection: fails if the envelope's `ts` is
+/// more than `ttl_secs` older than the host wall clock.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn wasm_decrypt_ttl(
+    data_ptr: *const u8,
+    data_len: usize,
+    sk_ptr: *const u8,
+    sk_len: usize,
+    hwid_ptr: *const u8,
+    hwid_len: usize,
+    telemetry: u32,
+    ttl_secs: u32,
+) -> *mut u8 {
+    decrypt_with(
+        data_ptr,
+        data_len,
+        sk_ptr,
+        sk_len,
+        hwid_ptr,
+        hwid_len,
+        telemetry,
+        Some(ttl_secs as u64),
+    )
+}
 
-    let mut buf = result.into_bytes();
-    buf.push(0);
-    buf.shrink_to_fit();
-    let ptr = buf.as_mut_ptr();
-    std::mem::forget(buf);
-    ptr
+#[allow(clippy::too_many_arguments)]
+fn decrypt_with(
+    data_ptr: *const u8,
+    data_len: usize,
+    sk_ptr: *const u8,
+    sk_len: usize,
+    hwid_ptr: *const u8,
+    hwid_len: usize,
+    telemetry: u32,
+    ttl_secs: Option<u64>,
+) -> *mut u8 {
+    into_host_string((|| {
+        let hwid = host_bytes(hwid_ptr, hwid_len, "HWID")?;
+        let hwid = (!hwid.is_empty()).then(|| hwid.to_vec());
+        let payload = host_str(data_ptr, data_len, "Envelope")?;
+        let sk_hex = host_str(sk_ptr, sk_len, "Secret key")?;
+        DarkstarCrypt::new()
+            .decrypt(payload, sk_hex, hwid, telemetry != 0, ttl_secs)
+            .map_err(|e| e.to_string())
+    })())
 }
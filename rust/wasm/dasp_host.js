@@ -0,0 +1,63 @@
+/*
+ * D-ASP (ASP Cascade 16) - WASM host imports
+ *
+ * Reference implementation of the `env` imports the wasm32-unknown-unknown
+ * build of the Rust engine expects (see `rust/src/host.rs`). Works in browsers
+ * and in Node.js 19+ (global `crypto` and `performance`).
+ *
+ *   host_getrandom(ptr, len) -> f64   fill `len` bytes of linear memory at
+ *                                     `ptr` from a CSPRNG; 0 = success
+ *   host_unix_time_ms() -> f64        wall clock, ms since the Unix epoch;
+ *                                     used for envelope `ts` and TTL checks
+ *   host_gettime_us() -> f64          monotonic clock in microseconds;
+ *                                     used for telemetry only
+ *
+ * Only an explicit 0 from host_getrandom is success. Anything else, including
+ * `undefined` from a function that returns nothing (it reaches the engine as
+ * NaN), makes the engine call fail with an error instead of producing a weak
+ * key; so does a NaN or negative time instead of an unchecked timestamp.
+ * Usage:
+ *
+ *   const host = createHostImports(() => instance.exports.memory);
+ *   const { instance } = await WebAssembly.instantiate(bytes, host);
+ *
+ * `options.randomFill(buf)` replaces crypto.getRandomValues: it must fill the
+ * Uint8Array and return the integer 0. Any other return value, or a throw, is
+ * reported to the engine as a failure.
+ */
+
+// crypto.getRandomValues refuses requests above 64 KiB
+const MAX_RANDOM_CHUNK = 65536;
+
+function defaultRandomFill(buf) {
+  for (let off = 0; off < buf.length; off += MAX_RANDOM_CHUNK) {
+    globalThis.crypto.getRandomValues(buf.subarray(off, off + MAX_RANDOM_CHUNK));
+  }
+  return 0;
+}
+
+function createHostImports(getMemory, options = {}) {
+  const randomFill = options.randomFill || defaultRandomFill;
+  return {
+    env: {
+      host_getrandom(ptr, len) {
+        try {
+          const buf = new Uint8Array(getMemory().buffer, ptr >>> 0, len >>> 0);
+          return randomFill(buf) === 0 ? 0 : 1;
+        } catch (e) {
+          return 1;
+        }
+      },
+      host_unix_time_ms() {
+        return Date.now();
+      },
+      host_gettime_us() {
+        return performance.now() * 1000;
+      },
+    },
+  };
+}
+
+if (typeof module !== 'undefined') {
+  module.exports = { createHostImports };
+}